@@ -2,11 +2,11 @@
 
 use std::process;
 
-use circadianlight::{cli::Program, environment::with_os_graphical_env};
+use circadianlight::cli::Program;
 use structopt::StructOpt;
 
 fn main() {
-    if let Err(error) = with_os_graphical_env(Program::from_args()) {
+    if let Err(error) = Program::from_args().run() {
         eprintln!("{}", error);
         process::exit(-1);
     }
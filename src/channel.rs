@@ -1,8 +1,17 @@
 //! Utilites for color channels.
 
+use chrono::{DateTime, FixedOffset};
+
 use crate::{
-    config::{ChannelConfig, Config, HourConfig},
-    hour::DayPhase,
+    config::{
+        ChannelConfig,
+        Config,
+        HourConfig,
+        ScheduleSource,
+    },
+    curve::TransitionCurve,
+    hour::{timelike_to_hours, DayPhase},
+    schedule::Interpolate,
 };
 
 /// Index of the red color channel, never intended to change.
@@ -12,6 +21,55 @@ pub const GREEN: usize = 1;
 /// Index of the blue color channel, never intended to change.
 pub const BLUE: usize = 2;
 
+/// Adjustment of the screen colors, made of the gamma of the color channels,
+/// of the brightness and of the base gamma.
+#[derive(Debug, Clone, Copy, PartialEq, PartialOrd)]
+pub struct ColorAdjustment {
+    /// Gamma of the color channels, in the order: red, green, blue. Each one
+    /// is a multiplier over the output of its channel, such that `0.5` halves
+    /// it.
+    pub gamma: [f64; 3],
+    /// Brightness, applied as a multiplier over the color channels, in the
+    /// interval `(0,1]`.
+    pub brightness: f64,
+    /// Exponent shaping the curve of every color channel before the
+    /// multipliers, as given to `xrandr --gamma`, where `1.0` keeps the curve
+    /// straight.
+    pub base_gamma: f64,
+}
+
+impl Default for ColorAdjustment {
+    fn default() -> Self {
+        Self { gamma: [1.0; 3], brightness: 1.0, base_gamma: 1.0 }
+    }
+}
+
+impl ColorAdjustment {
+    /// This adjustment with the gamma of each color channel multiplied by the
+    /// given calibration, in the order: red, green, blue.
+    pub fn calibrated(self, calibration: [f64; 3]) -> Self {
+        let mut gamma = self.gamma;
+        for (channel, multiplier) in gamma.iter_mut().zip(calibration) {
+            *channel *= multiplier;
+        }
+        Self { gamma, ..self }
+    }
+}
+
+impl Interpolate for ColorAdjustment {
+    fn interpolate(self, other: Self, scale: f64) -> Self {
+        let mut gamma = self.gamma;
+        for (channel, target) in gamma.iter_mut().zip(other.gamma) {
+            *channel = channel.interpolate(target, scale);
+        }
+        Self {
+            gamma,
+            brightness: self.brightness.interpolate(other.brightness, scale),
+            base_gamma: self.base_gamma.interpolate(other.base_gamma, scale),
+        }
+    }
+}
+
 /// Maps channels of different types.
 pub fn map_channel_vector<F, T, U>(input: [T; 3], mut mapper: F) -> [U; 3]
 where
@@ -21,11 +79,12 @@ where
     [mapper(input[0]), mapper(input[1]), mapper(input[2])]
 }
 
-/// Creates a linear channel function, where linear refers that the dusk is
-/// processed with a linear function.
-pub fn linear_channel_function(
+/// Creates a channel function, where the dawn and the dusk are processed with
+/// the given transition curve.
+pub fn channel_function(
     channel_config: ChannelConfig,
     hour_config: HourConfig,
+    curve: TransitionCurve,
 ) -> impl Fn(f64) -> f64 + Copy + Send + Sync + 'static {
     let min = channel_config.min();
     let max = channel_config.max();
@@ -33,31 +92,133 @@ pub fn linear_channel_function(
         hour_config,
         current_hour,
     ) {
+        DayPhase::Dawn(scale) => min + (max - min) * curve.apply(scale),
         DayPhase::Day => max,
+        DayPhase::Dusk(scale) => {
+            min + (max - min) * (1.0 - curve.apply(scale))
+        },
         DayPhase::Night => min,
-        DayPhase::Dusk(scale) => min + (max - min) * (1.0 - scale),
     }
 }
 
-/// Creates a gamma function that adapts to the hour of the day.
+/// Creates a linear channel function, where linear refers that the dawn and the
+/// dusk are processed with a linear function.
+pub fn linear_channel_function(
+    channel_config: ChannelConfig,
+    hour_config: HourConfig,
+) -> impl Fn(f64) -> f64 + Copy + Send + Sync + 'static {
+    channel_function(channel_config, hour_config, TransitionCurve::Linear)
+}
+
+/// Creates a gamma function that adapts to the date and hour of the day,
+/// following the schedule source of the given configuration.
 pub fn gamma_function(
-    config: Config,
-) -> impl Fn(f64) -> [f64; 3] + Copy + Send + Sync + 'static {
-    move |current_hour| {
-        map_channel_vector(config.channels, |channel_config| {
-            linear_channel_function(channel_config, config.hours)(current_hour)
-        })
+    config: &Config,
+) -> impl Fn(DateTime<FixedOffset>) -> [f64; 3] + Clone + Send + Sync + 'static
+{
+    let config = config.clone();
+    move |time| match &config.source {
+        ScheduleSource::Elevation(elevation_config) => {
+            let scale = config.curve.apply(elevation_config.daylight(&time));
+            let target =
+                config.night_target().interpolate(config.day_target(), scale);
+            target.gamma()
+        },
+        _ => config
+            .schedule_on(time.date_naive(), *time.offset())
+            .map_or([1.0; 3], |schedule| {
+                schedule.gamma_at(timelike_to_hours(&time))
+            }),
+    }
+}
+
+/// Creates a brightness function that adapts to the date and hour of the day,
+/// following the brightness schedule of the given configuration.
+pub fn brightness_function(
+    config: &Config,
+) -> impl Fn(DateTime<FixedOffset>) -> f64 + Clone + Send + Sync + 'static {
+    let config = config.clone();
+    move |time| match (&config.source, &config.brightness_schedule) {
+        (ScheduleSource::Elevation(elevation_config), None) => {
+            let scale = config.curve.apply(elevation_config.daylight(&time));
+            let brightness = config.brightness;
+            brightness.night().interpolate(brightness.day(), scale)
+        },
+        _ => config
+            .brightness_schedule_on(time.date_naive(), *time.offset())
+            .map_or(1.0, |schedule| {
+                schedule.target_at(timelike_to_hours(&time))
+            }),
+    }
+}
+
+/// Creates a function of the whole color adjustment that adapts to the date
+/// and hour of the day, following the given configuration.
+pub fn adjustment_function(
+    config: &Config,
+) -> impl Fn(DateTime<FixedOffset>) -> ColorAdjustment
+       + Clone
+       + Send
+       + Sync
+       + 'static {
+    let gamma = gamma_function(config);
+    let brightness = brightness_function(config);
+    let base_gamma = config.ramp.base_gamma();
+    move |time| ColorAdjustment {
+        gamma: gamma(time),
+        brightness: brightness(time),
+        base_gamma,
     }
 }
 
 #[cfg(test)]
 mod test {
-    use crate::config::{ChannelConfig, HourConfig};
+    use chrono::{FixedOffset, TimeZone};
+
+    use crate::{
+        config::{
+            BrightnessConfig,
+            ChannelConfig,
+            Config,
+            ElevationConfig,
+            HourConfig,
+            ScheduleSource,
+        },
+        curve::TransitionCurve,
+        solar::Location,
+    };
 
-    use super::linear_channel_function;
+    use super::{
+        brightness_function,
+        channel_function,
+        gamma_function,
+        linear_channel_function,
+        ColorAdjustment,
+    };
 
     const EPSILON: f64 = 0.01;
 
+    #[test]
+    fn linear_channel_function_on_dawn() {
+        let channel = linear_channel_function(
+            ChannelConfig::new(0.4, 0.9).unwrap(),
+            HourConfig::default(),
+        )(4.5 / 24.0);
+        assert!((channel - 0.65).abs() < EPSILON);
+
+        let channel = linear_channel_function(
+            ChannelConfig::new(0.4, 0.9).unwrap(),
+            HourConfig::default(),
+        )(4.0 / 24.0);
+        assert!((channel - 0.4).abs() < EPSILON);
+
+        let channel = linear_channel_function(
+            ChannelConfig::new(1.0, 1.0).unwrap(),
+            HourConfig::default(),
+        )(4.5 / 24.0);
+        assert!((channel - 1.0).abs() < EPSILON);
+    }
+
     #[test]
     fn linear_channel_function_on_day() {
         let channel = linear_channel_function(
@@ -126,4 +287,79 @@ mod test {
         )(1.0 / 24.0);
         assert!((channel - 0.8).abs() < EPSILON);
     }
+
+    #[test]
+    fn eased_channel_function_on_dusk() {
+        let linear = linear_channel_function(
+            ChannelConfig::new(0.4, 0.9).unwrap(),
+            HourConfig::default(),
+        );
+        let eased = channel_function(
+            ChannelConfig::new(0.4, 0.9).unwrap(),
+            HourConfig::default(),
+            TransitionCurve::Smoothstep,
+        );
+        assert!(eased(17.5 / 24.0) > linear(17.5 / 24.0) + EPSILON);
+        assert!(eased(20.5 / 24.0) < linear(20.5 / 24.0) - EPSILON);
+        assert!((eased(19.0 / 24.0) - linear(19.0 / 24.0)).abs() < EPSILON);
+    }
+
+    #[test]
+    fn gamma_function_follows_the_sun() {
+        let config = Config {
+            channels: [
+                ChannelConfig::new(1.0, 1.0).unwrap(),
+                ChannelConfig::new(0.4, 0.9).unwrap(),
+                ChannelConfig::new(0.4, 0.9).unwrap(),
+            ],
+            curve: TransitionCurve::Linear,
+            source: ScheduleSource::Elevation(
+                ElevationConfig::new(
+                    Location::new(51.5074, -0.1278).unwrap(),
+                    3.0,
+                    -6.0,
+                )
+                .unwrap(),
+            ),
+            ..Config::default()
+        };
+        let gamma = gamma_function(&config);
+        let offset = FixedOffset::east_opt(3600).unwrap();
+        let time = |hour, minute| {
+            offset.with_ymd_and_hms(2024, 6, 21, hour, minute, 0).unwrap()
+        };
+        assert!((gamma(time(13, 0))[1] - 0.9).abs() < EPSILON);
+        assert!((gamma(time(1, 0))[1] - 0.4).abs() < EPSILON);
+        let sunset = gamma(time(21, 40))[1];
+        assert!(sunset > 0.4 + EPSILON && sunset < 0.9 - EPSILON);
+    }
+
+    #[test]
+    fn brightness_function_follows_day_phases() {
+        let config = Config {
+            brightness: BrightnessConfig::new(1.0, 0.6).unwrap(),
+            ..Config::default()
+        };
+        let brightness = brightness_function(&config);
+        let offset = FixedOffset::east_opt(0).unwrap();
+        let time = |hour| {
+            offset.with_ymd_and_hms(2024, 6, 21, hour, 0, 0).unwrap()
+        };
+        assert!((brightness(time(12)) - 1.0).abs() < EPSILON);
+        assert!((brightness(time(19)) - 0.8).abs() < EPSILON);
+        assert!((brightness(time(23)) - 0.6).abs() < EPSILON);
+    }
+
+    #[test]
+    fn calibrate_adjustment() {
+        let adjustment = ColorAdjustment {
+            gamma: [1.0, 0.8, 0.6],
+            brightness: 0.9,
+            base_gamma: 1.0,
+        };
+        let calibrated = adjustment.calibrated([1.0, 0.9, 0.5]);
+        assert!((calibrated.gamma[1] - 0.72).abs() < EPSILON);
+        assert!((calibrated.gamma[2] - 0.3).abs() < EPSILON);
+        assert_eq!(calibrated.brightness, 0.9);
+    }
 }
@@ -1,106 +1,709 @@
 //! CLI (Command-Line Interface) utilites.
 
-use std::{io, thread, time::Duration};
+use std::{
+    io,
+    path::PathBuf,
+    sync::mpsc::{self, Receiver, Sender},
+    time::{Duration, Instant},
+};
 
 use chrono::{Local, NaiveTime};
 use structopt::StructOpt;
 
 use crate::{
-    channel::{self, gamma_function},
+    channel::{self, adjustment_function, ColorAdjustment},
     config::{
+        file::{self, ConfigFile, Entry, MonitorEntry, Position},
+        BrightnessConfig,
         ChannelConfig,
         Config,
+        ElevationConfig,
+        FadeConfig,
         HourConfig,
-        InvalidChannelBounds,
-        InvalidDayPhases,
+        MonitorConfig,
+        RampConfig,
+        RejectedValue,
+        ScheduleSource,
+        SolarConfig,
+        TemperatureConfig,
+    },
+    control::{self, Command, Reply, Request},
+    curve::TransitionCurve,
+    environment::{
+        self,
+        Backend,
+        BackendError,
+        Backoff,
+        BoxedGraphicalEnv,
+        CapturedGamma,
+        GraphicalEnv,
+        GraphicalEnvContext,
+        Rejection,
     },
-    environment::{GraphicalEnv, GraphicalEnvContext},
+    fade::{self, Fade},
     hour::timelike_to_hours,
+    monitor::Monitor,
+    overrides::{self, Expiry, Override, OverrideTarget},
+    schedule::{Keyframe, Schedule},
+    signal,
+    solar::{Location, Twilight},
+    watch,
 };
 
-/// Common args for configuring the gamma funcion.
+/// Minutes after the sunrise for the day phase start, and before the sunset
+/// for the dusk phase start, when not given.
+const DEFAULT_SOLAR_OFFSET: f64 = 30.0;
+
+/// Sun elevation in degrees above which it is full day, when not given.
+const DEFAULT_DAY_ELEVATION: f64 = 3.0;
+
+/// Sun elevation in degrees below which it is full night, when not given.
+const DEFAULT_NIGHT_ELEVATION: f64 = -6.0;
+
+/// Minimum and maximum of each color channel, in the order: red, green, blue,
+/// when not given.
+const DEFAULT_CHANNELS: [(f64, f64); 3] = [(1.0, 1.0), (0.6, 1.0), (0.3, 1.0)];
+
+/// Common args for configuring the gamma funcion. Values not given are taken
+/// from the configuration file, if any, and then from defaults.
 #[derive(Debug, Clone, StructOpt)]
 pub struct ConfigArgs {
-    /// Minimum red channel value, in the interval `[0,1]`.
+    /// Path of the configuration file in the TOML format. If not given,
+    /// `$XDG_CONFIG_HOME/circadianlight/config.toml` is used if it exists.
+    #[structopt(long = "--config")]
+    #[structopt(parse(from_os_str))]
+    config: Option<PathBuf>,
+    /// Name of the profile of the configuration file to be used. If not
+    /// given, the profile selected by the file is used, if any.
+    #[structopt(long = "--profile")]
+    #[structopt(short = "-p")]
+    profile: Option<String>,
+    /// Minimum red channel value, in the interval `[0,1]` (1.0 by default).
     #[structopt(long = "--min-red")]
     #[structopt(short = "-r")]
-    #[structopt(default_value = "1.0")]
-    min_red: f64,
-    /// Maximum red channel value, in the interval `[0,1]`.
+    min_red: Option<f64>,
+    /// Maximum red channel value, in the interval `[0,1]` (1.0 by default).
     #[structopt(long = "--max-red")]
     #[structopt(short = "-R")]
-    #[structopt(default_value = "1.0")]
-    max_red: f64,
-    /// Minimum green channel value, in the interval `[0,1]`.
+    max_red: Option<f64>,
+    /// Minimum green channel value, in the interval `[0,1]` (0.6 by default).
     #[structopt(long = "--min-green")]
     #[structopt(short = "-g")]
-    #[structopt(default_value = "0.6")]
-    min_green: f64,
-    /// Maximum green channel value, in the interval `[0,1]`.
+    min_green: Option<f64>,
+    /// Maximum green channel value, in the interval `[0,1]` (1.0 by default).
     #[structopt(long = "--max-green")]
     #[structopt(short = "-G")]
-    #[structopt(default_value = "1.0")]
-    max_green: f64,
-    /// Minimum blue channel value, in the interval `[0,1]`.
+    max_green: Option<f64>,
+    /// Minimum blue channel value, in the interval `[0,1]` (0.3 by default).
     #[structopt(long = "--min-blue")]
     #[structopt(short = "-b")]
-    #[structopt(default_value = "0.3")]
-    min_blue: f64,
-    /// Maximum blue channel value, in the interval `[0,1]`.
+    min_blue: Option<f64>,
+    /// Maximum blue channel value, in the interval `[0,1]` (1.0 by default).
     #[structopt(long = "--max-blue")]
     #[structopt(short = "-B")]
-    #[structopt(default_value = "1.0")]
-    max_blue: f64,
-    /// Starting hour of the day phase.
+    max_blue: Option<f64>,
+    /// Starting hour of the day phase (05:00 by default).
     #[structopt(long = "--day-start")]
     #[structopt(short = "-d")]
-    #[structopt(default_value = "05:00")]
     #[structopt(parse(try_from_str = parse_time_arg))]
-    day_start: NaiveTime,
-    /// Starting hour of the dusk phase.
+    day_start: Option<NaiveTime>,
+    /// Starting hour of the dusk phase (17:00 by default).
     #[structopt(long = "--dusk-start")]
     #[structopt(short = "-D")]
-    #[structopt(default_value = "17:00")]
     #[structopt(parse(try_from_str = parse_time_arg))]
-    dusk_start: NaiveTime,
-    /// Starting hour of the night phase.
+    dusk_start: Option<NaiveTime>,
+    /// Starting hour of the night phase (21:00 by default).
     #[structopt(long = "--night-start")]
     #[structopt(short = "-n")]
-    #[structopt(default_value = "21:00")]
     #[structopt(parse(try_from_str = parse_time_arg))]
-    night_start: NaiveTime,
+    night_start: Option<NaiveTime>,
+    /// Starting hour of the dawn phase (04:00 by default).
+    #[structopt(long = "--dawn-start")]
+    #[structopt(short = "-a")]
+    #[structopt(parse(try_from_str = parse_time_arg))]
+    dawn_start: Option<NaiveTime>,
+    /// Keyframe of a custom schedule, in the format `H:M=red:green:blue`
+    /// (e.g. `22:00=1.0:0.7:0.5`) or `H:M=KELVINK` (e.g. `22:00=3400K`). It
+    /// can be given multiple times, and if given, day phases and channels
+    /// bounds are ignored, and colors are interpolated between consecutive
+    /// keyframes.
+    #[structopt(long = "--keyframe")]
+    #[structopt(short = "-k")]
+    #[structopt(number_of_values = 1)]
+    #[structopt(parse(try_from_str = parse_keyframe_arg))]
+    keyframes: Vec<Keyframe>,
+    /// Curve of the transitions between phases or keyframes, one of `linear`,
+    /// `smoothstep`, `cosine`, `sigmoid`, `sigmoid:STEEPNESS` (with a
+    /// steepness of at least 0.1) or `exponential` (linear by default).
+    #[structopt(long = "--curve")]
+    #[structopt(short = "-c")]
+    curve: Option<TransitionCurve>,
+    /// Latitude of your location in degrees, positive to the north. If given
+    /// along with the longitude, day phases follow the sunrise, the sunset and
+    /// the twilights, and the given starting hours are ignored.
+    #[structopt(long = "--latitude")]
+    #[structopt(allow_hyphen_values = true)]
+    #[structopt(requires = "longitude")]
+    #[structopt(conflicts_with = "keyframes")]
+    latitude: Option<f64>,
+    /// Longitude of your location in degrees, positive to the east.
+    #[structopt(long = "--longitude")]
+    #[structopt(allow_hyphen_values = true)]
+    #[structopt(requires = "latitude")]
+    longitude: Option<f64>,
+    /// Minutes after the sunrise for the day phase start, and before the sunset
+    /// for the dusk phase start, when following the sun (30 by default).
+    #[structopt(long = "--solar-offset")]
+    solar_offset: Option<f64>,
+    /// Twilight that starts the dawn phase and ends the dusk phase when
+    /// following the sun, either `civil` or `nautical` (civil by default).
+    #[structopt(long = "--twilight")]
+    twilight: Option<Twilight>,
+    /// Follows the sun elevation at your location instead of day phases, such
+    /// that colors are interpolated between the night and the day elevations.
+    #[structopt(long = "--elevation")]
+    elevation: bool,
+    /// Sun elevation in degrees above which it is full day, when following
+    /// the sun elevation (3.0 by default).
+    #[structopt(long = "--day-elevation")]
+    #[structopt(allow_hyphen_values = true)]
+    day_elevation: Option<f64>,
+    /// Sun elevation in degrees below which it is full night, when following
+    /// the sun elevation (-6.0 by default).
+    #[structopt(long = "--night-elevation")]
+    #[structopt(allow_hyphen_values = true)]
+    night_elevation: Option<f64>,
+    /// Color temperature of the day phase in Kelvin. If given along with the
+    /// night temperature, color channels bounds are ignored, and colors are
+    /// interpolated in the temperature space.
+    #[structopt(long = "--day-temperature")]
+    #[structopt(requires = "night-temperature")]
+    day_temperature: Option<f64>,
+    /// Color temperature of the night phase in Kelvin.
+    #[structopt(long = "--night-temperature")]
+    #[structopt(requires = "day-temperature")]
+    night_temperature: Option<f64>,
+    /// Brightness of the day phase, in the interval `(0,1]` (1.0 by default).
+    #[structopt(long = "--day-brightness")]
+    day_brightness: Option<f64>,
+    /// Brightness of the night phase, in the interval `(0,1]` (1.0 by
+    /// default).
+    #[structopt(long = "--night-brightness")]
+    night_brightness: Option<f64>,
+    /// Keyframe of a custom brightness schedule, in the format `H:M=LEVEL`
+    /// (e.g. `22:00=0.7`). It can be given multiple times, and if given, day
+    /// and night brightness are ignored, and brightness is interpolated
+    /// between consecutive keyframes.
+    #[structopt(long = "--brightness-keyframe")]
+    #[structopt(number_of_values = 1)]
+    #[structopt(parse(try_from_str = parse_brightness_keyframe_arg))]
+    brightness_keyframes: Vec<Keyframe<f64>>,
+    /// Duration in seconds of the fade from the current colors to new ones,
+    /// such as on startup or when the profile changes, where zero disables
+    /// fades (2.0 by default).
+    #[structopt(long = "--fade-duration")]
+    fade_duration: Option<f64>,
+    /// Steps per second of a fade (20 by default).
+    #[structopt(long = "--fade-rate")]
+    fade_rate: Option<f64>,
+    /// Exponent shaping the curve of every color channel before the channel
+    /// values and the brightness scale it, as given to `xrandr --gamma`
+    /// (1.0, a straight curve, by default).
+    #[structopt(long = "--base-gamma")]
+    base_gamma: Option<f64>,
 }
 
 impl ConfigArgs {
-    /// Creates an hour configuration from these args.
-    pub fn create_hour_config(&self) -> Result<HourConfig, InvalidDayPhases> {
+    /// Path of the configuration file used by these args, if any: the one
+    /// given explicitly, or the default one if it exists.
+    pub fn config_path(&self) -> Option<PathBuf> {
+        match &self.config {
+            Some(path) => Some(path.clone()),
+            None => file::default_path().filter(|path| path.is_file()),
+        }
+    }
+
+    /// Path of the configuration file that should be watched for changes:
+    /// the one given explicitly, or the default one even if it does not exist
+    /// yet.
+    pub fn watched_config_path(&self) -> Option<PathBuf> {
+        self.config.clone().or_else(file::default_path)
+    }
+
+    /// Loads the configuration file used by these args, if any.
+    pub fn load_config_file(&self) -> io::Result<Option<ConfigFile>> {
+        let profile = self.profile.as_deref();
+        match (self.config_path(), profile) {
+            (Some(path), _) => ConfigFile::load(path, profile).map(Some),
+            (None, Some(profile)) if profile != file::DEFAULT_PROFILE => {
+                Err(io::Error::new(
+                    io::ErrorKind::NotFound,
+                    format!(
+                        "profile {} requires a configuration file",
+                        profile
+                    ),
+                ))
+            },
+            (None, _) => Ok(None),
+        }
+    }
+
+    /// Creates an hour configuration from these args, on top of the given
+    /// configuration file.
+    pub fn create_hour_config(
+        &self,
+        file: Option<&ConfigFile>,
+    ) -> io::Result<HourConfig> {
+        let mut layers = Layers::new(file);
+        let default = HourConfig::default();
+        let hour =
+            |time: Option<NaiveTime>| time.map(|time| timelike_to_hours(&time));
         HourConfig::new(
-            timelike_to_hours(&self.day_start),
-            timelike_to_hours(&self.dusk_start),
-            timelike_to_hours(&self.night_start),
+            layers
+                .pick(hour(self.day_start), |file| &file.day_start)
+                .unwrap_or(default.day_start()),
+            layers
+                .pick(hour(self.dusk_start), |file| &file.dusk_start)
+                .unwrap_or(default.dusk_start()),
+            layers
+                .pick(hour(self.night_start), |file| &file.night_start)
+                .unwrap_or(default.night_start()),
+            layers
+                .pick(hour(self.dawn_start), |file| &file.dawn_start)
+                .unwrap_or(default.dawn_start()),
         )
+        .map_err(|error| layers.invalid(error))
     }
 
-    /// Creates channels' configurations from these args.
+    /// Creates channels' configurations from these args, on top of the given
+    /// configuration file.
     pub fn create_channels_config(
         &self,
-    ) -> Result<[ChannelConfig; 3], InvalidChannelBounds> {
-        Ok([
-            ChannelConfig::new(self.min_red, self.max_red)?,
-            ChannelConfig::new(self.min_green, self.max_green)?,
-            ChannelConfig::new(self.min_blue, self.max_blue)?,
-        ])
+        file: Option<&ConfigFile>,
+    ) -> io::Result<[ChannelConfig; 3]> {
+        let bounds = [
+            (self.min_red, self.max_red),
+            (self.min_green, self.max_green),
+            (self.min_blue, self.max_blue),
+        ];
+        let entries: [Bounds; 3] = [
+            |file| (&file.min_red, &file.max_red),
+            |file| (&file.min_green, &file.max_green),
+            |file| (&file.min_blue, &file.max_blue),
+        ];
+        let mut channels = Config::default().channels;
+        for i in 0..channels.len() {
+            let mut layers = Layers::new(file);
+            let (min, max) = bounds[i];
+            let (default_min, default_max) = DEFAULT_CHANNELS[i];
+            let min = layers
+                .pick(min, |file| entries[i](file).0)
+                .unwrap_or(default_min);
+            let max = layers
+                .pick(max, |file| entries[i](file).1)
+                .unwrap_or(default_max);
+            channels[i] = ChannelConfig::new(min, max)
+                .map_err(|error| layers.invalid(error))?;
+        }
+        Ok(channels)
+    }
+
+    /// Creates the schedule source from these args, on top of the given
+    /// configuration file. Sources given in these args take precedence over
+    /// any source of the file, while a location given in these args is
+    /// layered over the sun settings of the file.
+    pub fn create_schedule_source(
+        &self,
+        file: Option<&ConfigFile>,
+    ) -> io::Result<ScheduleSource> {
+        let file = if self.keyframes.is_empty() || self.latitude.is_some() {
+            file
+        } else {
+            None
+        };
+        let elevation = self.elevation
+            || file
+                .and_then(|file| file.elevation.as_ref())
+                .is_some_and(|entry| entry.value);
+        match self.create_location(file)? {
+            Some(location) if elevation => Ok(ScheduleSource::Elevation(
+                self.create_elevation_config(location, file)?,
+            )),
+            Some(location) => Ok(ScheduleSource::Solar(
+                self.create_solar_config(location, file),
+            )),
+            None if elevation => Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "following the sun elevation requires a location",
+            )),
+            None => {
+                let mut layers = Layers::new(file);
+                let keyframes = (!self.keyframes.is_empty())
+                    .then(|| self.keyframes.clone());
+                match layers.pick(keyframes, |file| &file.keyframes) {
+                    Some(keyframes) => Schedule::new(keyframes)
+                        .map(ScheduleSource::Keyframes)
+                        .map_err(|error| layers.invalid(error)),
+                    None => Ok(ScheduleSource::Hours),
+                }
+            },
+        }
+    }
+
+    /// Creates the color temperatures configuration from these args, on top
+    /// of the given configuration file, if given.
+    pub fn create_temperature_config(
+        &self,
+        file: Option<&ConfigFile>,
+    ) -> io::Result<Option<TemperatureConfig>> {
+        let mut layers = Layers::new(file);
+        let day =
+            layers.pick(self.day_temperature, |file| &file.day_temperature);
+        let night =
+            layers.pick(self.night_temperature, |file| &file.night_temperature);
+        match (day, night) {
+            (Some(day), Some(night)) => TemperatureConfig::new(day, night)
+                .map(Some)
+                .map_err(|error| layers.invalid(error)),
+            _ => Ok(None),
+        }
+    }
+
+    /// Creates the brightness configuration from these args, on top of the
+    /// given configuration file.
+    pub fn create_brightness_config(
+        &self,
+        file: Option<&ConfigFile>,
+    ) -> io::Result<BrightnessConfig> {
+        let mut layers = Layers::new(file);
+        let default = BrightnessConfig::default();
+        BrightnessConfig::new(
+            layers
+                .pick(self.day_brightness, |file| &file.day_brightness)
+                .unwrap_or(default.day()),
+            layers
+                .pick(self.night_brightness, |file| &file.night_brightness)
+                .unwrap_or(default.night()),
+        )
+        .map_err(|error| layers.invalid(error))
     }
 
-    /// Creates whole configuration from these args.
+    /// Creates the fade configuration from these args, on top of the given
+    /// configuration file.
+    pub fn create_fade_config(
+        &self,
+        file: Option<&ConfigFile>,
+    ) -> io::Result<FadeConfig> {
+        let mut layers = Layers::new(file);
+        let default = FadeConfig::default();
+        FadeConfig::new(
+            layers
+                .pick(self.fade_duration, |file| &file.fade_duration)
+                .unwrap_or(default.duration().as_secs_f64()),
+            layers
+                .pick(self.fade_rate, |file| &file.fade_rate)
+                .unwrap_or(default.rate()),
+        )
+        .map_err(|error| layers.invalid(error))
+    }
+
+    /// Creates the gamma ramps configuration from these args, on top of the
+    /// given configuration file.
+    pub fn create_ramp_config(
+        &self,
+        file: Option<&ConfigFile>,
+    ) -> io::Result<RampConfig> {
+        let mut layers = Layers::new(file);
+        let default = RampConfig::default();
+        RampConfig::new(
+            layers
+                .pick(self.base_gamma, |file| &file.base_gamma)
+                .unwrap_or(default.base_gamma()),
+        )
+        .map_err(|error| layers.invalid(error))
+    }
+
+    /// Creates the custom brightness schedule from these args, on top of the
+    /// given configuration file, if given.
+    pub fn create_brightness_schedule(
+        &self,
+        file: Option<&ConfigFile>,
+    ) -> io::Result<Option<Schedule<f64>>> {
+        let mut layers = Layers::new(file);
+        let keyframes = (!self.brightness_keyframes.is_empty())
+            .then(|| self.brightness_keyframes.clone());
+        match layers.pick(keyframes, |file| &file.brightness_keyframes) {
+            Some(keyframes) => Schedule::new(keyframes)
+                .map(Some)
+                .map_err(|error| layers.invalid(error)),
+            None => Ok(None),
+        }
+    }
+
+    /// Creates the location whose sun is followed from these args, on top of
+    /// the given configuration file, if given.
+    pub fn create_location(
+        &self,
+        file: Option<&ConfigFile>,
+    ) -> io::Result<Option<Location>> {
+        let mut layers = Layers::new(file);
+        let latitude = layers.pick(self.latitude, |file| &file.latitude);
+        let longitude = layers.pick(self.longitude, |file| &file.longitude);
+        match (latitude, longitude) {
+            (Some(latitude), Some(longitude)) => {
+                Location::new(latitude, longitude)
+                    .map(Some)
+                    .map_err(|error| layers.invalid(error))
+            },
+            _ => Ok(None),
+        }
+    }
+
+    /// Creates the configuration of day phases following the sun at the given
+    /// location from these args, on top of the given configuration file.
+    pub fn create_solar_config(
+        &self,
+        location: Location,
+        file: Option<&ConfigFile>,
+    ) -> SolarConfig {
+        let mut layers = Layers::new(file);
+        let offset = layers
+            .pick(self.solar_offset, |file| &file.solar_offset)
+            .unwrap_or(DEFAULT_SOLAR_OFFSET);
+        SolarConfig {
+            location,
+            twilight: layers
+                .pick(self.twilight, |file| &file.twilight)
+                .unwrap_or_default(),
+            offset: offset / (24.0 * 60.0),
+        }
+    }
+
+    /// Creates the configuration following the sun elevation at the given
+    /// location from these args, on top of the given configuration file.
+    pub fn create_elevation_config(
+        &self,
+        location: Location,
+        file: Option<&ConfigFile>,
+    ) -> io::Result<ElevationConfig> {
+        let mut layers = Layers::new(file);
+        ElevationConfig::new(
+            location,
+            layers
+                .pick(self.day_elevation, |file| &file.day_elevation)
+                .unwrap_or(DEFAULT_DAY_ELEVATION),
+            layers
+                .pick(self.night_elevation, |file| &file.night_elevation)
+                .unwrap_or(DEFAULT_NIGHT_ELEVATION),
+        )
+        .map_err(|error| layers.invalid(error))
+    }
+
+    /// Creates the configurations specific to monitors of the given
+    /// configuration file, whose missing values are taken from the given
+    /// general color channels and brightness configurations. Color channels
+    /// bounds of monitors are rejected unless `bounded`, that is, unless the
+    /// colors are computed from the bounds, rather than from color
+    /// temperatures or keyframes.
+    pub fn create_monitor_configs(
+        &self,
+        file: Option<&ConfigFile>,
+        channels: [ChannelConfig; 3],
+        brightness: BrightnessConfig,
+        bounded: bool,
+    ) -> io::Result<Vec<MonitorConfig>> {
+        let Some(file) = file else { return Ok(Vec::new()) };
+        let entries: [MonitorBounds; 3] = [
+            |monitor| (&monitor.min_red, &monitor.max_red),
+            |monitor| (&monitor.min_green, &monitor.max_green),
+            |monitor| (&monitor.min_blue, &monitor.max_blue),
+        ];
+        let mut monitors = Vec::with_capacity(file.monitors.len());
+        for (i, monitor) in file.monitors.iter().enumerate() {
+            let ignored = entries
+                .iter()
+                .flat_map(|entry| {
+                    let (min, max) = entry(monitor);
+                    [min, max]
+                })
+                .flatten()
+                .next()
+                .filter(|_| !bounded);
+            if let Some(ignored) = ignored {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    file.invalid(
+                        &ignored.position,
+                        "color channels bounds of monitors cannot be used \
+                         along with color temperatures or keyframes",
+                    ),
+                ));
+            }
+            let mut monitor_channels = channels;
+            for j in 0..channels.len() {
+                let mut layers = Layers::new(Some(file));
+                let min = layers
+                    .pick(None, |file| entries[j](&file.monitors[i]).0)
+                    .unwrap_or(channels[j].min());
+                let max = layers
+                    .pick(None, |file| entries[j](&file.monitors[i]).1)
+                    .unwrap_or(channels[j].max());
+                monitor_channels[j] = ChannelConfig::new(min, max)
+                    .map_err(|error| layers.invalid(error))?;
+            }
+            let mut layers = Layers::new(Some(file));
+            let monitor_brightness = BrightnessConfig::new(
+                layers
+                    .pick(None, |file| &file.monitors[i].day_brightness)
+                    .unwrap_or(brightness.day()),
+                layers
+                    .pick(None, |file| &file.monitors[i].night_brightness)
+                    .unwrap_or(brightness.night()),
+            )
+            .map_err(|error| layers.invalid(error))?;
+            let mut layers = Layers::new(Some(file));
+            let calibration = layers
+                .pick(None, |file| &file.monitors[i].calibration)
+                .unwrap_or([1.0; 3]);
+            let monitor_config = MonitorConfig::new(
+                monitor.matcher.value.clone(),
+                monitor_channels,
+                monitor_brightness,
+                calibration,
+            )
+            .map_err(|error| layers.invalid(error))?;
+            monitors.push(monitor_config);
+        }
+        Ok(monitors)
+    }
+
+    /// Creates whole configuration from these args, on top of the
+    /// configuration file, if any.
     pub fn create_config(&self) -> io::Result<Config> {
-        let hours = self.create_hour_config().map_err(|error| {
-            io::Error::new(io::ErrorKind::InvalidInput, error)
-        })?;
-        let channels = self.create_channels_config().map_err(|error| {
-            io::Error::new(io::ErrorKind::InvalidInput, error)
-        })?;
-        Ok(Config { hours, channels })
+        self.create_config_from(self.load_config_file()?.as_ref())
+    }
+
+    /// Creates whole configuration from these args, on top of the given
+    /// configuration file.
+    pub fn create_config_from(
+        &self,
+        file: Option<&ConfigFile>,
+    ) -> io::Result<Config> {
+        let channels = self.create_channels_config(file)?;
+        let brightness = self.create_brightness_config(file)?;
+        let hours = self.create_hour_config(file)?;
+        let temperature = self.create_temperature_config(file)?;
+        let brightness_schedule = self.create_brightness_schedule(file)?;
+        let curve = Layers::new(file)
+            .pick(self.curve, |file| &file.curve)
+            .unwrap_or_default();
+        let source = self.create_schedule_source(file)?;
+        let fade = self.create_fade_config(file)?;
+        let ramp = self.create_ramp_config(file)?;
+        let bounded = temperature.is_none()
+            && !matches!(source, ScheduleSource::Keyframes(_));
+        let monitors =
+            self.create_monitor_configs(file, channels, brightness, bounded)?;
+        Ok(Config {
+            hours,
+            channels,
+            temperature,
+            brightness,
+            brightness_schedule,
+            curve,
+            source,
+            fade,
+            ramp,
+            monitors,
+        })
+    }
+}
+
+/// Accessor of the bounds of a color channel in a configuration file.
+type Bounds = fn(&ConfigFile) -> (&Option<Entry<f64>>, &Option<Entry<f64>>);
+
+/// Accessor of the bounds of a color channel in a configuration specific to
+/// monitors.
+type MonitorBounds =
+    fn(&MonitorEntry) -> (&Option<Entry<f64>>, &Option<Entry<f64>>);
+
+/// Where a value picked by [`Layers`] comes from.
+#[derive(Debug, Clone, Copy)]
+enum Origin<'file> {
+    /// Given in the arguments.
+    Arg,
+    /// Taken from the configuration file, at the given position.
+    File(&'file Position),
+    /// Not given at all, so a default is used.
+    Default,
+}
+
+impl Origin<'_> {
+    /// Precedence of this origin when blaming a rejected value, where lower
+    /// comes first.
+    fn precedence(self) -> u8 {
+        match self {
+            Self::Arg => 0,
+            Self::File(_) => 1,
+            Self::Default => 2,
+        }
+    }
+}
+
+/// Values of arguments layered on top of a configuration file, keeping track
+/// of where each picked value comes from.
+#[derive(Debug, Clone)]
+struct Layers<'file> {
+    file: Option<&'file ConfigFile>,
+    origins: Vec<Origin<'file>>,
+}
+
+impl<'file> Layers<'file> {
+    fn new(file: Option<&'file ConfigFile>) -> Self {
+        Self { file, origins: Vec::new() }
+    }
+
+    /// Picks the value of an argument if given, otherwise the value of the
+    /// file.
+    fn pick<T, F>(&mut self, arg: Option<T>, entry: F) -> Option<T>
+    where
+        T: Clone + 'file,
+        F: FnOnce(&'file ConfigFile) -> &'file Option<Entry<T>>,
+    {
+        if arg.is_some() {
+            self.origins.push(Origin::Arg);
+            return arg;
+        }
+        match self.file.and_then(|file| entry(file).as_ref()) {
+            Some(entry) => {
+                self.origins.push(Origin::File(&entry.position));
+                Some(entry.value.clone())
+            },
+            None => {
+                self.origins.push(Origin::Default);
+                None
+            },
+        }
+    }
+
+    /// Makes an error for invalid picked values, pointing to the file if the
+    /// rejected value was taken from it. Among values that may be rejected
+    /// (or among all values, if none may be alone), arguments are blamed
+    /// first, then values of the file, in the order they were picked.
+    fn invalid<E>(&self, error: E) -> io::Error
+    where
+        E: RejectedValue + Send + Sync + 'static,
+    {
+        let rejected = (0..self.origins.len()).min_by_key(|&i| {
+            (!error.rejects(i), self.origins[i].precedence())
+        });
+        match (self.file, rejected.map(|i| self.origins[i])) {
+            (Some(file), Some(Origin::File(position))) => io::Error::new(
+                io::ErrorKind::InvalidInput,
+                file.invalid(position, error),
+            ),
+            _ => io::Error::new(io::ErrorKind::InvalidInput, error),
+        }
     }
 }
 
@@ -113,22 +716,31 @@ impl ConfigArgs {
 #[derive(Debug, Clone, StructOpt)]
 #[structopt(version = "0.1")]
 pub struct Program {
+    /// Backend used to control the screen colors, one of `wlr`,
+    /// `night-light`, `randr`, `xrandr` or `kms` (see the `backends`
+    /// subcommand). If not given, the first available one is used.
+    #[structopt(long = "--backend")]
+    #[structopt(env = environment::BACKEND_VAR)]
+    #[structopt(global = true)]
+    backend: Option<Backend>,
     #[structopt(subcommand)]
     subcommand: SubCommand,
 }
 
-impl GraphicalEnvContext for Program {
-    type Output = ();
-
-    fn with_graphical_env<G>(self, graphical_env: G) -> io::Result<Self::Output>
-    where
-        G: GraphicalEnv,
-    {
-        self.subcommand.with_graphical_env(graphical_env)
-    }
-
-    fn without_graphical_env(self) -> io::Result<Self::Output> {
-        self.subcommand.without_graphical_env()
+impl Program {
+    /// Runs the program with the selected backend.
+    pub fn run(self) -> io::Result<()> {
+        match self.subcommand {
+            SubCommand::Backends(subcommand) => subcommand.run(),
+            SubCommand::Ctl(subcommand) => subcommand.run(),
+            SubCommand::Serve(subcommand) => {
+                let subcommand =
+                    ServeSubCommand { backend: self.backend, ..subcommand };
+                let subcommand = SubCommand::Serve(subcommand);
+                environment::with_backend(self.backend, subcommand)
+            },
+            subcommand => environment::with_backend(self.backend, subcommand),
+        }
     }
 }
 
@@ -143,6 +755,14 @@ pub enum SubCommand {
     /// Applies once the color spectrum to the screen according to current hour
     /// (or the given hour).
     Apply(ApplySubCommand),
+    /// Controls a running service.
+    Ctl(CtlSubCommand),
+    /// Restores neutral colors to the screen.
+    Reset(ResetSubCommand),
+    /// Lists the backends and whether they are available.
+    Backends(BackendsSubCommand),
+    /// Lists the connected monitors.
+    Monitors(MonitorsSubCommand),
 }
 
 impl GraphicalEnvContext for SubCommand {
@@ -162,6 +782,14 @@ impl GraphicalEnvContext for SubCommand {
             Self::Apply(subcommand) => {
                 subcommand.with_graphical_env(graphical_env)
             },
+            Self::Ctl(subcommand) => subcommand.run(),
+            Self::Reset(subcommand) => {
+                subcommand.with_graphical_env(graphical_env)
+            },
+            Self::Backends(subcommand) => subcommand.run(),
+            Self::Monitors(subcommand) => {
+                subcommand.with_graphical_env(graphical_env)
+            },
         }
     }
 
@@ -170,11 +798,20 @@ impl GraphicalEnvContext for SubCommand {
             Self::Serve(subcommand) => subcommand.without_graphical_env(),
             Self::Print(subcommand) => subcommand.without_graphical_env(),
             Self::Apply(subcommand) => subcommand.without_graphical_env(),
+            Self::Ctl(subcommand) => subcommand.run(),
+            Self::Reset(subcommand) => subcommand.without_graphical_env(),
+            Self::Backends(subcommand) => subcommand.run(),
+            Self::Monitors(subcommand) => subcommand.without_graphical_env(),
         }
     }
 }
 
 /// Run it as a service, running minute to minute or in the desired interval.
+/// The configuration file is reloaded whenever it changes, monitors are
+/// adjusted as soon as they are connected, and the service can be controlled
+/// through a local socket (see the `ctl` subcommand). The colors
+/// found at startup are restored on exit, including on SIGINT, SIGTERM and
+/// SIGHUP.
 #[derive(Debug, Clone, StructOpt)]
 pub struct ServeSubCommand {
     /// Seconds to wait beetween every update to screen colors.
@@ -188,9 +825,18 @@ pub struct ServeSubCommand {
     #[structopt(long = "--monitors")]
     #[structopt(short = "-m")]
     monitors: Option<Vec<String>>,
+    /// Path of the control socket. If not given,
+    /// `$XDG_RUNTIME_DIR/circadianlight.sock` is used.
+    #[structopt(long = "--socket")]
+    #[structopt(parse(from_os_str))]
+    socket: Option<PathBuf>,
     /// Arguments for configuration of the gamma function.
     #[structopt(flatten)]
     config_args: ConfigArgs,
+    /// Backend reloaded when the display cannot be reached, the first
+    /// available one if not given.
+    #[structopt(skip)]
+    backend: Option<Backend>,
 }
 
 impl GraphicalEnvContext for ServeSubCommand {
@@ -200,21 +846,479 @@ impl GraphicalEnvContext for ServeSubCommand {
     where
         G: GraphicalEnv,
     {
-        let config = self.config_args.create_config()?;
+        let monitors = match &self.monitors {
+            Some(monitors) => {
+                check_monitors(&graphical_env, monitors)?;
+                monitors.clone()
+            },
+            None => graphical_env.list_monitor_names()?,
+        };
+        let original_gamma = capture_gamma(&graphical_env, monitors);
+        let mut state = ServeState::new(
+            self.config_args.clone(),
+            overrides::default_state_path(),
+            original_gamma,
+        )?;
+        let (sender, events) = mpsc::channel();
+        let result = signal::forward_termination(sender.clone(), || {
+            ServeEvent::Terminate
+        });
+        if let Err(error) = result {
+            eprintln!("colors will not be restored on signals: {}", error);
+        }
+        self.watch_monitors(&graphical_env, &sender);
+        if let Some(path) = self.config_args.watched_config_path() {
+            let result = watch::watch_file(&path, sender.clone(), || {
+                ServeEvent::ConfigChanged
+            });
+            // The default directory missing is expected, as long as no file
+            // is used.
+            match result {
+                Err(error) if self.config_args.config_path().is_some() => {
+                    eprintln!("configuration will not be reloaded: {}", error);
+                },
+                _ => (),
+            }
+        }
+        let _listener = match resolve_socket_path(self.socket.clone()) {
+            Ok(path) => Some(control::listen(&path, sender.clone())?),
+            Err(error) => {
+                eprintln!("control socket disabled: {}", error);
+                None
+            },
+        };
+        self.run(&graphical_env, &mut state, &sender, &events)
+    }
+}
+
+impl ServeSubCommand {
+    /// Applies the colors until a termination signal is received or applying
+    /// fails, and then restores the colors captured at startup.
+    fn run<G>(
+        &self,
+        graphical_env: G,
+        state: &mut ServeState,
+        sender: &Sender<ServeEvent>,
+        events: &Receiver<ServeEvent>,
+    ) -> io::Result<()>
+    where
+        G: GraphicalEnv,
+    {
+        let mut reloaded = None;
+        let result =
+            self.serve(&graphical_env, &mut reloaded, state, sender, events);
+        match &reloaded {
+            Some(reloaded) => restore_gamma(reloaded, &state.original),
+            None => restore_gamma(&graphical_env, &state.original),
+        }
+        result
+    }
+
+    /// Applies the colors until a termination signal is received or applying
+    /// fails. Transient failures are retried with an increasing delay, up to
+    /// the interval. When the display cannot be reached, such as when the
+    /// display server restarted, the backend is loaded again, and used from
+    /// then on.
+    fn serve<G>(
+        &self,
+        graphical_env: G,
+        reloaded: &mut Option<BoxedGraphicalEnv>,
+        state: &mut ServeState,
+        sender: &Sender<ServeEvent>,
+        events: &Receiver<ServeEvent>,
+    ) -> io::Result<()>
+    where
+        G: GraphicalEnv,
+    {
+        let interval = Duration::from_secs(self.sleep_seconds);
+        let mut backoff = Backoff::new(interval);
         loop {
-            let gamma = create_color_channels(config, None);
-            match &self.monitors {
-                Some(monitors) => {
-                    graphical_env.apply_gamma(gamma, monitors)?;
+            let result = match reloaded {
+                Some(reloaded) => self.update(&*reloaded, state),
+                None => self.update(&graphical_env, state),
+            };
+            let wake_up_in = match result {
+                Ok(()) => {
+                    backoff.reset();
+                    state
+                        .wake_up_in()
+                        .map_or(interval, |wake_up_in| wake_up_in.min(interval))
                 },
-                None => {
-                    let monitors = graphical_env.list_monitors()?;
-                    graphical_env.apply_gamma(gamma, monitors)?;
+                Err(error) if error.is_transient() => {
+                    if let BackendError::DisplayUnreachable { .. } = error {
+                        if let Some(graphical_env) =
+                            self.reload_graphical_env(sender)
+                        {
+                            *reloaded = Some(graphical_env);
+                        }
+                    }
+                    let delay = backoff.next_delay();
+                    eprintln!("retrying in {:?}: {}", delay, error);
+                    delay
                 },
+                Err(error) => return Err(error.into()),
+            };
+            let deadline = Instant::now() + wake_up_in;
+            loop {
+                let timeout =
+                    deadline.saturating_duration_since(Instant::now());
+                let Ok(event) = events.recv_timeout(timeout) else { break };
+                if let ServeEvent::Terminate = event {
+                    return Ok(());
+                }
+                if state.handle(event) {
+                    break;
+                }
             }
-            thread::sleep(Duration::from_secs(self.sleep_seconds));
         }
     }
+
+    /// Loads the backend again, watching its monitors. If it is still not
+    /// available, `None` is returned, so that the previous one is used.
+    fn reload_graphical_env(
+        &self,
+        sender: &Sender<ServeEvent>,
+    ) -> Option<BoxedGraphicalEnv> {
+        match environment::load_graphical_env(self.backend) {
+            Ok(graphical_env) => {
+                self.watch_monitors(&graphical_env, sender);
+                Some(graphical_env)
+            },
+            Err(error) => {
+                eprintln!("could not reload the backend: {}", error);
+                None
+            },
+        }
+    }
+
+    /// Watches the monitors of the given environment, unless the monitors
+    /// were given.
+    fn watch_monitors<G>(&self, graphical_env: G, sender: &Sender<ServeEvent>)
+    where
+        G: GraphicalEnv,
+    {
+        if self.monitors.is_some() {
+            return;
+        }
+        let sender = sender.clone();
+        let result = graphical_env.watch_monitors(Box::new(move || {
+            sender.send(ServeEvent::MonitorsChanged).is_ok()
+        }));
+        if let Err(error) = result {
+            eprintln!("monitors will not be watched: {}", error);
+        }
+    }
+
+    /// Applies the current colors to the given monitors, or to all of the
+    /// currently connected ones.
+    fn update<G>(
+        &self,
+        graphical_env: G,
+        state: &mut ServeState,
+    ) -> Result<(), BackendError>
+    where
+        G: GraphicalEnv,
+    {
+        let monitors = resolve_monitors(
+            &graphical_env,
+            self.monitors.as_deref(),
+            &state.config,
+        )?;
+        state.apply(&graphical_env, monitors)
+    }
+}
+
+/// Event received by a running server.
+#[derive(Debug)]
+enum ServeEvent {
+    /// The configuration file changed.
+    ConfigChanged,
+    /// A monitor was connected, disconnected or reconfigured.
+    MonitorsChanged,
+    /// A command was received through the control socket.
+    Request(Request),
+    /// A signal asked the server to terminate.
+    Terminate,
+}
+
+impl From<Request> for ServeEvent {
+    fn from(request: Request) -> Self {
+        Self::Request(request)
+    }
+}
+
+/// State of a running server.
+#[derive(Debug)]
+struct ServeState {
+    config_args: ConfigArgs,
+    config: Config,
+    profile: Option<String>,
+    state_path: Option<PathBuf>,
+    color_override: Option<Override>,
+    original: Vec<(String, CapturedGamma)>,
+    applied: Vec<(String, ColorAdjustment)>,
+    fade: Option<Fade>,
+}
+
+impl ServeState {
+    /// Creates the state of a server, restoring the override persisted at the
+    /// given path, if any and still active. Colors will fade from the given
+    /// captured colors of each monitor, which are restored on exit.
+    fn new(
+        config_args: ConfigArgs,
+        state_path: Option<PathBuf>,
+        original: Vec<(String, CapturedGamma)>,
+    ) -> io::Result<Self> {
+        let color_override = match &state_path {
+            Some(path) => Override::load(path).unwrap_or_else(|error| {
+                eprintln!("ignoring the persisted override: {}", error);
+                None
+            }),
+            None => None,
+        };
+        let mut state = Self {
+            config_args,
+            config: Config::default(),
+            profile: None,
+            state_path,
+            color_override: None,
+            applied: original
+                .iter()
+                .map(|(monitor, captured)| {
+                    (monitor.clone(), captured.adjustment())
+                })
+                .collect(),
+            original,
+            fade: None,
+        };
+        state.reload()?;
+        match color_override {
+            Some(color_override) if color_override.is_active(Local::now()) => {
+                state.color_override = Some(color_override);
+            },
+            Some(_) => state.persist(),
+            None => (),
+        }
+        Ok(state)
+    }
+
+    /// Reloads the configuration, keeping the previous one if the new one is
+    /// invalid. Colors fade to the ones of the new configuration.
+    fn reload(&mut self) -> io::Result<()> {
+        let file = self.config_args.load_config_file()?;
+        self.config = self.config_args.create_config_from(file.as_ref())?;
+        self.profile = file.and_then(|file| file.profile);
+        self.begin_fade();
+        Ok(())
+    }
+
+    /// Begins a fade from the colors currently applied.
+    fn begin_fade(&mut self) {
+        let duration = self.config.fade.duration();
+        let fade = Fade::new(self.applied.clone(), Instant::now(), duration);
+        self.fade = Some(fade);
+    }
+
+    /// Persists the current override, or its absence.
+    fn persist(&self) {
+        let Some(path) = &self.state_path else { return };
+        let result = match &self.color_override {
+            Some(color_override) => color_override.save(path),
+            None => Override::clear(path),
+        };
+        if let Err(error) = result {
+            eprintln!("could not persist the override: {}", error);
+        }
+    }
+
+    /// Target of the current override, if any. Expired overrides are ended
+    /// here.
+    fn override_target(&mut self) -> Option<OverrideTarget> {
+        let color_override = self.color_override?;
+        if color_override.is_active(Local::now()) {
+            return Some(color_override.target);
+        }
+        self.end_override();
+        None
+    }
+
+    /// Color adjustment that should be currently applied, regardless of the
+    /// configurations specific to monitors.
+    fn adjustment(&mut self) -> ColorAdjustment {
+        let scheduled = create_color_adjustment(&self.config, None);
+        match self.override_target() {
+            Some(target) => target.adjustment(&self.config, scheduled),
+            None => scheduled,
+        }
+    }
+
+    /// Color adjustment that should be currently applied to each of the given
+    /// monitors.
+    fn adjustments(
+        &mut self,
+        monitors: &[Monitor],
+    ) -> Vec<(String, ColorAdjustment)> {
+        let target = self.override_target();
+        monitors
+            .iter()
+            .map(|monitor| {
+                let adjustment = create_monitor_adjustment(
+                    &self.config,
+                    monitor,
+                    None,
+                    target,
+                );
+                (monitor.name.clone(), adjustment)
+            })
+            .collect()
+    }
+
+    /// Applies the current colors to the given monitors, following the
+    /// current fade, if any. The colors of monitors seen for the first time,
+    /// such as monitors just connected, are captured beforehand, so that they
+    /// are restored on exit as well.
+    fn apply<G>(
+        &mut self,
+        graphical_env: G,
+        monitors: Vec<Monitor>,
+    ) -> Result<(), BackendError>
+    where
+        G: GraphicalEnv,
+    {
+        let new = monitors
+            .iter()
+            .filter(|monitor| {
+                !self.original.iter().any(|(name, _)| *name == monitor.name)
+            })
+            .map(|monitor| monitor.name.clone())
+            .collect::<Vec<_>>();
+        if !new.is_empty() {
+            self.original.extend(capture_gamma(&graphical_env, new));
+        }
+        let targets = self.adjustments(&monitors);
+        let now = Instant::now();
+        let adjustments = match &self.fade {
+            Some(fade) => fade.adjustments(&targets, now),
+            None => targets,
+        };
+        if self.fade.as_ref().is_some_and(|fade| fade.is_finished(now)) {
+            self.fade = None;
+        }
+        environment::apply_per_monitor(graphical_env, &adjustments)?;
+        self.applied = adjustments;
+        Ok(())
+    }
+
+    /// Ends the current override, if any, fading back to the schedule.
+    fn end_override(&mut self) {
+        if self.color_override.take().is_some() {
+            self.persist();
+            self.begin_fade();
+        }
+    }
+
+    /// Time until the colors should be updated regardless of the interval, if
+    /// any, such as during a fade or at the expiry of an override.
+    fn wake_up_in(&self) -> Option<Duration> {
+        if self.fade.is_some() {
+            return Some(self.config.fade.step());
+        }
+        let until = self.color_override?.until?;
+        Some((until - Local::now()).to_std().unwrap_or(Duration::ZERO))
+    }
+
+    /// Handles an event, returning whether colors should be applied again.
+    fn handle(&mut self, event: ServeEvent) -> bool {
+        match event {
+            ServeEvent::ConfigChanged => {
+                if let Err(error) = self.reload() {
+                    eprintln!("keeping the previous configuration: {}", error);
+                }
+                true
+            },
+            ServeEvent::MonitorsChanged => true,
+            ServeEvent::Request(request) => {
+                let changes_colors = request.command != Command::Status;
+                let reply = self.execute(request.command.clone());
+                request.reply(reply);
+                changes_colors
+            },
+            ServeEvent::Terminate => false,
+        }
+    }
+
+    /// Executes a command received through the control socket.
+    fn execute(&mut self, command: Command) -> Reply {
+        match command {
+            Command::Pause(expiry) => {
+                return self.start_override(OverrideTarget::Neutral, expiry)
+            },
+            Command::Resume => self.end_override(),
+            Command::Status => return Ok(self.status()),
+            Command::Profile(name) => {
+                let previous = self.config_args.profile.replace(name);
+                if let Err(error) = self.reload() {
+                    self.config_args.profile = previous;
+                    return Err(error.to_string());
+                }
+            },
+            Command::Override { target, expiry } => {
+                return self.start_override(target, expiry);
+            },
+            Command::Reload => {
+                self.reload().map_err(|error| error.to_string())?;
+            },
+        }
+        Ok(String::new())
+    }
+
+    /// Starts an override with the given target and expiry, replacing the
+    /// current one, if any, and describing when it ends.
+    fn start_override(
+        &mut self,
+        target: OverrideTarget,
+        expiry: Expiry,
+    ) -> Reply {
+        let until = expiry
+            .resolve(Local::now())
+            .map_err(|error| error.to_string())?;
+        self.color_override = Some(Override { target, until });
+        self.persist();
+        self.begin_fade();
+        Ok(match until {
+            Some(until) => format!("until {}", until.format("%Y-%m-%d %H:%M")),
+            None => String::new(),
+        })
+    }
+
+    /// Description of the current state.
+    fn status(&mut self) -> String {
+        let adjustment = self.adjustment();
+        let (paused, color_override) = match self.color_override {
+            Some(color_override) => {
+                let until = match color_override.until {
+                    Some(until) => until.format("%Y-%m-%dT%H:%M").to_string(),
+                    None => "never".to_owned(),
+                };
+                (
+                    color_override.target == OverrideTarget::Neutral,
+                    format!("{} until={}", color_override.target, until),
+                )
+            },
+            None => (false, "none".to_owned()),
+        };
+        format!(
+            "paused={} override={} profile={} red={:.3} green={:.3} blue={:.3} \
+             brightness={:.3}",
+            paused,
+            color_override,
+            self.profile.as_deref().unwrap_or(file::DEFAULT_PROFILE),
+            adjustment.gamma[channel::RED],
+            adjustment.gamma[channel::GREEN],
+            adjustment.gamma[channel::BLUE],
+            adjustment.brightness,
+        )
+    }
 }
 
 /// Just prints the color spectrum for the current hour (or the given
@@ -240,26 +1344,33 @@ impl GraphicalEnvContext for PrintSubCommand {
         G: GraphicalEnv,
     {
         let config = self.config_args.create_config()?;
-        let gamma = create_color_channels(config, self.time);
-        println!("{}", graphical_env.format_gamma(gamma)?);
+        let adjustment = create_color_adjustment(&config, self.time);
+        println!(
+            "{} {:.3}",
+            graphical_env.format_gamma(adjustment)?,
+            adjustment.brightness,
+        );
         Ok(())
     }
 
     fn without_graphical_env(self) -> io::Result<Self::Output> {
         let config = self.config_args.create_config()?;
-        let gamma = create_color_channels(config, self.time);
+        let adjustment = create_color_adjustment(&config, self.time);
         println!(
-            "red={:.3} green={:.3} blue={:.3}",
-            gamma[channel::RED],
-            gamma[channel::GREEN],
-            gamma[channel::BLUE],
+            "red={:.3} green={:.3} blue={:.3} brightness={:.3}",
+            adjustment.gamma[channel::RED],
+            adjustment.gamma[channel::GREEN],
+            adjustment.gamma[channel::BLUE],
+            adjustment.brightness,
         );
         Ok(())
     }
 }
 
 /// Applies once the color spectrum to the screen according to current hour
-/// (or the given hour).
+/// (or the given hour). In graphical environments where colors do not persist
+/// after the program exits, such as Wayland, it keeps running until
+/// interrupted.
 #[derive(Debug, Clone, StructOpt)]
 pub struct ApplySubCommand {
     /// The time in format `H:M` from which the colors will be computed. If not
@@ -287,28 +1398,704 @@ impl GraphicalEnvContext for ApplySubCommand {
         G: GraphicalEnv,
     {
         let config = self.config_args.create_config()?;
-        let gamma = create_color_channels(config, self.time);
-        match self.monitors {
-            Some(monitors) => {
-                graphical_env.apply_gamma(gamma, monitors)?;
+        let monitors = resolve_monitors(
+            &graphical_env,
+            self.monitors.as_deref(),
+            &config,
+        )?;
+        let targets = monitors
+            .iter()
+            .map(|monitor| {
+                let adjustment = create_monitor_adjustment(
+                    &config,
+                    monitor,
+                    self.time,
+                    None,
+                );
+                (monitor.name.clone(), adjustment)
+            })
+            .collect::<Vec<_>>();
+        let names = monitors.into_iter().map(|monitor| monitor.name).collect();
+        let current = capture_gamma(&graphical_env, names)
+            .into_iter()
+            .map(|(monitor, captured)| (monitor, captured.adjustment()))
+            .collect();
+        fade::fade_to(&graphical_env, current, &targets, config.fade)?;
+        if !graphical_env.persists_gamma() {
+            // Colors are lost as soon as the program exits.
+            let (sender, terminated) = mpsc::channel();
+            signal::forward_termination(sender, || ())?;
+            eprintln!("keeping the colors until interrupted");
+            let _ = terminated.recv();
+        }
+        Ok(())
+    }
+}
+
+/// Restores neutral colors to the screen, that is, `1:1:1` gamma and full
+/// brightness.
+#[derive(Debug, Clone, StructOpt)]
+pub struct ResetSubCommand {
+    /// List of monitors to be reset. If not given, it will be obtained from
+    /// your graphical environment, and all of currently used monitors will
+    /// be reset.
+    #[structopt(long = "--monitors")]
+    #[structopt(short = "-m")]
+    monitors: Option<Vec<String>>,
+}
+
+impl GraphicalEnvContext for ResetSubCommand {
+    type Output = ();
+
+    fn with_graphical_env<G>(self, graphical_env: G) -> io::Result<Self::Output>
+    where
+        G: GraphicalEnv,
+    {
+        let monitors = match self.monitors {
+            Some(monitors) => monitors,
+            None => graphical_env.list_monitor_names()?,
+        };
+        graphical_env.apply_gamma(ColorAdjustment::default(), monitors)?;
+        Ok(())
+    }
+}
+
+/// Lists the backends, i.e. the ways of controlling the screen colors, in the
+/// order they are probed, telling whether each is available in the current
+/// session, or why it is not.
+#[derive(Debug, Clone, StructOpt)]
+pub struct BackendsSubCommand {}
+
+impl BackendsSubCommand {
+    fn run(self) -> io::Result<()> {
+        let mut selected = false;
+        for backend in Backend::ALL {
+            match backend.probe() {
+                Ok(()) if !selected => {
+                    selected = true;
+                    println!("{:<12} available (default)", backend);
+                },
+                Ok(()) => println!("{:<12} available", backend),
+                Err(Rejection { reason, .. }) => {
+                    println!("{:<12} unavailable: {}", backend, reason)
+                },
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Lists the connected monitors, along with what the backend tells about
+/// them: whether each is the primary one, its geometry, the size of its gamma
+/// ramps, and the vendor, model and serial number read from its EDID.
+#[derive(Debug, Clone, StructOpt)]
+pub struct MonitorsSubCommand {
+    /// Prints the monitors as JSON instead of a table.
+    #[structopt(long = "--json")]
+    json: bool,
+}
+
+impl GraphicalEnvContext for MonitorsSubCommand {
+    type Output = ();
+
+    fn with_graphical_env<G>(self, graphical_env: G) -> io::Result<Self::Output>
+    where
+        G: GraphicalEnv,
+    {
+        let monitors = graphical_env.list_monitors()?;
+        if self.json {
+            let json = serde_json::to_string_pretty(&monitors)
+                .map_err(io::Error::other)?;
+            println!("{}", json);
+        } else {
+            print!("{}", format_monitor_table(&monitors));
+        }
+        Ok(())
+    }
+}
+
+/// Controls a running service through its control socket.
+#[derive(Debug, Clone, StructOpt)]
+pub struct CtlSubCommand {
+    /// Path of the control socket of the service. If not given,
+    /// `$XDG_RUNTIME_DIR/circadianlight.sock` is used.
+    #[structopt(long = "--socket")]
+    #[structopt(parse(from_os_str))]
+    socket: Option<PathBuf>,
+    /// Command sent to the service.
+    #[structopt(subcommand)]
+    command: CtlCommand,
+}
+
+impl CtlSubCommand {
+    fn run(self) -> io::Result<()> {
+        let path = resolve_socket_path(self.socket)?;
+        let message = control::send(&path, &self.command.into())?;
+        if !message.is_empty() {
+            println!("{}", message);
+        }
+        Ok(())
+    }
+}
+
+/// Command sent to a running service.
+#[derive(Debug, Clone, StructOpt)]
+pub enum CtlCommand {
+    /// Stops following the schedule, restoring neutral colors, until it is
+    /// resumed or the pause expires.
+    Pause(ExpiryArgs),
+    /// Follows the schedule again, ending any pause or override.
+    Resume,
+    /// Prints the current state of the service.
+    Status,
+    /// Switches to the profile of the configuration file with the given name.
+    Profile {
+        /// Name of the profile.
+        name: String,
+    },
+    /// Overrides the scheduled colors until it is resumed or the override
+    /// expires.
+    Override {
+        /// Target of the colors, either `night`, `day`, `neutral`, or in the
+        /// format `red:green:blue` (e.g. `1.0:0.7:0.5`) or `KELVINK` (e.g.
+        /// `3400K`).
+        target: OverrideTarget,
+        /// When the override expires.
+        #[structopt(flatten)]
+        expiry: ExpiryArgs,
+    },
+    /// Reloads the configuration file.
+    Reload,
+}
+
+impl From<CtlCommand> for Command {
+    fn from(command: CtlCommand) -> Self {
+        match command {
+            CtlCommand::Pause(expiry) => Self::Pause(expiry.into()),
+            CtlCommand::Resume => Self::Resume,
+            CtlCommand::Status => Self::Status,
+            CtlCommand::Profile { name } => Self::Profile(name),
+            CtlCommand::Override { target, expiry } => {
+                Self::Override { target, expiry: expiry.into() }
             },
+            CtlCommand::Reload => Self::Reload,
+        }
+    }
+}
+
+/// Args for the expiry of a pause or of an override. If none is given, it
+/// does not expire.
+#[derive(Debug, Clone, StructOpt)]
+pub struct ExpiryArgs {
+    /// Minutes after which it expires.
+    #[structopt(long = "--minutes")]
+    #[structopt(conflicts_with = "until")]
+    minutes: Option<f64>,
+    /// Hour in the format `H:M` at which it expires, at its next occurrence.
+    #[structopt(long = "--until")]
+    #[structopt(parse(try_from_str = parse_time_arg))]
+    until: Option<NaiveTime>,
+}
+
+impl From<ExpiryArgs> for Expiry {
+    fn from(args: ExpiryArgs) -> Self {
+        match (args.minutes, args.until) {
+            (Some(minutes), _) => Self::After(minutes),
+            (None, Some(until)) => Self::Until(until),
+            (None, None) => Self::Never,
+        }
+    }
+}
+
+/// Formats the given monitors as a table, with a header and a line for each
+/// monitor, where unknown information is shown as `-`.
+fn format_monitor_table(monitors: &[Monitor]) -> String {
+    let unknown = || "-".to_owned();
+    let mut rows = vec![[
+        "NAME", "PRIMARY", "GEOMETRY", "RAMP", "VENDOR", "MODEL", "SERIAL",
+    ]
+    .map(str::to_owned)];
+    for monitor in monitors {
+        let edid = monitor.edid.as_ref();
+        rows.push([
+            monitor.name.clone(),
+            if monitor.primary { "yes" } else { "no" }.to_owned(),
+            monitor.geometry.map_or_else(unknown, |geometry| {
+                geometry.to_string()
+            }),
+            monitor.ramp_size.map_or_else(unknown, |size| size.to_string()),
+            edid.map_or_else(unknown, |edid| edid.vendor.clone()),
+            edid.and_then(|edid| edid.model.clone()).unwrap_or_else(unknown),
+            monitor.serial().map_or_else(unknown, str::to_owned),
+        ]);
+    }
+    let mut widths = [0; 7];
+    for row in &rows {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.chars().count());
+        }
+    }
+    let mut table = String::new();
+    for row in &rows {
+        let cells = row
+            .iter()
+            .zip(widths)
+            .map(|(cell, width)| format!("{:<width$}", cell, width = width))
+            .collect::<Vec<_>>();
+        table.push_str(cells.join("  ").trim_end());
+        table.push('\n');
+    }
+    table
+}
+
+/// Captures the gamma currently applied to each of the given monitors, such
+/// as to restore it later. Monitors whose gamma cannot be captured are assumed
+/// to have neutral colors.
+fn capture_gamma<G>(
+    graphical_env: G,
+    monitors: Vec<String>,
+) -> Vec<(String, CapturedGamma)>
+where
+    G: GraphicalEnv,
+{
+    monitors
+        .into_iter()
+        .map(|monitor| {
+            let captured =
+                graphical_env.capture_gamma(&monitor).unwrap_or_else(|error| {
+                    eprintln!("assuming neutral colors: {}", error);
+                    CapturedGamma::Adjustment(ColorAdjustment::default())
+                });
+            (monitor, captured)
+        })
+        .collect()
+}
+
+/// Restores the gamma previously captured for each monitor.
+fn restore_gamma<G>(graphical_env: G, captured: &[(String, CapturedGamma)])
+where
+    G: GraphicalEnv,
+{
+    for (monitor, captured) in captured {
+        if let Err(error) = graphical_env.restore_gamma(monitor, captured) {
+            eprintln!("could not restore the colors of {}: {}", monitor, error);
+        }
+    }
+}
+
+/// Checks that each of the given monitors is connected, so that misspelled
+/// monitors are not retried forever.
+fn check_monitors<G>(graphical_env: G, names: &[String]) -> io::Result<()>
+where
+    G: GraphicalEnv,
+{
+    let connected = graphical_env.list_monitor_names()?;
+    match names.iter().find(|name| !connected.contains(name)) {
+        Some(name) => Err(BackendError::unknown_output(name.as_str()).into()),
+        None => Ok(()),
+    }
+}
+
+/// Describes the given monitors, or all of the currently connected ones.
+/// Monitors are only described beyond their names when some configuration is
+/// specific to monitors, which may select them by serial, since describing
+/// them is much slower.
+fn resolve_monitors<G>(
+    graphical_env: G,
+    names: Option<&[String]>,
+    config: &Config,
+) -> Result<Vec<Monitor>, BackendError>
+where
+    G: GraphicalEnv,
+{
+    if config.monitors.is_empty() {
+        let monitors = match names {
+            Some(names) => names.iter().map(Monitor::new).collect(),
             None => {
-                let monitors = graphical_env.list_monitors()?;
-                graphical_env.apply_gamma(gamma, monitors)?;
+                let names = graphical_env.list_monitor_names()?;
+                names.into_iter().map(Monitor::new).collect()
             },
-        }
-        Ok(())
+        };
+        return Ok(monitors);
     }
+    let connected = graphical_env.list_monitors()?;
+    let Some(names) = names else { return Ok(connected) };
+    let monitors = names
+        .iter()
+        .map(|name| {
+            connected
+                .iter()
+                .find(|monitor| monitor.name == *name)
+                .cloned()
+                .unwrap_or_else(|| Monitor::new(name))
+        })
+        .collect();
+    Ok(monitors)
+}
+
+fn resolve_socket_path(socket: Option<PathBuf>) -> io::Result<PathBuf> {
+    socket.or_else(control::default_socket_path).ok_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::NotFound,
+            "no socket path given and $XDG_RUNTIME_DIR is not set",
+        )
+    })
 }
 
 fn parse_time_arg(arg: &str) -> chrono::format::ParseResult<NaiveTime> {
     NaiveTime::parse_from_str(arg, "%H:%M")
 }
 
-fn create_color_channels(config: Config, time: Option<NaiveTime>) -> [f64; 3] {
-    let hours = match time {
-        Some(offset) => timelike_to_hours(&offset),
-        None => timelike_to_hours(&Local::now()),
+fn parse_keyframe_arg(arg: &str) -> Result<Keyframe, String> {
+    let (time, target) = arg.split_once('=').ok_or_else(|| {
+        format!("expected H:M=red:green:blue or H:M=KELVINK, found {}", arg)
+    })?;
+    let time = parse_time_arg(time).map_err(|error| error.to_string())?;
+    let target = target.parse().map_err(|error| format!("{}", error))?;
+    Ok(Keyframe { time: timelike_to_hours(&time), target })
+}
+
+fn parse_brightness_keyframe_arg(arg: &str) -> Result<Keyframe<f64>, String> {
+    let (time, brightness) = arg
+        .split_once('=')
+        .ok_or_else(|| format!("expected H:M=LEVEL, found {}", arg))?;
+    let time = parse_time_arg(time).map_err(|error| error.to_string())?;
+    let brightness = brightness.parse().map_err(|error| format!("{}", error))?;
+    let brightness = BrightnessConfig::level(brightness)
+        .map_err(|error| error.to_string())?;
+    Ok(Keyframe { time: timelike_to_hours(&time), target: brightness })
+}
+
+fn create_color_adjustment(
+    config: &Config,
+    time: Option<NaiveTime>,
+) -> ColorAdjustment {
+    let now = Local::now();
+    let time = match time {
+        Some(time) => now
+            .date_naive()
+            .and_time(time)
+            .and_local_timezone(Local)
+            .earliest()
+            .unwrap_or(now),
+        None => now,
     };
-    gamma_function(config)(hours)
+    adjustment_function(config)(time.with_timezone(time.offset()))
+}
+
+/// Color adjustment of the given monitor, following its specific
+/// configuration, if any, at the given time (or now), under the given override
+/// target, if any. The calibration of the monitor is applied last, except when
+/// paused, so that a pause really restores neutral colors.
+fn create_monitor_adjustment(
+    config: &Config,
+    monitor: &Monitor,
+    time: Option<NaiveTime>,
+    target: Option<OverrideTarget>,
+) -> ColorAdjustment {
+    let monitor_config = config.for_monitor(monitor);
+    let scheduled = create_color_adjustment(&monitor_config, time);
+    match target {
+        Some(OverrideTarget::Neutral) => ColorAdjustment::default(),
+        Some(target) => target
+            .adjustment(&monitor_config, scheduled)
+            .calibrated(config.calibration(monitor)),
+        None => scheduled.calibrated(config.calibration(monitor)),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::{env, fs, io, process, sync::mpsc, thread};
+
+    use structopt::StructOpt;
+
+    use crate::{
+        channel::ColorAdjustment,
+        config::{
+            file::{ConfigFile, InvalidConfigFile},
+            ScheduleSource,
+        },
+        control::{self, Command, Request},
+        environment::{
+            middleware::DryRun,
+            BackendError,
+            CapturedGamma,
+            GraphicalEnv,
+            GraphicalEnvContext,
+        },
+        monitor::Monitor,
+        overrides::OverrideTarget,
+        solar::{Location, Twilight},
+    };
+
+    use super::{
+        capture_gamma,
+        create_monitor_adjustment,
+        ConfigArgs,
+        Program,
+        ResetSubCommand,
+        ServeEvent,
+        ServeState,
+        ServeSubCommand,
+    };
+
+    /// Environment with fixed monitors, failing to apply any colors, so that
+    /// it is only used through a dry run.
+    #[derive(Debug)]
+    struct Fixed;
+
+    impl GraphicalEnv for Fixed {
+        fn list_monitors(&self) -> Result<Vec<Monitor>, BackendError> {
+            Ok(vec![Monitor::new("eDP-1"), Monitor::new("HDMI-1")])
+        }
+
+        fn format_gamma(
+            &self,
+            adjustment: ColorAdjustment,
+        ) -> Result<String, BackendError> {
+            let gamma = adjustment.gamma;
+            Ok(format!("{}:{}:{}", gamma[0], gamma[1], gamma[2]))
+        }
+
+        fn read_gamma(
+            &self,
+            _monitor: &str,
+        ) -> Result<ColorAdjustment, BackendError> {
+            Ok(ColorAdjustment::default())
+        }
+
+        fn apply_gamma<I>(
+            &self,
+            _adjustment: ColorAdjustment,
+            _monitors: I,
+        ) -> Result<(), BackendError>
+        where
+            I: IntoIterator,
+            I::Item: AsRef<str>,
+        {
+            Err(io::Error::other("colors cannot be changed").into())
+        }
+    }
+
+    const WARM: ColorAdjustment = ColorAdjustment {
+        gamma: [1.0, 0.8, 0.6],
+        brightness: 0.9,
+        base_gamma: 1.0,
+    };
+
+    fn file_error(error: io::Error) -> Option<InvalidConfigFile> {
+        let error = error.into_inner()?.downcast::<InvalidConfigFile>().ok()?;
+        Some(*error)
+    }
+
+    #[test]
+    fn default_channels() {
+        let args = ConfigArgs::from_iter(["test"]);
+        let [red, green, blue] = args.create_channels_config(None).unwrap();
+        assert_eq!((red.min(), red.max()), (1.0, 1.0));
+        assert_eq!((green.min(), green.max()), (0.6, 1.0));
+        assert_eq!((blue.min(), blue.max()), (0.3, 1.0));
+    }
+
+    #[test]
+    fn invalid_values_point_to_the_rejected_key() {
+        let source = "[hours]\ndawn_start = \"04:00\"\n";
+        let file = ConfigFile::parse("config.toml", source, None).unwrap();
+        let args = ConfigArgs::from_iter(["test", "--day-start", "23:00"]);
+        let error = args.create_hour_config(Some(&file)).unwrap_err();
+        assert!(file_error(error).is_none());
+
+        let source =
+            "[hours]\ndawn_start = \"04:00\"\nnight_start = \"16:00\"\n";
+        let file = ConfigFile::parse("config.toml", source, None).unwrap();
+        let args = ConfigArgs::from_iter(["test"]);
+        let error = args.create_hour_config(Some(&file)).unwrap_err();
+        let error = file_error(error).unwrap();
+        assert_eq!(error.line, Some(3));
+        assert_eq!(error.key.as_deref(), Some("hours.night_start"));
+    }
+
+    #[test]
+    fn cli_location_keeps_file_sun_settings() {
+        let source = "[sun]\nlatitude = 10.0\nlongitude = 20.0\n\
+            offset = 30.0\ntwilight = \"nautical\"\n";
+        let file = ConfigFile::parse("config.toml", source, None).unwrap();
+        let args = ConfigArgs::from_iter([
+            "test",
+            "--latitude",
+            "51.5",
+            "--longitude",
+            "-0.1",
+        ]);
+        let source = args.create_schedule_source(Some(&file)).unwrap();
+        let ScheduleSource::Solar(solar) = source else {
+            panic!("expected a solar schedule, found {:?}", source)
+        };
+        assert_eq!(solar.location, Location::new(51.5, -0.1).unwrap());
+        assert_eq!(solar.twilight, Twilight::Nautical);
+        assert!((solar.offset - 30.0 / (24.0 * 60.0)).abs() < 1e-9);
+    }
+
+    #[test]
+    fn monitor_bounds_require_bounded_colors() {
+        let source = "[temperature]\nday = 6500\nnight = 3400\n\n\
+            [[monitors]]\nname = \"eDP-1\"\n\
+            channels = { blue = { min = 0.6 } }\n";
+        let file = ConfigFile::parse("config.toml", source, None).unwrap();
+        let args = ConfigArgs::from_iter(["test"]);
+        let error = args.create_config_from(Some(&file)).unwrap_err();
+        let error = file_error(error).unwrap();
+        assert_eq!(error.line, Some(7));
+        assert_eq!(error.key.as_deref(), Some("monitors[0].channels.blue.min"));
+
+        let source = "[[monitors]]\nname = \"eDP-1\"\n\
+            channels = { blue = { min = 0.6 } }\n";
+        let file = ConfigFile::parse("config.toml", source, None).unwrap();
+        args.create_config_from(Some(&file)).unwrap();
+    }
+
+    #[test]
+    fn pause_ignores_calibration() {
+        let source = "[[monitors]]\nname = \"eDP-1\"\n\
+            calibration = [1.0, 0.9, 0.8]\n";
+        let file = ConfigFile::parse("config.toml", source, None).unwrap();
+        let args = ConfigArgs::from_iter(["test"]);
+        let config = args.create_config_from(Some(&file)).unwrap();
+        let monitor = Monitor::new("eDP-1");
+        let target = Some(OverrideTarget::Neutral);
+        let adjustment =
+            create_monitor_adjustment(&config, &monitor, None, target);
+        assert_eq!(adjustment, ColorAdjustment::default());
+        let target = Some(OverrideTarget::Day);
+        let adjustment =
+            create_monitor_adjustment(&config, &monitor, None, target);
+        assert!((adjustment.gamma[2] - 0.8).abs() < 0.01);
+    }
+
+    #[test]
+    fn serve_restores_captured_colors_on_termination() {
+        let env = DryRun::new(Fixed);
+        env.apply_gamma(WARM, ["eDP-1"]).unwrap();
+        let neutral = ColorAdjustment::default();
+        let captured = capture_gamma(&env, env.list_monitor_names().unwrap());
+        assert_eq!(
+            captured,
+            [
+                ("eDP-1".to_owned(), CapturedGamma::Adjustment(WARM)),
+                ("HDMI-1".to_owned(), CapturedGamma::Adjustment(neutral)),
+            ],
+        );
+
+        let path = env::temp_dir()
+            .join(format!("circadianlight-serve-{}.toml", process::id()));
+        fs::write(&path, "").unwrap();
+        let serve = ServeSubCommand::from_iter([
+            "serve".as_ref(),
+            "--config".as_ref(),
+            path.as_os_str(),
+            "--keyframe".as_ref(),
+            "00:00=3400K".as_ref(),
+            "--fade-duration".as_ref(),
+            "0".as_ref(),
+        ]);
+        let mut state =
+            ServeState::new(serve.config_args.clone(), None, captured.clone())
+                .unwrap();
+        fs::remove_file(&path).unwrap();
+        let (sender, events) = mpsc::channel();
+        sender.send(ServeEvent::Terminate).unwrap();
+        serve.serve(&env, &mut None, &mut state, &sender, &events).unwrap();
+        assert_ne!(env.read_gamma("eDP-1").unwrap(), WARM);
+        assert_ne!(env.read_gamma("HDMI-1").unwrap(), neutral);
+
+        sender.send(ServeEvent::Terminate).unwrap();
+        serve.run(&env, &mut state, &sender, &events).unwrap();
+        assert_eq!(env.read_gamma("eDP-1").unwrap(), WARM);
+        assert_eq!(env.read_gamma("HDMI-1").unwrap(), neutral);
+    }
+
+    #[test]
+    fn serve_fails_on_unknown_monitors() {
+        let env = DryRun::new(Fixed);
+        let serve =
+            ServeSubCommand::from_iter(["serve", "-m", "eDP-1", "HMDI-1"]);
+        let error = serve.with_graphical_env(&env).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::NotFound);
+        assert!(error.to_string().contains("HMDI-1"));
+    }
+
+    #[test]
+    fn serve_captures_colors_of_new_monitors() {
+        let env = DryRun::new(Fixed);
+        env.apply_gamma(WARM, ["HDMI-1"]).unwrap();
+        let captured = capture_gamma(&env, vec!["eDP-1".to_owned()]);
+        let path = env::temp_dir()
+            .join(format!("circadianlight-capture-{}.toml", process::id()));
+        fs::write(&path, "").unwrap();
+        let args = ConfigArgs::from_iter([
+            "test".as_ref(),
+            "--config".as_ref(),
+            path.as_os_str(),
+            "--fade-duration".as_ref(),
+            "0".as_ref(),
+        ]);
+        let mut state = ServeState::new(args, None, captured).unwrap();
+        fs::remove_file(&path).unwrap();
+        let monitors = env.list_monitors().unwrap();
+        state.apply(&env, monitors).unwrap();
+        assert_ne!(env.read_gamma("HDMI-1").unwrap(), WARM);
+        let neutral = ColorAdjustment::default();
+        assert_eq!(
+            state.original,
+            [
+                ("eDP-1".to_owned(), CapturedGamma::Adjustment(neutral)),
+                ("HDMI-1".to_owned(), CapturedGamma::Adjustment(WARM)),
+            ],
+        );
+    }
+
+    #[test]
+    fn reset_chosen_monitors() {
+        let env = DryRun::new(Fixed);
+        env.apply_gamma(WARM, ["eDP-1", "HDMI-1"]).unwrap();
+        let reset = ResetSubCommand::from_iter(["reset", "-m", "HDMI-1"]);
+        reset.with_graphical_env(&env).unwrap();
+        assert_eq!(env.read_gamma("eDP-1").unwrap(), WARM);
+        let neutral = ColorAdjustment::default();
+        assert_eq!(env.read_gamma("HDMI-1").unwrap(), neutral);
+
+        ResetSubCommand::from_iter(["reset"]).with_graphical_env(&env).unwrap();
+        assert_eq!(env.read_gamma("eDP-1").unwrap(), neutral);
+    }
+
+    /// The KMS backend is unavailable with a display server, and without a
+    /// DRM card, so it is rejected wherever the tests run, in practice.
+    #[test]
+    fn ctl_does_not_load_backends() {
+        let path = env::temp_dir()
+            .join(format!("circadianlight-ctl-{}.sock", process::id()));
+        let (sender, receiver) = mpsc::channel::<Request>();
+        let _listener = control::listen(&path, sender).unwrap();
+        thread::spawn(move || {
+            for request in receiver {
+                let reply = match &request.command {
+                    Command::Status => Ok("paused=false".to_owned()),
+                    command => Err(format!("cannot {}", command)),
+                };
+                request.reply(reply);
+            }
+        });
+        let socket = path.to_str().unwrap();
+        let program = Program::from_iter([
+            "circadianlight",
+            "--backend",
+            "kms",
+            "ctl",
+            "--socket",
+            socket,
+            "status",
+        ]);
+        program.run().unwrap();
+    }
 }
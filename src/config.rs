@@ -1,7 +1,43 @@
 //! Configuration for the execution of the program, including configuration for
 //! the day phases, as well for channels minimum and maximum.
 
-use std::{error::Error, fmt};
+use std::{error::Error, fmt, time::Duration};
+
+use chrono::{DateTime, FixedOffset, NaiveDate, TimeZone};
+
+use crate::{
+    channel::map_channel_vector,
+    curve::TransitionCurve,
+    hour::cycle_distance,
+    monitor::Monitor,
+    schedule::{ColorTarget, Interpolate, Schedule},
+    solar::{self, Crossing, Location, SolarEvents, Twilight},
+    temperature::{MAX_TEMPERATURE, MIN_TEMPERATURE, NEUTRAL_TEMPERATURE},
+};
+
+pub mod file;
+
+/// Maximum duration of a fade, in seconds.
+pub const MAX_FADE_DURATION: f64 = 24.0 * 60.0 * 60.0;
+
+/// Maximum rate of a fade, in steps per second.
+pub const MAX_FADE_RATE: f64 = 1000.0;
+
+/// Minimum rate of a fade, in steps per second, that is, at least one step
+/// in the longest fade.
+pub const MIN_FADE_RATE: f64 = 1.0 / MAX_FADE_DURATION;
+
+/// Error yielded when a configuration is created from invalid values, telling
+/// which of them may be the rejected one.
+pub trait RejectedValue: Error {
+    /// Whether the value at the given index, in the order documented by the
+    /// error, may be the rejected one, that is, whether replacing it alone
+    /// could make the configuration valid. Errors of a single value always
+    /// reject it.
+    fn rejects(&self, _index: usize) -> bool {
+        true
+    }
+}
 
 /// Error yielded when an invalid day phase cycle is given during a
 /// [`HourConfig`].
@@ -16,6 +52,9 @@ pub struct InvalidDayPhases {
     /// Starting hour of the night phase, divided por 24h (in the interval
     /// `[0,1)`).
     pub night_start: f64,
+    /// Starting hour of the dawn phase, divided por 24h (in the interval
+    /// `[0,1)`).
+    pub dawn_start: f64,
 }
 
 impl fmt::Display for InvalidDayPhases {
@@ -23,15 +62,32 @@ impl fmt::Display for InvalidDayPhases {
         write!(
             fmtr,
             "Invalid day phases sequence, expected a cycle of day -> dusk -> \
-             night -> day; on an interval [0.0, 1.0), given day start: {}, \
-             dusk start: {}, night start: {}",
-            self.day_start, self.dusk_start, self.night_start
+             night -> dawn -> day; on an interval [0.0, 1.0), given day \
+             start: {}, dusk start: {}, night start: {}, dawn start: {}",
+            self.day_start, self.dusk_start, self.night_start, self.dawn_start
         )
     }
 }
 
 impl Error for InvalidDayPhases {}
 
+/// Values are in the order: day start, dusk start, night start, dawn start.
+impl RejectedValue for InvalidDayPhases {
+    fn rejects(&self, index: usize) -> bool {
+        let cycle = [
+            self.day_start,
+            self.dusk_start,
+            self.night_start,
+            self.dawn_start,
+        ];
+        let others = (0..cycle.len())
+            .filter(|&i| i != index)
+            .map(|i| cycle[i])
+            .collect::<Vec<_>>();
+        cycle_wraps(&others) <= 1
+    }
+}
+
 /// Error yielded when invalid channel bounds are found during the creation of a
 /// [`ChannelConfig`].
 #[derive(Debug, Clone)]
@@ -55,6 +111,215 @@ impl fmt::Display for InvalidChannelBounds {
 
 impl Error for InvalidChannelBounds {}
 
+/// Values are in the order: min, max.
+impl RejectedValue for InvalidChannelBounds {}
+
+/// Error yielded when invalid sun elevations are given during the creation of
+/// an [`ElevationConfig`].
+#[derive(Debug, Clone)]
+pub struct InvalidElevations {
+    /// Given elevation above which it is full day, in degrees.
+    pub day: f64,
+    /// Given elevation below which it is full night, in degrees.
+    pub night: f64,
+}
+
+impl fmt::Display for InvalidElevations {
+    fn fmt(&self, fmtr: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            fmtr,
+            "Invalid sun elevations, expected night < day; on an interval \
+             [-90.0, 90.0], given day: {}, night: {}",
+            self.day, self.night
+        )
+    }
+}
+
+impl Error for InvalidElevations {}
+
+/// Values are in the order: day, night.
+impl RejectedValue for InvalidElevations {
+    fn rejects(&self, index: usize) -> bool {
+        match index {
+            0 => -90.0 <= self.night && self.night < 90.0,
+            _ => -90.0 < self.day && self.day <= 90.0,
+        }
+    }
+}
+
+/// Error yielded when invalid color temperatures are given during the creation
+/// of a [`TemperatureConfig`].
+#[derive(Debug, Clone)]
+pub struct InvalidTemperatures {
+    /// Given color temperature of the day, in Kelvin.
+    pub day: f64,
+    /// Given color temperature of the night, in Kelvin.
+    pub night: f64,
+}
+
+impl fmt::Display for InvalidTemperatures {
+    fn fmt(&self, fmtr: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            fmtr,
+            "Invalid color temperatures, expected night <= day; on an \
+             interval [{}, {}], given day: {}, night: {}",
+            MIN_TEMPERATURE, MAX_TEMPERATURE, self.day, self.night
+        )
+    }
+}
+
+impl Error for InvalidTemperatures {}
+
+/// Values are in the order: day, night.
+impl RejectedValue for InvalidTemperatures {
+    fn rejects(&self, index: usize) -> bool {
+        let range = MIN_TEMPERATURE..=MAX_TEMPERATURE;
+        match index {
+            0 => range.contains(&self.night),
+            _ => range.contains(&self.day),
+        }
+    }
+}
+
+/// Error yielded when invalid brightness levels are given during the creation
+/// of a [`BrightnessConfig`].
+#[derive(Debug, Clone)]
+pub struct InvalidBrightness {
+    /// Given brightness of the day (expected in the interval `(0,1]`).
+    pub day: f64,
+    /// Given brightness of the night (expected in the interval `(0,1]`).
+    pub night: f64,
+}
+
+impl fmt::Display for InvalidBrightness {
+    fn fmt(&self, fmtr: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            fmtr,
+            "Invalid brightness levels, expected night <= day; on an \
+             interval (0.0, 1.0], given day: {}, night: {}",
+            self.day, self.night
+        )
+    }
+}
+
+impl Error for InvalidBrightness {}
+
+/// Values are in the order: day, night.
+impl RejectedValue for InvalidBrightness {
+    fn rejects(&self, index: usize) -> bool {
+        match index {
+            0 => 0.0 < self.night && self.night <= 1.0,
+            _ => 0.0 < self.day && self.day <= 1.0,
+        }
+    }
+}
+
+/// Error yielded when an invalid brightness level is given, such as in a
+/// keyframe of a brightness schedule.
+#[derive(Debug, Clone)]
+pub struct InvalidBrightnessLevel {
+    /// Given brightness level (expected in the interval `(0,1]`).
+    pub level: f64,
+}
+
+impl fmt::Display for InvalidBrightnessLevel {
+    fn fmt(&self, fmtr: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            fmtr,
+            "Invalid brightness level, expected on an interval (0.0, 1.0], \
+             given: {}",
+            self.level
+        )
+    }
+}
+
+impl Error for InvalidBrightnessLevel {}
+
+/// Error yielded when an invalid fade duration or rate is given during a
+/// [`FadeConfig`].
+#[derive(Debug, Clone)]
+pub struct InvalidFade {
+    /// Given duration in seconds (expected in the interval
+    /// `[0,MAX_FADE_DURATION]`).
+    pub duration: f64,
+    /// Given rate in steps per second (expected in the interval
+    /// `[MIN_FADE_RATE,MAX_FADE_RATE]`).
+    pub rate: f64,
+}
+
+impl fmt::Display for InvalidFade {
+    fn fmt(&self, fmtr: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            fmtr,
+            "Invalid fade, expected duration on an interval [0, {}] and \
+             rate on an interval [{}, {}], given duration: {}, rate: {}",
+            MAX_FADE_DURATION,
+            MIN_FADE_RATE,
+            MAX_FADE_RATE,
+            self.duration,
+            self.rate
+        )
+    }
+}
+
+impl Error for InvalidFade {}
+
+/// Values are in the order: duration, rate.
+impl RejectedValue for InvalidFade {
+    fn rejects(&self, index: usize) -> bool {
+        match index {
+            0 => !FadeConfig::is_valid_duration(self.duration),
+            _ => !FadeConfig::is_valid_rate(self.rate),
+        }
+    }
+}
+
+/// Error yielded when an invalid base gamma is given during the creation of a
+/// [`RampConfig`].
+#[derive(Debug, Clone)]
+pub struct InvalidRamp {
+    /// Given base gamma (expected to be positive).
+    pub base_gamma: f64,
+}
+
+impl fmt::Display for InvalidRamp {
+    fn fmt(&self, fmtr: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            fmtr,
+            "Invalid ramp, expected base gamma > 0, given base gamma: {}",
+            self.base_gamma
+        )
+    }
+}
+
+impl Error for InvalidRamp {}
+
+impl RejectedValue for InvalidRamp {}
+
+/// Error yielded when an invalid calibration is given during the creation of a
+/// [`MonitorConfig`].
+#[derive(Debug, Clone)]
+pub struct InvalidCalibration {
+    /// Given multiplier of each color channel (expected to be positive), in
+    /// the order: red, green, blue.
+    pub calibration: [f64; 3],
+}
+
+impl fmt::Display for InvalidCalibration {
+    fn fmt(&self, fmtr: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            fmtr,
+            "Invalid calibration, expected positive multipliers, given red: \
+             {}, green: {}, blue: {}",
+            self.calibration[0], self.calibration[1], self.calibration[2]
+        )
+    }
+}
+
+impl Error for InvalidCalibration {}
+
+impl RejectedValue for InvalidCalibration {}
+
 /// Configuration used for customizing starting hour of each day phase. See
 /// [`DayPhase`](crate::hour::DayPhase).
 #[derive(Debug, Clone, Copy, PartialEq, PartialOrd)]
@@ -62,6 +327,7 @@ pub struct HourConfig {
     day_start: f64,
     dusk_start: f64,
     night_start: f64,
+    dawn_start: f64,
 }
 
 impl Default for HourConfig {
@@ -70,29 +336,36 @@ impl Default for HourConfig {
             day_start: 5.0 / 24.0,
             dusk_start: 17.0 / 24.0,
             night_start: 21.0 / 24.0,
+            dawn_start: 4.0 / 24.0,
         }
     }
 }
 
 impl HourConfig {
     /// Creates a new hour configuration, given hour of the day phase start,
-    /// hour of the dusk phase start and hour of the night phase start,
-    /// compressed in the interval `[0,1)`, i.e. divided by `24h`.
-    /// Note that, wraping around `24h`, the order `day -> dusk -> night -> day`
-    /// should be respected, i.e. `day <= dusk <= night`, `dusk <= night <=
-    /// day`, `night <= day <= dusk` are valid, but otherwise not.
+    /// hour of the dusk phase start, hour of the night phase start and hour of
+    /// the dawn phase start, compressed in the interval `[0,1)`, i.e. divided
+    /// by `24h`.
+    /// Note that, wraping around `24h`, the order `day -> dusk -> night -> dawn
+    /// -> day` should be respected, i.e. `day <= dusk <= night <= dawn`,
+    /// `dusk <= night <= dawn <= day`, `night <= dawn <= day <= dusk` and
+    /// `dawn <= day <= dusk <= night` are valid, but otherwise not.
     pub fn new(
         day_start: f64,
         dusk_start: f64,
         night_start: f64,
+        dawn_start: f64,
     ) -> Result<Self, InvalidDayPhases> {
-        if day_start <= dusk_start && dusk_start <= night_start
-            || night_start <= day_start && day_start <= dusk_start
-            || dusk_start <= night_start && night_start <= day_start
-        {
-            Ok(Self { day_start, dusk_start, night_start })
+        let cycle = [day_start, dusk_start, night_start, dawn_start];
+        if cycle_wraps(&cycle) <= 1 {
+            Ok(Self { day_start, dusk_start, night_start, dawn_start })
         } else {
-            Err(InvalidDayPhases { day_start, dusk_start, night_start })
+            Err(InvalidDayPhases {
+                day_start,
+                dusk_start,
+                night_start,
+                dawn_start,
+            })
         }
     }
 
@@ -113,6 +386,20 @@ impl HourConfig {
     pub fn night_start(self) -> f64 {
         self.night_start
     }
+
+    /// Starting hour of the dawn phase, compressed `24h` into the interval
+    /// `[0,1)`.
+    pub fn dawn_start(self) -> f64 {
+        self.dawn_start
+    }
+}
+
+/// Number of times the given hours, in the order of a cycle, wrap around
+/// `24h`, including from the last one back to the first one.
+fn cycle_wraps(cycle: &[f64]) -> usize {
+    (0..cycle.len())
+        .filter(|&i| cycle[(i + 1) % cycle.len()] < cycle[i])
+        .count()
 }
 
 /// Configuration of a color channel.
@@ -154,13 +441,539 @@ impl ChannelConfig {
     }
 }
 
-/// General configuration of the application.
+/// Configuration of the color temperatures of the day and of the night, as an
+/// alternative to color channels bounds.
+#[derive(Debug, Clone, Copy, PartialEq, PartialOrd)]
+pub struct TemperatureConfig {
+    day: f64,
+    night: f64,
+}
+
+impl Default for TemperatureConfig {
+    fn default() -> Self {
+        Self { day: NEUTRAL_TEMPERATURE, night: 3400.0 }
+    }
+}
+
+impl TemperatureConfig {
+    /// Creates the configuration of color temperatures, given the temperature
+    /// of the day and of the night, in Kelvin.
+    /// Note that `night <= day` is a restriction that must be respected.
+    pub fn new(day: f64, night: f64) -> Result<Self, InvalidTemperatures> {
+        if MIN_TEMPERATURE <= night && night <= day && day <= MAX_TEMPERATURE {
+            Ok(Self { day, night })
+        } else {
+            Err(InvalidTemperatures { day, night })
+        }
+    }
+
+    /// Color temperature of the day, in Kelvin.
+    pub fn day(self) -> f64 {
+        self.day
+    }
+
+    /// Color temperature of the night, in Kelvin.
+    pub fn night(self) -> f64 {
+        self.night
+    }
+}
+
+/// Configuration of the brightness of the day and of the night, applied as a
+/// multiplier over the color channels.
+#[derive(Debug, Clone, Copy, PartialEq, PartialOrd)]
+pub struct BrightnessConfig {
+    day: f64,
+    night: f64,
+}
+
+impl Default for BrightnessConfig {
+    fn default() -> Self {
+        Self { day: 1.0, night: 1.0 }
+    }
+}
+
+impl BrightnessConfig {
+    /// Creates the configuration of brightness, given the brightness of the
+    /// day and of the night, in the interval `(0,1]`.
+    /// Note that `night <= day` is a restriction that must be respected.
+    pub fn new(day: f64, night: f64) -> Result<Self, InvalidBrightness> {
+        if 0.0 < night && night <= day && day <= 1.0 {
+            Ok(Self { day, night })
+        } else {
+            Err(InvalidBrightness { day, night })
+        }
+    }
+
+    /// Brightness of the day, in the interval `(0,1]`.
+    pub fn day(self) -> f64 {
+        self.day
+    }
+
+    /// Brightness of the night, in the interval `(0,1]`.
+    pub fn night(self) -> f64 {
+        self.night
+    }
+
+    /// Checks a single brightness level, such as the target of a keyframe of
+    /// a brightness schedule, which must be in the interval `(0,1]`.
+    pub fn level(level: f64) -> Result<f64, InvalidBrightnessLevel> {
+        if 0.0 < level && level <= 1.0 {
+            Ok(level)
+        } else {
+            Err(InvalidBrightnessLevel { level })
+        }
+    }
+}
+
+/// Configuration of the fades from the current colors to new ones, such as
+/// on startup or when the profile changes.
+#[derive(Debug, Clone, Copy, PartialEq, PartialOrd)]
+pub struct FadeConfig {
+    duration: f64,
+    rate: f64,
+}
+
+impl Default for FadeConfig {
+    fn default() -> Self {
+        Self { duration: 2.0, rate: 20.0 }
+    }
+}
+
+impl FadeConfig {
+    /// Creates the configuration of fades, given their duration in seconds,
+    /// where zero disables them, up to a day, and their rate in steps per
+    /// second, up to a thousand, with at least one step per day.
+    pub fn new(duration: f64, rate: f64) -> Result<Self, InvalidFade> {
+        if Self::is_valid_duration(duration) && Self::is_valid_rate(rate) {
+            Ok(Self { duration, rate })
+        } else {
+            Err(InvalidFade { duration, rate })
+        }
+    }
+
+    fn is_valid_duration(duration: f64) -> bool {
+        (0.0..=MAX_FADE_DURATION).contains(&duration)
+    }
+
+    fn is_valid_rate(rate: f64) -> bool {
+        (MIN_FADE_RATE..=MAX_FADE_RATE).contains(&rate)
+    }
+
+    /// Duration of a fade.
+    pub fn duration(self) -> Duration {
+        Duration::from_secs_f64(self.duration)
+    }
+
+    /// Number of steps per second of a fade.
+    pub fn rate(self) -> f64 {
+        self.rate
+    }
+
+    /// Interval between two steps of a fade.
+    pub fn step(self) -> Duration {
+        Duration::from_secs_f64(1.0 / self.rate)
+    }
+}
+
+/// Configuration of the gamma ramps set on the screens, besides the
+/// multipliers of the color channels and the brightness.
 #[derive(Debug, Clone, Copy, PartialEq, PartialOrd)]
+pub struct RampConfig {
+    base_gamma: f64,
+}
+
+impl Default for RampConfig {
+    fn default() -> Self {
+        Self { base_gamma: 1.0 }
+    }
+}
+
+impl RampConfig {
+    /// Creates the configuration of gamma ramps, given the exponent shaping
+    /// the curve of every color channel, as given to `xrandr --gamma`.
+    /// Note that it must be positive.
+    pub fn new(base_gamma: f64) -> Result<Self, InvalidRamp> {
+        if base_gamma > 0.0 && base_gamma.is_finite() {
+            Ok(Self { base_gamma })
+        } else {
+            Err(InvalidRamp { base_gamma })
+        }
+    }
+
+    /// Exponent shaping the curve of every color channel, where `1.0` keeps
+    /// the curve straight.
+    pub fn base_gamma(self) -> f64 {
+        self.base_gamma
+    }
+}
+
+/// Criterion selecting the monitors a [`MonitorConfig`] applies to.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd)]
+pub enum MonitorMatch {
+    /// Monitors plugged into the connector or output with the given name,
+    /// such as `eDP-1`.
+    Name(String),
+    /// Monitors whose EDID has the given serial number, wherever they are
+    /// plugged.
+    Serial(String),
+}
+
+impl MonitorMatch {
+    /// Whether the given monitor is selected by this criterion.
+    pub fn matches(&self, monitor: &Monitor) -> bool {
+        match self {
+            Self::Name(name) => monitor.name == *name,
+            Self::Serial(serial) => monitor.serial() == Some(serial.as_str()),
+        }
+    }
+}
+
+impl fmt::Display for MonitorMatch {
+    fn fmt(&self, fmtr: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Name(name) => write!(fmtr, "name {}", name),
+            Self::Serial(serial) => write!(fmtr, "serial {}", serial),
+        }
+    }
+}
+
+/// Configuration of the monitors selected by some criterion, overriding the
+/// color channels bounds and the brightness of the general configuration,
+/// along with a static calibration of the color channels, such as to make
+/// panels with different tints look alike.
+#[derive(Debug, Clone, PartialEq, PartialOrd)]
+pub struct MonitorConfig {
+    matcher: MonitorMatch,
+    channels: [ChannelConfig; 3],
+    brightness: BrightnessConfig,
+    calibration: [f64; 3],
+}
+
+impl MonitorConfig {
+    /// Creates the configuration of the monitors selected by the given
+    /// criterion, given their color channels bounds, their brightness and the
+    /// calibration multiplier of each color channel.
+    /// Note that multipliers must be positive.
+    pub fn new(
+        matcher: MonitorMatch,
+        channels: [ChannelConfig; 3],
+        brightness: BrightnessConfig,
+        calibration: [f64; 3],
+    ) -> Result<Self, InvalidCalibration> {
+        let valid = calibration
+            .iter()
+            .all(|&multiplier| multiplier > 0.0 && multiplier.is_finite());
+        if valid {
+            Ok(Self { matcher, channels, brightness, calibration })
+        } else {
+            Err(InvalidCalibration { calibration })
+        }
+    }
+
+    /// Criterion selecting the monitors.
+    pub fn matcher(&self) -> &MonitorMatch {
+        &self.matcher
+    }
+
+    /// Configuration of color channels, in the order: red, green, blue.
+    pub fn channels(&self) -> [ChannelConfig; 3] {
+        self.channels
+    }
+
+    /// Configuration of the brightness of the day and of the night.
+    pub fn brightness(&self) -> BrightnessConfig {
+        self.brightness
+    }
+
+    /// Multiplier applied over each computed color channel, in the order:
+    /// red, green, blue.
+    pub fn calibration(&self) -> [f64; 3] {
+        self.calibration
+    }
+}
+
+/// Day phases derived from the position of the sun in a given day.
+#[derive(Debug, Clone, Copy, PartialEq, PartialOrd)]
+pub enum SolarHours {
+    /// The usual cycle of day phases.
+    Cycle(HourConfig),
+    /// The sun never sets, the whole day is a day phase.
+    PolarDay,
+    /// The sun never reaches the twilight elevation, the whole day is a night
+    /// phase.
+    PolarNight,
+}
+
+/// Configuration used for deriving day phases from sunrise, sunset and
+/// twilights at a given location.
+#[derive(Debug, Clone, Copy, PartialEq, PartialOrd)]
+pub struct SolarConfig {
+    /// Location whose sun position is followed.
+    pub location: Location,
+    /// Twilight whose start is the dawn start and whose end is the night
+    /// start.
+    pub twilight: Twilight,
+    /// Offset after sunrise for the day start, and before sunset for the dusk
+    /// start, compressed `24h` into the interval `[0,1)`.
+    pub offset: f64,
+}
+
+impl SolarConfig {
+    /// Derives the day phases for the given date, with hours in the local time
+    /// given by the offset from UTC. The dawn starts with the twilight, the
+    /// day starts after the sunrise, the dusk starts before the sunset, and
+    /// the night starts at the end of the twilight.
+    pub fn hour_config(
+        self,
+        date: NaiveDate,
+        utc_offset: FixedOffset,
+    ) -> SolarHours {
+        let events = SolarEvents::compute(self.location, date, utc_offset);
+        let midnight = (events.noon + 0.5).rem_euclid(1.0);
+        let (dawn_start, night_start) = match events.twilight(self.twilight) {
+            Crossing::Times { rise, set } => (rise, set),
+            Crossing::AlwaysAbove => (midnight, midnight),
+            Crossing::AlwaysBelow => return SolarHours::PolarNight,
+        };
+        let (day_start, dusk_start) = match events.sun {
+            Crossing::Times { rise, set } => {
+                let half_day = cycle_distance(rise, set) / 2.0;
+                let offset = self.offset.max(0.0);
+                if offset < half_day {
+                    let day_start = (rise + offset).rem_euclid(1.0);
+                    let dusk_start = (set - offset).rem_euclid(1.0);
+                    (day_start, dusk_start)
+                } else {
+                    (events.noon, events.noon)
+                }
+            },
+            Crossing::AlwaysAbove => return SolarHours::PolarDay,
+            Crossing::AlwaysBelow => (events.noon, events.noon),
+        };
+        let hours =
+            HourConfig::new(day_start, dusk_start, night_start, dawn_start)
+                .expect("solar events should follow the day phases cycle");
+        SolarHours::Cycle(hours)
+    }
+}
+
+/// Configuration used for following the sun elevation at a given location,
+/// instead of day phases.
+#[derive(Debug, Clone, Copy, PartialEq, PartialOrd)]
+pub struct ElevationConfig {
+    location: Location,
+    day: f64,
+    night: f64,
+}
+
+impl ElevationConfig {
+    /// Creates a new sun elevation configuration, given the location whose sun
+    /// is followed, the elevation above which it is full day, and the
+    /// elevation below which it is full night, both in degrees.
+    /// Note that `night < day` is a restriction that must be respected.
+    pub fn new(
+        location: Location,
+        day: f64,
+        night: f64,
+    ) -> Result<Self, InvalidElevations> {
+        if -90.0 <= night && night < day && day <= 90.0 {
+            Ok(Self { location, day, night })
+        } else {
+            Err(InvalidElevations { day, night })
+        }
+    }
+
+    /// Location whose sun is followed.
+    pub fn location(self) -> Location {
+        self.location
+    }
+
+    /// Elevation of the sun above which it is full day, in degrees.
+    pub fn day(self) -> f64 {
+        self.day
+    }
+
+    /// Elevation of the sun below which it is full night, in degrees.
+    pub fn night(self) -> f64 {
+        self.night
+    }
+
+    /// Computes how much it is day at the given instant, in the interval
+    /// `[0,1]`, given `0` for full night and `1` for full day.
+    pub fn daylight<Tz>(self, time: &DateTime<Tz>) -> f64
+    where
+        Tz: TimeZone,
+    {
+        let elevation = solar::elevation(self.location, time);
+        ((elevation - self.night) / (self.day - self.night)).clamp(0.0, 1.0)
+    }
+}
+
+/// Source of the schedule followed by the gamma color spectrum along the day.
+#[derive(Debug, Clone, PartialEq, PartialOrd)]
+pub enum ScheduleSource {
+    /// Schedule built from the day phases and color channels bounds of the
+    /// configuration.
+    Hours,
+    /// Custom schedule made of arbitrary keyframes.
+    Keyframes(Schedule),
+    /// Schedule built from day phases derived from the sun position, and color
+    /// channels bounds of the configuration.
+    Solar(SolarConfig),
+    /// No schedule along the day hours, color channels bounds of the
+    /// configuration are interpolated according to the sun elevation.
+    Elevation(ElevationConfig),
+}
+
+/// General configuration of the application.
+#[derive(Debug, Clone, PartialEq, PartialOrd)]
 pub struct Config {
     /// Configuration of day phases.
     pub hours: HourConfig,
     /// Configuration of color channels, in the order: red, green, blue.
     pub channels: [ChannelConfig; 3],
+    /// Configuration of color temperatures. If given, color channels bounds
+    /// are ignored.
+    pub temperature: Option<TemperatureConfig>,
+    /// Configuration of the brightness of the day and of the night.
+    pub brightness: BrightnessConfig,
+    /// Custom schedule of the brightness, independent from the schedule
+    /// source. If given, the brightness configuration is ignored.
+    pub brightness_schedule: Option<Schedule<f64>>,
+    /// Curve used in transitions, such as the dawn and the dusk.
+    pub curve: TransitionCurve,
+    /// Source of the schedule actually followed.
+    pub source: ScheduleSource,
+    /// Configuration of the fades between colors.
+    pub fade: FadeConfig,
+    /// Configuration of the gamma ramps.
+    pub ramp: RampConfig,
+    /// Configurations of specific monitors. The first one matching a monitor
+    /// overrides this configuration for it.
+    pub monitors: Vec<MonitorConfig>,
+}
+
+impl Config {
+    /// Configuration specific to the given monitor, that is, the first one
+    /// matching it, if any.
+    pub fn monitor_config(&self, monitor: &Monitor) -> Option<&MonitorConfig> {
+        self.monitors.iter().find(|config| config.matcher.matches(monitor))
+    }
+
+    /// Configuration of the given monitor, that is, this configuration with
+    /// the color channels bounds and the brightness of the configuration
+    /// specific to the monitor, if any.
+    pub fn for_monitor(&self, monitor: &Monitor) -> Config {
+        let mut config = self.clone();
+        if let Some(monitor_config) = self.monitor_config(monitor) {
+            config.channels = monitor_config.channels;
+            config.brightness = monitor_config.brightness;
+        }
+        config
+    }
+
+    /// Calibration multiplier of each color channel of the given monitor,
+    /// which is neutral unless a configuration specific to the monitor says
+    /// otherwise.
+    pub fn calibration(&self, monitor: &Monitor) -> [f64; 3] {
+        self.monitor_config(monitor)
+            .map_or([1.0; 3], |monitor_config| monitor_config.calibration)
+    }
+
+    /// Target of the color spectrum during the night.
+    pub fn night_target(&self) -> ColorTarget {
+        match self.temperature {
+            Some(temperature) => ColorTarget::Temperature(temperature.night()),
+            None => ColorTarget::Gamma(map_channel_vector(
+                self.channels,
+                ChannelConfig::min,
+            )),
+        }
+    }
+
+    /// Target of the color spectrum during the day.
+    pub fn day_target(&self) -> ColorTarget {
+        match self.temperature {
+            Some(temperature) => ColorTarget::Temperature(temperature.day()),
+            None => ColorTarget::Gamma(map_channel_vector(
+                self.channels,
+                ChannelConfig::max,
+            )),
+        }
+    }
+
+    /// Builds the schedule followed by the gamma color spectrum according to
+    /// this configuration, for the given date, with hours in the local time
+    /// given by the offset from UTC. Yields nothing if the source does not
+    /// follow the day hours, such as the sun elevation.
+    pub fn schedule_on(
+        &self,
+        date: NaiveDate,
+        utc_offset: FixedOffset,
+    ) -> Option<Schedule> {
+        match &self.source {
+            ScheduleSource::Keyframes(schedule) => {
+                Some(schedule.clone().with_curve(self.curve))
+            },
+            _ => self.phases_schedule_on(
+                date,
+                utc_offset,
+                self.night_target(),
+                self.day_target(),
+            ),
+        }
+    }
+
+    /// Builds the schedule followed by the brightness according to this
+    /// configuration, for the given date, with hours in the local time given
+    /// by the offset from UTC. Yields nothing if the source does not follow
+    /// the day hours, such as the sun elevation.
+    pub fn brightness_schedule_on(
+        &self,
+        date: NaiveDate,
+        utc_offset: FixedOffset,
+    ) -> Option<Schedule<f64>> {
+        match &self.brightness_schedule {
+            Some(schedule) => Some(schedule.clone().with_curve(self.curve)),
+            None => self.phases_schedule_on(
+                date,
+                utc_offset,
+                self.brightness.night(),
+                self.brightness.day(),
+            ),
+        }
+    }
+
+    /// Builds a schedule from the day phases of this configuration, given the
+    /// targets of the night and of the day. Custom keyframes fall back to the
+    /// configured hours.
+    fn phases_schedule_on<T>(
+        &self,
+        date: NaiveDate,
+        utc_offset: FixedOffset,
+        night: T,
+        day: T,
+    ) -> Option<Schedule<T>>
+    where
+        T: Interpolate,
+    {
+        let schedule = match &self.source {
+            ScheduleSource::Hours | ScheduleSource::Keyframes(_) => {
+                Schedule::from_hours(self.hours, night, day)
+            },
+            ScheduleSource::Solar(solar) => {
+                match solar.hour_config(date, utc_offset) {
+                    SolarHours::Cycle(hours) => {
+                        Schedule::from_hours(hours, night, day)
+                    },
+                    SolarHours::PolarDay => Schedule::constant(day),
+                    SolarHours::PolarNight => Schedule::constant(night),
+                }
+            },
+            ScheduleSource::Elevation(_) => return None,
+        };
+        Some(schedule.with_curve(self.curve))
+    }
 }
 
 impl Default for Config {
@@ -172,26 +985,90 @@ impl Default for Config {
                 ChannelConfig { min: 0.65, max: 1.0 },
                 ChannelConfig { min: 0.45, max: 1.0 },
             ],
+            temperature: None,
+            brightness: BrightnessConfig::default(),
+            brightness_schedule: None,
+            curve: TransitionCurve::default(),
+            source: ScheduleSource::Hours,
+            fade: FadeConfig::default(),
+            ramp: RampConfig::default(),
+            monitors: Vec::new(),
         }
     }
 }
 
 #[cfg(test)]
 mod test {
-    use super::{ChannelConfig, HourConfig};
+    use chrono::{FixedOffset, NaiveDate};
+
+    use crate::{
+        hour::DayPhase,
+        monitor::{Edid, Monitor},
+        solar::{Location, Twilight},
+    };
+
+    use super::{
+        BrightnessConfig,
+        ChannelConfig,
+        Config,
+        ElevationConfig,
+        FadeConfig,
+        HourConfig,
+        MonitorConfig,
+        MonitorMatch,
+        RampConfig,
+        RejectedValue,
+        SolarConfig,
+        SolarHours,
+        TemperatureConfig,
+    };
+
+    fn solar_hours(latitude: f64, longitude: f64, month: u32) -> SolarHours {
+        let config = SolarConfig {
+            location: Location::new(latitude, longitude).unwrap(),
+            twilight: Twilight::Civil,
+            offset: 0.5 / 24.0,
+        };
+        config.hour_config(
+            NaiveDate::from_ymd_opt(2024, month, 21).unwrap(),
+            FixedOffset::east_opt(3600).unwrap(),
+        )
+    }
 
     #[test]
     fn error_when_day_phase_cycle_is_invalid() {
-        HourConfig::new(0.5, 0.1, 0.7).unwrap_err();
-        HourConfig::new(0.1, 0.7, 0.5).unwrap_err();
-        HourConfig::new(0.7, 0.5, 0.1).unwrap_err();
+        HourConfig::new(0.5, 0.1, 0.7, 0.9).unwrap_err();
+        HourConfig::new(0.1, 0.7, 0.5, 0.9).unwrap_err();
+        HourConfig::new(0.7, 0.5, 0.1, 0.05).unwrap_err();
+        HourConfig::new(0.2, 0.5, 0.7, 0.6).unwrap_err();
+        HourConfig::new(0.2, 0.5, 0.7, 0.3).unwrap_err();
+    }
+
+    #[test]
+    fn rejected_values_of_invalid_configs() {
+        let error = HourConfig::new(
+            23.0 / 24.0,
+            17.0 / 24.0,
+            21.0 / 24.0,
+            4.0 / 24.0,
+        )
+        .unwrap_err();
+        let rejected =
+            (0..4).filter(|&i| error.rejects(i)).collect::<Vec<_>>();
+        assert_eq!(rejected, [0, 3]);
+
+        let error = BrightnessConfig::new(0.8, 1.5).unwrap_err();
+        assert!(!error.rejects(0));
+        assert!(error.rejects(1));
     }
 
     #[test]
     fn ok_when_day_phase_cycle_is_valid() {
-        HourConfig::new(0.1, 0.5, 0.7).unwrap();
-        HourConfig::new(0.5, 0.7, 0.1).unwrap();
-        HourConfig::new(0.7, 0.1, 0.5).unwrap();
+        HourConfig::new(0.1, 0.5, 0.7, 0.9).unwrap();
+        HourConfig::new(0.5, 0.7, 0.9, 0.1).unwrap();
+        HourConfig::new(0.7, 0.9, 0.1, 0.5).unwrap();
+        HourConfig::new(0.9, 0.1, 0.5, 0.7).unwrap();
+        HourConfig::new(0.2, 0.5, 0.7, 0.2).unwrap();
     }
 
     #[test]
@@ -204,4 +1081,136 @@ mod test {
         ChannelConfig::new(0.1, 0.9).unwrap();
         ChannelConfig::new(1.0, 1.0).unwrap();
     }
+
+    #[test]
+    fn error_when_brightness_is_invalid() {
+        BrightnessConfig::new(0.5, 0.9).unwrap_err();
+        BrightnessConfig::new(1.0, 0.0).unwrap_err();
+        BrightnessConfig::new(1.5, 0.5).unwrap_err();
+        BrightnessConfig::new(1.0, 0.7).unwrap();
+    }
+
+    #[test]
+    fn error_when_temperatures_are_invalid() {
+        TemperatureConfig::new(3400.0, 6500.0).unwrap_err();
+        TemperatureConfig::new(6500.0, 500.0).unwrap_err();
+        TemperatureConfig::new(30000.0, 3400.0).unwrap_err();
+        TemperatureConfig::new(6500.0, 3400.0).unwrap();
+    }
+
+    #[test]
+    fn error_when_fade_is_invalid() {
+        FadeConfig::new(-1.0, 20.0).unwrap_err();
+        FadeConfig::new(1e20, 20.0).unwrap_err();
+        FadeConfig::new(f64::NAN, 20.0).unwrap_err();
+        FadeConfig::new(2.0, 0.0).unwrap_err();
+        FadeConfig::new(2.0, 1e-30).unwrap_err();
+        FadeConfig::new(2.0, f64::INFINITY).unwrap_err();
+        let config = FadeConfig::new(0.0, 20.0).unwrap();
+        assert_eq!(config.step().as_millis(), 50);
+    }
+
+    #[test]
+    fn error_when_base_gamma_is_invalid() {
+        RampConfig::new(0.0).unwrap_err();
+        RampConfig::new(f64::INFINITY).unwrap_err();
+        RampConfig::new(1.2).unwrap();
+    }
+
+    #[test]
+    fn error_when_elevations_are_invalid() {
+        let location = Location::new(0.0, 0.0).unwrap();
+        ElevationConfig::new(location, -6.0, 3.0).unwrap_err();
+        ElevationConfig::new(location, 3.0, 3.0).unwrap_err();
+        ElevationConfig::new(location, 3.0, -100.0).unwrap_err();
+        ElevationConfig::new(location, 3.0, -6.0).unwrap();
+    }
+
+    #[test]
+    fn solar_hours_follow_the_sun() {
+        let hours = match solar_hours(51.5074, -0.1278, 6) {
+            SolarHours::Cycle(hours) => hours,
+            hours => panic!("Expected cycle, found {:?}", hours),
+        };
+        assert!(hours.dawn_start() < hours.day_start());
+        assert!(hours.day_start() < hours.dusk_start());
+        assert!(hours.dusk_start() < hours.night_start());
+        assert!((hours.day_start() * 24.0 - 5.2).abs() < 0.1);
+        assert!((hours.dusk_start() * 24.0 - 20.85).abs() < 0.1);
+    }
+
+    #[test]
+    fn solar_hours_in_polar_regions() {
+        assert_eq!(solar_hours(69.6492, 18.9553, 6), SolarHours::PolarDay);
+        assert_eq!(solar_hours(78.2232, 15.6267, 12), SolarHours::PolarNight);
+        let hours = match solar_hours(69.6492, 18.9553, 12) {
+            SolarHours::Cycle(hours) => hours,
+            hours => panic!("Expected cycle, found {:?}", hours),
+        };
+        assert!(!matches!(
+            DayPhase::from_current_hour(hours, hours.day_start()),
+            DayPhase::Day
+        ));
+    }
+
+    #[test]
+    fn error_when_calibration_is_invalid() {
+        let config = Config::default();
+        let create = |calibration| {
+            MonitorConfig::new(
+                MonitorMatch::Name("eDP-1".to_owned()),
+                config.channels,
+                config.brightness,
+                calibration,
+            )
+        };
+        create([1.0, 0.0, 0.9]).unwrap_err();
+        create([1.0, -0.5, 0.9]).unwrap_err();
+        create([1.0, f64::NAN, 0.9]).unwrap_err();
+        create([1.0, 0.95, 0.9]).unwrap();
+    }
+
+    #[test]
+    fn monitors_override_config() {
+        let blue = ChannelConfig::new(0.6, 1.0).unwrap();
+        let channels =
+            [ChannelConfig::default(), ChannelConfig::default(), blue];
+        let brightness = BrightnessConfig::new(0.9, 0.7).unwrap();
+        let serial = MonitorConfig::new(
+            MonitorMatch::Serial("7YRZ".to_owned()),
+            channels,
+            brightness,
+            [1.0, 0.95, 0.9],
+        )
+        .unwrap();
+        let name = MonitorConfig::new(
+            MonitorMatch::Name("DP-1".to_owned()),
+            Config::default().channels,
+            BrightnessConfig::default(),
+            [0.9, 1.0, 1.0],
+        )
+        .unwrap();
+        let config =
+            Config { monitors: vec![serial, name], ..Config::default() };
+
+        let external = Monitor {
+            edid: Some(Edid {
+                vendor: "DEL".to_owned(),
+                product: 0xa0ec,
+                model: None,
+                serial: Some("7YRZ".to_owned()),
+            }),
+            ..Monitor::new("DP-1")
+        };
+        let for_external = config.for_monitor(&external);
+        assert_eq!(for_external.channels[2], blue);
+        assert_eq!(for_external.brightness, brightness);
+        assert_eq!(config.calibration(&external), [1.0, 0.95, 0.9]);
+
+        let other = Monitor::new("DP-1");
+        assert_eq!(config.calibration(&other), [0.9, 1.0, 1.0]);
+        let laptop = Monitor::new("eDP-1");
+        assert_eq!(config.for_monitor(&laptop), config);
+        assert_eq!(config.calibration(&laptop), [1.0; 3]);
+    }
 }
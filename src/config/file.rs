@@ -0,0 +1,999 @@
+//! Configuration file in the TOML format, whose values are layered below the
+//! command-line arguments.
+
+use std::{
+    collections::BTreeMap,
+    env,
+    error::Error,
+    fmt,
+    fs,
+    io,
+    ops::Range,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+use chrono::NaiveTime;
+use serde::Deserialize;
+use toml::Spanned;
+
+use crate::{
+    config::{BrightnessConfig, MonitorMatch},
+    curve::TransitionCurve,
+    hour::timelike_to_hours,
+    schedule::{ColorTarget, Keyframe},
+    solar::Twilight,
+};
+
+/// Name of the directory of this application in the configuration directory.
+pub const APP_DIR: &str = "circadianlight";
+
+/// Name of the configuration file in the application directory.
+pub const FILE_NAME: &str = "config.toml";
+
+/// Name of the profile made of the top-level values of the file, unless a
+/// profile with this name is defined.
+pub const DEFAULT_PROFILE: &str = "default";
+
+/// Error yielded when a configuration file is malformed or holds an invalid
+/// value.
+#[derive(Debug, Clone)]
+pub struct InvalidConfigFile {
+    /// Path of the configuration file.
+    pub path: PathBuf,
+    /// Line (starting from 1) of the offending value, if known.
+    pub line: Option<usize>,
+    /// Key of the offending value, if known, such as `channels.red.min`.
+    pub key: Option<String>,
+    /// Description of the error.
+    pub message: String,
+}
+
+impl fmt::Display for InvalidConfigFile {
+    fn fmt(&self, fmtr: &mut fmt::Formatter) -> fmt::Result {
+        write!(fmtr, "{}", self.path.display())?;
+        if let Some(line) = self.line {
+            write!(fmtr, ":{}", line)?;
+        }
+        if let Some(key) = &self.key {
+            write!(fmtr, ": {}", key)?;
+        }
+        write!(fmtr, ": {}", self.message)
+    }
+}
+
+impl Error for InvalidConfigFile {}
+
+/// Position of a value in a configuration file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Position {
+    /// Key of the value, such as `channels.red.min`.
+    pub key: String,
+    /// Line where the value is, starting from 1.
+    pub line: usize,
+}
+
+/// A value read from a configuration file, along with its position.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Entry<T> {
+    /// The value itself.
+    pub value: T,
+    /// Where the value was read.
+    pub position: Position,
+}
+
+/// Values read from a configuration file. Every value is optional, and
+/// missing ones are taken from command-line arguments or defaults.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct ConfigFile {
+    /// Path of the configuration file.
+    pub path: PathBuf,
+    /// Name of the selected profile, if any.
+    pub profile: Option<String>,
+    /// Names of the profiles defined in the file.
+    pub profiles: Vec<String>,
+    /// Minimum red channel value.
+    pub min_red: Option<Entry<f64>>,
+    /// Maximum red channel value.
+    pub max_red: Option<Entry<f64>>,
+    /// Minimum green channel value.
+    pub min_green: Option<Entry<f64>>,
+    /// Maximum green channel value.
+    pub max_green: Option<Entry<f64>>,
+    /// Minimum blue channel value.
+    pub min_blue: Option<Entry<f64>>,
+    /// Maximum blue channel value.
+    pub max_blue: Option<Entry<f64>>,
+    /// Starting hour of the day phase, divided by 24h.
+    pub day_start: Option<Entry<f64>>,
+    /// Starting hour of the dusk phase, divided by 24h.
+    pub dusk_start: Option<Entry<f64>>,
+    /// Starting hour of the night phase, divided by 24h.
+    pub night_start: Option<Entry<f64>>,
+    /// Starting hour of the dawn phase, divided by 24h.
+    pub dawn_start: Option<Entry<f64>>,
+    /// Keyframes of a custom schedule.
+    pub keyframes: Option<Entry<Vec<Keyframe>>>,
+    /// Curve of the transitions.
+    pub curve: Option<Entry<TransitionCurve>>,
+    /// Latitude of the location whose sun is followed.
+    pub latitude: Option<Entry<f64>>,
+    /// Longitude of the location whose sun is followed.
+    pub longitude: Option<Entry<f64>>,
+    /// Minutes between the sunrise and the day start, and between the dusk
+    /// start and the sunset.
+    pub solar_offset: Option<Entry<f64>>,
+    /// Twilight that starts the dawn and ends the dusk.
+    pub twilight: Option<Entry<Twilight>>,
+    /// Whether the sun elevation is followed instead of day phases.
+    pub elevation: Option<Entry<bool>>,
+    /// Sun elevation above which it is full day.
+    pub day_elevation: Option<Entry<f64>>,
+    /// Sun elevation below which it is full night.
+    pub night_elevation: Option<Entry<f64>>,
+    /// Color temperature of the day phase.
+    pub day_temperature: Option<Entry<f64>>,
+    /// Color temperature of the night phase.
+    pub night_temperature: Option<Entry<f64>>,
+    /// Brightness of the day phase.
+    pub day_brightness: Option<Entry<f64>>,
+    /// Brightness of the night phase.
+    pub night_brightness: Option<Entry<f64>>,
+    /// Keyframes of a custom brightness schedule.
+    pub brightness_keyframes: Option<Entry<Vec<Keyframe<f64>>>>,
+    /// Duration of fades, in seconds.
+    pub fade_duration: Option<Entry<f64>>,
+    /// Steps per second of fades.
+    pub fade_rate: Option<Entry<f64>>,
+    /// Exponent shaping the curve of every color channel.
+    pub base_gamma: Option<Entry<f64>>,
+    /// Values specific to some monitors, with the ones of the selected
+    /// profile first.
+    pub monitors: Vec<MonitorEntry>,
+}
+
+/// Values of a `[[monitors]]` table of a configuration file, overriding the
+/// other values for the monitors it selects.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MonitorEntry {
+    /// Criterion selecting the monitors, by name or by serial number.
+    pub matcher: Entry<MonitorMatch>,
+    /// Minimum red channel value.
+    pub min_red: Option<Entry<f64>>,
+    /// Maximum red channel value.
+    pub max_red: Option<Entry<f64>>,
+    /// Minimum green channel value.
+    pub min_green: Option<Entry<f64>>,
+    /// Maximum green channel value.
+    pub max_green: Option<Entry<f64>>,
+    /// Minimum blue channel value.
+    pub min_blue: Option<Entry<f64>>,
+    /// Maximum blue channel value.
+    pub max_blue: Option<Entry<f64>>,
+    /// Brightness of the day phase.
+    pub day_brightness: Option<Entry<f64>>,
+    /// Brightness of the night phase.
+    pub night_brightness: Option<Entry<f64>>,
+    /// Multiplier of each color channel.
+    pub calibration: Option<Entry<[f64; 3]>>,
+}
+
+impl ConfigFile {
+    /// Loads the configuration file at the given path, selecting the given
+    /// profile, or the one selected in the file if none is given.
+    pub fn load<P>(path: P, profile: Option<&str>) -> io::Result<Self>
+    where
+        P: AsRef<Path>,
+    {
+        let path = path.as_ref();
+        let source = fs::read_to_string(path).map_err(|error| {
+            io::Error::new(
+                error.kind(),
+                format!("{}: {}", path.display(), error),
+            )
+        })?;
+        Self::parse(path, &source, profile)
+            .map_err(|error| io::Error::new(io::ErrorKind::InvalidData, error))
+    }
+
+    /// Parses the contents of a configuration file, given its path for error
+    /// messages, selecting the given profile, or the one selected in the file
+    /// if none is given.
+    pub fn parse<P>(
+        path: P,
+        source: &str,
+        profile: Option<&str>,
+    ) -> Result<Self, InvalidConfigFile>
+    where
+        P: AsRef<Path>,
+    {
+        let parser = Parser { path: path.as_ref(), source, prefix: "" };
+        let table = toml::from_str::<FileTable>(source).map_err(|error| {
+            InvalidConfigFile {
+                path: parser.path.to_owned(),
+                line: error.span().map(|span| parser.line(span)),
+                key: None,
+                message: error.message().to_owned(),
+            }
+        })?;
+        let (base, selected, mut profiles) = table.into_parts();
+        let mut file = parser.convert(base)?;
+        file.profiles = profiles.keys().cloned().collect();
+        let name = match (profile, &selected) {
+            (Some(name), _) => Some(name),
+            (None, Some(name)) => Some(name.get_ref().as_str()),
+            (None, None) => None,
+        };
+        if let Some(name) = name {
+            match profiles.remove(name) {
+                Some(table) => {
+                    let prefix = format!("profiles.{}.", name);
+                    let parser = Parser { prefix: &prefix, ..parser };
+                    file = parser.convert(table)?.or(file);
+                },
+                None if name == DEFAULT_PROFILE => (),
+                None => {
+                    let mut error = InvalidConfigFile {
+                        path: parser.path.to_owned(),
+                        line: None,
+                        key: None,
+                        message: format!(
+                            "unknown profile {}, expected one of: {}",
+                            name,
+                            file.profile_names().collect::<Vec<_>>().join(", ")
+                        ),
+                    };
+                    if let (None, Some(selected)) = (profile, &selected) {
+                        error.line = Some(parser.line(selected.span()));
+                        error.key = Some("profile".to_owned());
+                    }
+                    return Err(error);
+                },
+            }
+            file.profile = Some(name.to_owned());
+        }
+        if let Some(elevation) = &file.elevation {
+            if elevation.value && file.latitude.is_none() {
+                return Err(file.invalid(
+                    &elevation.position,
+                    "following the sun elevation requires a location",
+                ));
+            }
+        }
+        Ok(file)
+    }
+
+    /// Names of all profiles that can be selected in this file, including the
+    /// default one.
+    pub fn profile_names(&self) -> impl Iterator<Item = &str> + '_ {
+        let defined = self.profiles.iter().any(|name| name == DEFAULT_PROFILE);
+        let default = Some(DEFAULT_PROFILE).filter(|_| !defined);
+        default.into_iter().chain(self.profiles.iter().map(String::as_str))
+    }
+
+    /// Makes an error pointing to the value at the given position of this
+    /// file.
+    pub fn invalid<E>(&self, position: &Position, error: E) -> InvalidConfigFile
+    where
+        E: fmt::Display,
+    {
+        InvalidConfigFile {
+            path: self.path.clone(),
+            line: Some(position.line),
+            key: Some(position.key.clone()),
+            message: error.to_string(),
+        }
+    }
+
+    /// Layers the values of this file on top of the given lower file. If this
+    /// file has a schedule source of its own (keyframes or a location), the
+    /// source of the lower file is discarded.
+    fn or(self, mut lower: Self) -> Self {
+        if self.keyframes.is_some() || self.latitude.is_some() {
+            lower.keyframes = None;
+            lower.latitude = None;
+            lower.longitude = None;
+            lower.elevation = None;
+        }
+        Self {
+            path: self.path,
+            profile: self.profile.or(lower.profile),
+            profiles: lower.profiles,
+            min_red: self.min_red.or(lower.min_red),
+            max_red: self.max_red.or(lower.max_red),
+            min_green: self.min_green.or(lower.min_green),
+            max_green: self.max_green.or(lower.max_green),
+            min_blue: self.min_blue.or(lower.min_blue),
+            max_blue: self.max_blue.or(lower.max_blue),
+            day_start: self.day_start.or(lower.day_start),
+            dusk_start: self.dusk_start.or(lower.dusk_start),
+            night_start: self.night_start.or(lower.night_start),
+            dawn_start: self.dawn_start.or(lower.dawn_start),
+            keyframes: self.keyframes.or(lower.keyframes),
+            curve: self.curve.or(lower.curve),
+            latitude: self.latitude.or(lower.latitude),
+            longitude: self.longitude.or(lower.longitude),
+            solar_offset: self.solar_offset.or(lower.solar_offset),
+            twilight: self.twilight.or(lower.twilight),
+            elevation: self.elevation.or(lower.elevation),
+            day_elevation: self.day_elevation.or(lower.day_elevation),
+            night_elevation: self.night_elevation.or(lower.night_elevation),
+            day_temperature: self.day_temperature.or(lower.day_temperature),
+            night_temperature: self
+                .night_temperature
+                .or(lower.night_temperature),
+            day_brightness: self.day_brightness.or(lower.day_brightness),
+            night_brightness: self.night_brightness.or(lower.night_brightness),
+            brightness_keyframes: self
+                .brightness_keyframes
+                .or(lower.brightness_keyframes),
+            fade_duration: self.fade_duration.or(lower.fade_duration),
+            fade_rate: self.fade_rate.or(lower.fade_rate),
+            base_gamma: self.base_gamma.or(lower.base_gamma),
+            monitors: self.monitors.into_iter().chain(lower.monitors).collect(),
+        }
+    }
+}
+
+/// Default path of the configuration file, that is,
+/// `$XDG_CONFIG_HOME/circadianlight/config.toml`, falling back to
+/// `$HOME/.config/circadianlight/config.toml`.
+pub fn default_path() -> Option<PathBuf> {
+    let config_home = env::var_os("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .filter(|path| path.is_absolute())
+        .or_else(|| {
+            env::var_os("HOME").map(|home| PathBuf::from(home).join(".config"))
+        })?;
+    Some(config_home.join(APP_DIR).join(FILE_NAME))
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct FileTable {
+    profile: Option<Spanned<String>>,
+    #[serde(default)]
+    profiles: BTreeMap<String, ProfileTable>,
+    curve: Option<Spanned<String>>,
+    base_gamma: Option<Spanned<f64>>,
+    #[serde(default)]
+    hours: HoursTable,
+    #[serde(default)]
+    channels: ChannelsTable,
+    temperature: Option<TemperatureTable>,
+    #[serde(default)]
+    brightness: BrightnessTable,
+    keyframes: Option<Spanned<Vec<KeyframeTable>>>,
+    #[serde(default)]
+    sun: SunTable,
+    #[serde(default)]
+    fade: FadeTable,
+    #[serde(default)]
+    monitors: Vec<Spanned<MonitorTable>>,
+}
+
+impl FileTable {
+    fn into_parts(
+        self,
+    ) -> (ProfileTable, Option<Spanned<String>>, BTreeMap<String, ProfileTable>)
+    {
+        let base = ProfileTable {
+            curve: self.curve,
+            base_gamma: self.base_gamma,
+            hours: self.hours,
+            channels: self.channels,
+            temperature: self.temperature,
+            brightness: self.brightness,
+            keyframes: self.keyframes,
+            sun: self.sun,
+            fade: self.fade,
+            monitors: self.monitors,
+        };
+        (base, self.profile, self.profiles)
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ProfileTable {
+    curve: Option<Spanned<String>>,
+    base_gamma: Option<Spanned<f64>>,
+    #[serde(default)]
+    hours: HoursTable,
+    #[serde(default)]
+    channels: ChannelsTable,
+    temperature: Option<TemperatureTable>,
+    #[serde(default)]
+    brightness: BrightnessTable,
+    keyframes: Option<Spanned<Vec<KeyframeTable>>>,
+    #[serde(default)]
+    sun: SunTable,
+    #[serde(default)]
+    fade: FadeTable,
+    #[serde(default)]
+    monitors: Vec<Spanned<MonitorTable>>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct HoursTable {
+    day_start: Option<Spanned<String>>,
+    dusk_start: Option<Spanned<String>>,
+    night_start: Option<Spanned<String>>,
+    dawn_start: Option<Spanned<String>>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ChannelsTable {
+    #[serde(default)]
+    red: BoundsTable,
+    #[serde(default)]
+    green: BoundsTable,
+    #[serde(default)]
+    blue: BoundsTable,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct BoundsTable {
+    min: Option<Spanned<f64>>,
+    max: Option<Spanned<f64>>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct TemperatureTable {
+    day: Spanned<f64>,
+    night: Spanned<f64>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct BrightnessTable {
+    day: Option<Spanned<f64>>,
+    night: Option<Spanned<f64>>,
+    keyframes: Option<Spanned<Vec<BrightnessKeyframeTable>>>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct KeyframeTable {
+    time: Spanned<String>,
+    gamma: Option<Spanned<[f64; 3]>>,
+    temperature: Option<Spanned<f64>>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct BrightnessKeyframeTable {
+    time: Spanned<String>,
+    level: Spanned<f64>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct SunTable {
+    latitude: Option<Spanned<f64>>,
+    longitude: Option<Spanned<f64>>,
+    offset: Option<Spanned<f64>>,
+    twilight: Option<Spanned<String>>,
+    elevation: Option<Spanned<bool>>,
+    day_elevation: Option<Spanned<f64>>,
+    night_elevation: Option<Spanned<f64>>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct FadeTable {
+    duration: Option<Spanned<f64>>,
+    rate: Option<Spanned<f64>>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct MonitorTable {
+    name: Option<Spanned<String>>,
+    serial: Option<Spanned<String>>,
+    #[serde(default)]
+    channels: ChannelsTable,
+    #[serde(default)]
+    brightness: MonitorBrightnessTable,
+    calibration: Option<Spanned<[f64; 3]>>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct MonitorBrightnessTable {
+    day: Option<Spanned<f64>>,
+    night: Option<Spanned<f64>>,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Parser<'src> {
+    path: &'src Path,
+    source: &'src str,
+    prefix: &'src str,
+}
+
+impl<'src> Parser<'src> {
+    fn line(self, span: Range<usize>) -> usize {
+        let start = span.start.min(self.source.len());
+        self.source[..start].matches('\n').count() + 1
+    }
+
+    fn position(self, key: &str, span: Range<usize>) -> Position {
+        Position {
+            key: format!("{}{}", self.prefix, key),
+            line: self.line(span),
+        }
+    }
+
+    fn invalid<E>(
+        self,
+        key: &str,
+        span: Range<usize>,
+        error: E,
+    ) -> InvalidConfigFile
+    where
+        E: fmt::Display,
+    {
+        InvalidConfigFile {
+            path: self.path.to_owned(),
+            line: Some(self.line(span)),
+            key: Some(format!("{}{}", self.prefix, key)),
+            message: error.to_string(),
+        }
+    }
+
+    fn entry<T>(
+        self,
+        key: &str,
+        value: Option<Spanned<T>>,
+    ) -> Option<Entry<T>> {
+        value.map(|value| Entry {
+            position: self.position(key, value.span()),
+            value: value.into_inner(),
+        })
+    }
+
+    fn parsed<T>(
+        self,
+        key: &str,
+        value: Option<Spanned<String>>,
+    ) -> Result<Option<Entry<T>>, InvalidConfigFile>
+    where
+        T: FromStr,
+        T::Err: fmt::Display,
+    {
+        let Some(value) = value else { return Ok(None) };
+        let parsed = value
+            .get_ref()
+            .parse()
+            .map_err(|error| self.invalid(key, value.span(), error))?;
+        Ok(Some(Entry {
+            value: parsed,
+            position: self.position(key, value.span()),
+        }))
+    }
+
+    fn hour(
+        self,
+        key: &str,
+        value: &Spanned<String>,
+    ) -> Result<f64, InvalidConfigFile> {
+        let time = NaiveTime::parse_from_str(value.get_ref(), "%H:%M")
+            .map_err(|error| self.invalid(key, value.span(), error))?;
+        Ok(timelike_to_hours(&time))
+    }
+
+    fn hour_entry(
+        self,
+        key: &str,
+        value: Option<Spanned<String>>,
+    ) -> Result<Option<Entry<f64>>, InvalidConfigFile> {
+        let Some(value) = value else { return Ok(None) };
+        Ok(Some(Entry {
+            value: self.hour(key, &value)?,
+            position: self.position(key, value.span()),
+        }))
+    }
+
+    fn keyframes(
+        self,
+        value: Option<Spanned<Vec<KeyframeTable>>>,
+    ) -> Result<Option<Entry<Vec<Keyframe>>>, InvalidConfigFile> {
+        let Some(value) = value else { return Ok(None) };
+        let position = self.position("keyframes", value.span());
+        let mut keyframes = Vec::new();
+        for (i, keyframe) in value.into_inner().into_iter().enumerate() {
+            let key = format!("keyframes[{}]", i);
+            let time = self.hour(&format!("{}.time", key), &keyframe.time)?;
+            let target = match (keyframe.gamma, keyframe.temperature) {
+                (Some(gamma), None) => {
+                    ColorTarget::channels(*gamma.get_ref()).map_err(
+                        |error| {
+                            self.invalid(
+                                &format!("{}.gamma", key),
+                                gamma.span(),
+                                error,
+                            )
+                        },
+                    )?
+                },
+                (None, Some(temperature)) => {
+                    ColorTarget::temperature(*temperature.get_ref())
+                        .map_err(|error| {
+                            self.invalid(
+                                &format!("{}.temperature", key),
+                                temperature.span(),
+                                error,
+                            )
+                        })?
+                },
+                _ => {
+                    return Err(self.invalid(
+                        &key,
+                        keyframe.time.span(),
+                        "expected exactly one of gamma or temperature",
+                    ))
+                },
+            };
+            keyframes.push(Keyframe { time, target });
+        }
+        Ok(Some(Entry { value: keyframes, position }))
+    }
+
+    fn brightness_keyframes(
+        self,
+        value: Option<Spanned<Vec<BrightnessKeyframeTable>>>,
+    ) -> Result<Option<Entry<Vec<Keyframe<f64>>>>, InvalidConfigFile> {
+        let Some(value) = value else { return Ok(None) };
+        let position = self.position("brightness.keyframes", value.span());
+        let mut keyframes = Vec::new();
+        for (i, keyframe) in value.into_inner().into_iter().enumerate() {
+            let key = format!("brightness.keyframes[{}]", i);
+            let time = self.hour(&format!("{}.time", key), &keyframe.time)?;
+            let level = BrightnessConfig::level(*keyframe.level.get_ref())
+                .map_err(|error| {
+                    self.invalid(
+                        &format!("{}.level", key),
+                        keyframe.level.span(),
+                        error,
+                    )
+                })?;
+            keyframes.push(Keyframe { time, target: level });
+        }
+        Ok(Some(Entry { value: keyframes, position }))
+    }
+
+    fn monitors(
+        self,
+        tables: Vec<Spanned<MonitorTable>>,
+    ) -> Result<Vec<MonitorEntry>, InvalidConfigFile> {
+        let mut monitors = Vec::new();
+        for (i, table) in tables.into_iter().enumerate() {
+            let span = table.span();
+            let table = table.into_inner();
+            let key = |suffix: &str| format!("monitors[{}]{}", i, suffix);
+            let matcher = match (table.name, table.serial) {
+                (Some(name), None) => Entry {
+                    position: self.position(&key(".name"), name.span()),
+                    value: MonitorMatch::Name(name.into_inner()),
+                },
+                (None, Some(serial)) => Entry {
+                    position: self.position(&key(".serial"), serial.span()),
+                    value: MonitorMatch::Serial(serial.into_inner()),
+                },
+                _ => {
+                    return Err(self.invalid(
+                        &key(""),
+                        span,
+                        "expected either a name or a serial",
+                    ))
+                },
+            };
+            let channels = table.channels;
+            let brightness = table.brightness;
+            monitors.push(MonitorEntry {
+                matcher,
+                min_red: self
+                    .entry(&key(".channels.red.min"), channels.red.min),
+                max_red: self
+                    .entry(&key(".channels.red.max"), channels.red.max),
+                min_green: self
+                    .entry(&key(".channels.green.min"), channels.green.min),
+                max_green: self
+                    .entry(&key(".channels.green.max"), channels.green.max),
+                min_blue: self
+                    .entry(&key(".channels.blue.min"), channels.blue.min),
+                max_blue: self
+                    .entry(&key(".channels.blue.max"), channels.blue.max),
+                day_brightness: self
+                    .entry(&key(".brightness.day"), brightness.day),
+                night_brightness: self
+                    .entry(&key(".brightness.night"), brightness.night),
+                calibration: self
+                    .entry(&key(".calibration"), table.calibration),
+            });
+        }
+        Ok(monitors)
+    }
+
+    fn convert(
+        self,
+        table: ProfileTable,
+    ) -> Result<ConfigFile, InvalidConfigFile> {
+        let sun = table.sun;
+        match (&sun.latitude, &sun.longitude) {
+            (Some(_), None) | (None, Some(_)) => {
+                let given = sun.latitude.as_ref().or(sun.longitude.as_ref());
+                let span = given.map_or(0..0, Spanned::span);
+                return Err(self.invalid(
+                    "sun",
+                    span,
+                    "latitude and longitude must be given together",
+                ));
+            },
+            _ => (),
+        }
+        if let (Some(_), Some(keyframes)) = (&sun.latitude, &table.keyframes) {
+            return Err(self.invalid(
+                "keyframes",
+                keyframes.span(),
+                "keyframes cannot be given along with a sun location",
+            ));
+        }
+        let (day_temperature, night_temperature) = match table.temperature {
+            Some(temperature) => {
+                (Some(temperature.day), Some(temperature.night))
+            },
+            None => (None, None),
+        };
+        let channels = table.channels;
+        Ok(ConfigFile {
+            path: self.path.to_owned(),
+            profile: None,
+            profiles: Vec::new(),
+            min_red: self.entry("channels.red.min", channels.red.min),
+            max_red: self.entry("channels.red.max", channels.red.max),
+            min_green: self.entry("channels.green.min", channels.green.min),
+            max_green: self.entry("channels.green.max", channels.green.max),
+            min_blue: self.entry("channels.blue.min", channels.blue.min),
+            max_blue: self.entry("channels.blue.max", channels.blue.max),
+            day_start: self
+                .hour_entry("hours.day_start", table.hours.day_start)?,
+            dusk_start: self
+                .hour_entry("hours.dusk_start", table.hours.dusk_start)?,
+            night_start: self
+                .hour_entry("hours.night_start", table.hours.night_start)?,
+            dawn_start: self
+                .hour_entry("hours.dawn_start", table.hours.dawn_start)?,
+            keyframes: self.keyframes(table.keyframes)?,
+            curve: self.parsed("curve", table.curve)?,
+            latitude: self.entry("sun.latitude", sun.latitude),
+            longitude: self.entry("sun.longitude", sun.longitude),
+            solar_offset: self.entry("sun.offset", sun.offset),
+            twilight: self.parsed("sun.twilight", sun.twilight)?,
+            elevation: self.entry("sun.elevation", sun.elevation),
+            day_elevation: self.entry("sun.day_elevation", sun.day_elevation),
+            night_elevation: self
+                .entry("sun.night_elevation", sun.night_elevation),
+            day_temperature: self.entry("temperature.day", day_temperature),
+            night_temperature: self
+                .entry("temperature.night", night_temperature),
+            day_brightness: self.entry("brightness.day", table.brightness.day),
+            night_brightness: self
+                .entry("brightness.night", table.brightness.night),
+            brightness_keyframes: self
+                .brightness_keyframes(table.brightness.keyframes)?,
+            fade_duration: self.entry("fade.duration", table.fade.duration),
+            fade_rate: self.entry("fade.rate", table.fade.rate),
+            base_gamma: self.entry("base_gamma", table.base_gamma),
+            monitors: self.monitors(table.monitors)?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        config::MonitorMatch,
+        curve::TransitionCurve,
+        schedule::ColorTarget,
+    };
+
+    use super::ConfigFile;
+
+    const EPSILON: f64 = 0.0001;
+
+    #[test]
+    fn parse_full_file() {
+        let source = r#"
+curve = "smoothstep"
+base_gamma = 1.1
+
+[hours]
+day_start = "06:00"
+dusk_start = "18:00"
+night_start = "22:00"
+dawn_start = "05:30"
+
+[channels.green]
+min = 0.7
+
+[temperature]
+day = 6500
+night = 3400
+
+[brightness]
+night = 0.8
+keyframes = [{ time = "23:00", level = 0.6 }]
+
+[fade]
+duration = 1.5
+
+[[keyframes]]
+time = "13:00"
+gamma = [1.0, 0.9, 0.9]
+
+[[keyframes]]
+time = "22:00"
+temperature = 3400
+"#;
+        let file = ConfigFile::parse("config.toml", source, None).unwrap();
+        assert_eq!(file.curve.unwrap().value, TransitionCurve::Smoothstep);
+        assert_eq!(file.base_gamma.unwrap().value, 1.1);
+        let day_start = file.day_start.unwrap();
+        assert!((day_start.value - 0.25).abs() < EPSILON);
+        assert_eq!(day_start.position.line, 6);
+        assert_eq!(day_start.position.key, "hours.day_start");
+        let min_green = file.min_green.unwrap();
+        assert_eq!(min_green.value, 0.7);
+        assert_eq!(min_green.position.line, 12);
+        assert!(file.min_red.is_none());
+        assert_eq!(file.night_temperature.unwrap().value, 3400.0);
+        assert_eq!(file.night_brightness.unwrap().value, 0.8);
+        assert!(file.day_brightness.is_none());
+        assert_eq!(file.brightness_keyframes.unwrap().value.len(), 1);
+        assert_eq!(file.fade_duration.unwrap().value, 1.5);
+        assert!(file.fade_rate.is_none());
+        let keyframes = file.keyframes.unwrap().value;
+        assert_eq!(keyframes[0].target, ColorTarget::Gamma([1.0, 0.9, 0.9]));
+        assert_eq!(keyframes[1].target, ColorTarget::Temperature(3400.0));
+    }
+
+    #[test]
+    fn errors_point_to_line_and_key() {
+        let source = "[hours]\nday_start = \"05:00\"\ndusk_start = \"25:00\"\n";
+        let error = ConfigFile::parse("config.toml", source, None).unwrap_err();
+        assert_eq!(error.line, Some(3));
+        assert_eq!(error.key.as_deref(), Some("hours.dusk_start"));
+
+        let source = "\n[channels.red]\nminimum = 0.5\n";
+        let error = ConfigFile::parse("config.toml", source, None).unwrap_err();
+        assert_eq!(error.line, Some(3));
+
+        let source = "[sun]\nlatitude = 51.5\n";
+        let error = ConfigFile::parse("config.toml", source, None).unwrap_err();
+        assert_eq!(error.line, Some(2));
+
+        let source = "[[keyframes]]\ntime = \"10:00\"\n";
+        let error = ConfigFile::parse("config.toml", source, None).unwrap_err();
+        assert_eq!(error.key.as_deref(), Some("keyframes[0]"));
+
+        let source = "[[keyframes]]\ntime = \"10:00\"\ntemperature = 50\n";
+        let error = ConfigFile::parse("config.toml", source, None).unwrap_err();
+        assert_eq!(error.line, Some(3));
+        assert_eq!(error.key.as_deref(), Some("keyframes[0].temperature"));
+
+        let source = "[brightness]\nkeyframes = [\n  { time = \"22:00\", \
+                      level = 0 },\n]\n";
+        let error = ConfigFile::parse("config.toml", source, None).unwrap_err();
+        assert_eq!(error.line, Some(3));
+        assert_eq!(
+            error.key.as_deref(),
+            Some("brightness.keyframes[0].level"),
+        );
+
+        let source =
+            "[[keyframes]]\ntime = \"10:00\"\ngamma = [1.0, -7.0, 0.5]\n";
+        let error = ConfigFile::parse("config.toml", source, None).unwrap_err();
+        assert_eq!(error.line, Some(3));
+        assert_eq!(error.key.as_deref(), Some("keyframes[0].gamma"));
+
+        let source = "\ncurve = \"sigmoid:1e-20\"\n";
+        let error = ConfigFile::parse("config.toml", source, None).unwrap_err();
+        assert_eq!(error.line, Some(2));
+        assert_eq!(error.key.as_deref(), Some("curve"));
+    }
+
+    #[test]
+    fn select_profiles() {
+        let source = r#"
+profile = "reading"
+
+[channels.blue]
+min = 0.3
+
+[sun]
+latitude = 51.5
+longitude = -0.1
+
+[profiles.reading]
+channels = { blue = { min = 0.6 } }
+
+[profiles.movie]
+brightness = { night = 0.7 }
+[[profiles.movie.keyframes]]
+time = "12:00"
+temperature = 6500
+"#;
+        let file = ConfigFile::parse("config.toml", source, None).unwrap();
+        assert_eq!(file.profile.as_deref(), Some("reading"));
+        let min_blue = file.min_blue.unwrap();
+        assert_eq!(min_blue.value, 0.6);
+        assert_eq!(min_blue.position.key, "profiles.reading.channels.blue.min");
+        assert_eq!(file.latitude.unwrap().value, 51.5);
+
+        let file =
+            ConfigFile::parse("config.toml", source, Some("movie")).unwrap();
+        assert_eq!(file.min_blue.unwrap().value, 0.3);
+        assert_eq!(file.night_brightness.unwrap().value, 0.7);
+        assert!(file.keyframes.is_some());
+        assert!(file.latitude.is_none());
+
+        let file =
+            ConfigFile::parse("config.toml", source, Some("default")).unwrap();
+        assert_eq!(file.min_blue.as_ref().unwrap().value, 0.3);
+        let names = file.profile_names().collect::<Vec<_>>();
+        assert_eq!(names, ["default", "movie", "reading"]);
+
+        ConfigFile::parse("config.toml", source, Some("gaming")).unwrap_err();
+        let source = "profile = \"gaming\"\n";
+        let error = ConfigFile::parse("config.toml", source, None).unwrap_err();
+        assert_eq!(error.line, Some(1));
+    }
+
+    #[test]
+    fn parse_monitors() {
+        let source = r#"
+[[monitors]]
+serial = "7YRZ"
+calibration = [1.0, 0.95, 0.9]
+brightness = { night = 0.6 }
+
+[[profiles.movie.monitors]]
+name = "HDMI-1"
+channels = { blue = { min = 0.3 } }
+"#;
+        let file = ConfigFile::parse("config.toml", source, None).unwrap();
+        assert_eq!(file.monitors.len(), 1);
+        let monitor = &file.monitors[0];
+        assert_eq!(monitor.matcher.value, MonitorMatch::Serial("7YRZ".into()));
+        assert_eq!(monitor.matcher.position.key, "monitors[0].serial");
+        let calibration = monitor.calibration.as_ref().unwrap();
+        assert_eq!(calibration.value, [1.0, 0.95, 0.9]);
+        assert_eq!(calibration.position.line, 4);
+        assert_eq!(monitor.night_brightness.as_ref().unwrap().value, 0.6);
+        assert!(monitor.min_blue.is_none());
+
+        let file =
+            ConfigFile::parse("config.toml", source, Some("movie")).unwrap();
+        assert_eq!(file.monitors.len(), 2);
+        let monitor = &file.monitors[0];
+        assert_eq!(monitor.matcher.value, MonitorMatch::Name("HDMI-1".into()));
+        let min_blue = monitor.min_blue.as_ref().unwrap();
+        assert_eq!(min_blue.value, 0.3);
+        assert_eq!(
+            min_blue.position.key,
+            "profiles.movie.monitors[0].channels.blue.min"
+        );
+
+        let source = "[[monitors]]
+calibration = [1.0, 1.0, 0.9]
+";
+        let error = ConfigFile::parse("config.toml", source, None).unwrap_err();
+        assert_eq!(error.key.as_deref(), Some("monitors[0]"));
+    }
+}
@@ -0,0 +1,387 @@
+//! Control of a running server through a local socket, using a line-based
+//! protocol: every request is a line with a command, such as `pause` or
+//! `profile movie`, and every reply is a line starting with either `ok` or
+//! `error`, followed by a message.
+
+use std::{
+    env,
+    error::Error,
+    fmt,
+    fs,
+    path::{Path, PathBuf},
+    str::FromStr,
+    sync::mpsc::Sender,
+};
+
+use chrono::NaiveTime;
+
+use crate::overrides::{Expiry, OverrideTarget};
+
+pub use self::socket::{listen, send};
+
+/// Name of the socket in the runtime directory.
+pub const SOCKET_NAME: &str = "circadianlight.sock";
+
+/// Error yielded when an invalid command is parsed.
+#[derive(Debug, Clone)]
+pub struct InvalidCommand {
+    /// The given (invalid) command.
+    pub command: String,
+}
+
+impl fmt::Display for InvalidCommand {
+    fn fmt(&self, fmtr: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            fmtr,
+            "Invalid command, expected one of pause [EXPIRY], resume, status, \
+             reload, profile NAME or override TARGET [EXPIRY], where EXPIRY \
+             is either for MINUTES or until H:M, given: {}",
+            self.command
+        )
+    }
+}
+
+impl Error for InvalidCommand {}
+
+/// A command sent to a running server.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Command {
+    /// Stops following the schedule, restoring neutral colors, until the
+    /// given expiry.
+    Pause(Expiry),
+    /// Follows the schedule again, ending any pause or override.
+    Resume,
+    /// Asks for the current state of the server.
+    Status,
+    /// Switches to the profile with the given name.
+    Profile(String),
+    /// Overrides the scheduled colors until the given expiry.
+    Override {
+        /// Target of the screen colors during the override.
+        target: OverrideTarget,
+        /// When the override expires.
+        expiry: Expiry,
+    },
+    /// Reloads the configuration.
+    Reload,
+}
+
+impl fmt::Display for Command {
+    fn fmt(&self, fmtr: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Pause(expiry) => {
+                write!(fmtr, "pause")?;
+                fmt_expiry(*expiry, fmtr)
+            },
+            Self::Resume => fmtr.pad("resume"),
+            Self::Status => fmtr.pad("status"),
+            Self::Profile(name) => write!(fmtr, "profile {}", name),
+            Self::Override { target, expiry } => {
+                write!(fmtr, "override {}", target)?;
+                fmt_expiry(*expiry, fmtr)
+            },
+            Self::Reload => fmtr.pad("reload"),
+        }
+    }
+}
+
+fn fmt_expiry(expiry: Expiry, fmtr: &mut fmt::Formatter) -> fmt::Result {
+    match expiry {
+        Expiry::Never => Ok(()),
+        Expiry::After(minutes) => write!(fmtr, " for {}", minutes),
+        Expiry::Until(time) => write!(fmtr, " until {}", time.format("%H:%M")),
+    }
+}
+
+fn parse_expiry(words: &[&str]) -> Option<Expiry> {
+    match words {
+        [] => Some(Expiry::Never),
+        ["for", minutes] => {
+            let minutes = minutes.parse::<f64>().ok()?;
+            (minutes > 0.0 && minutes.is_finite())
+                .then_some(Expiry::After(minutes))
+        },
+        ["until", time] => {
+            NaiveTime::parse_from_str(time, "%H:%M").ok().map(Expiry::Until)
+        },
+        _ => None,
+    }
+}
+
+impl FromStr for Command {
+    type Err = InvalidCommand;
+
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        let error = || InvalidCommand { command: input.to_owned() };
+        let words = input.split_whitespace().collect::<Vec<_>>();
+        match words[..] {
+            ["pause", ref expiry @ ..] => {
+                parse_expiry(expiry).map(Self::Pause).ok_or_else(error)
+            },
+            ["resume"] => Ok(Self::Resume),
+            ["status"] => Ok(Self::Status),
+            ["profile", name] => Ok(Self::Profile(name.to_owned())),
+            ["override", target, ref expiry @ ..] => {
+                let target = target.parse().map_err(|_| error())?;
+                let expiry = parse_expiry(expiry).ok_or_else(error)?;
+                Ok(Self::Override { target, expiry })
+            },
+            ["reload"] => Ok(Self::Reload),
+            _ => Err(error()),
+        }
+    }
+}
+
+/// Reply to a command: a message if it succeeded, otherwise an error
+/// message.
+pub type Reply = Result<String, String>;
+
+/// A command received by the server, waiting for a reply.
+#[derive(Debug)]
+pub struct Request {
+    /// The received command.
+    pub command: Command,
+    reply: Sender<Reply>,
+}
+
+impl Request {
+    /// Replies to this request. Ignored if the client has gone.
+    pub fn reply(self, reply: Reply) {
+        let _ = self.reply.send(reply);
+    }
+}
+
+/// A socket listening to commands. The socket file is removed when this is
+/// dropped.
+#[derive(Debug)]
+pub struct Listener {
+    path: PathBuf,
+}
+
+impl Listener {
+    /// Path of the socket.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl Drop for Listener {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+/// Default path of the socket, that is,
+/// `$XDG_RUNTIME_DIR/circadianlight.sock`.
+pub fn default_socket_path() -> Option<PathBuf> {
+    env::var_os("XDG_RUNTIME_DIR")
+        .map(PathBuf::from)
+        .filter(|path| path.is_absolute())
+        .map(|path| path.join(SOCKET_NAME))
+}
+
+#[cfg(unix)]
+mod socket {
+    use std::{
+        fs,
+        io::{self, BufRead, BufReader, Write},
+        net::Shutdown,
+        os::unix::net::{UnixListener, UnixStream},
+        path::Path,
+        sync::mpsc::{self, Sender},
+        thread,
+    };
+
+    use super::{Command, Listener, Request};
+
+    /// Listens to commands at the given socket path in background threads,
+    /// sending received requests through the given sender. Fails if another
+    /// server is already listening at the path.
+    pub fn listen<T>(path: &Path, sender: Sender<T>) -> io::Result<Listener>
+    where
+        T: From<Request> + Send + 'static,
+    {
+        if path.exists() {
+            if UnixStream::connect(path).is_ok() {
+                return Err(io::Error::new(
+                    io::ErrorKind::AddrInUse,
+                    format!(
+                        "a server is already listening at {}",
+                        path.display()
+                    ),
+                ));
+            }
+            fs::remove_file(path)?;
+        }
+        let socket = UnixListener::bind(path)?;
+        thread::spawn(move || {
+            for stream in socket.incoming() {
+                let Ok(stream) = stream else { continue };
+                let sender = sender.clone();
+                thread::spawn(move || {
+                    if let Err(error) = serve_client(stream, sender) {
+                        eprintln!("control connection failed: {}", error);
+                    }
+                });
+            }
+        });
+        Ok(Listener { path: path.to_owned() })
+    }
+
+    /// Sends a command to the server listening at the given socket path, and
+    /// waits for its reply. An error reply is converted into an I/O error.
+    pub fn send(path: &Path, command: &Command) -> io::Result<String> {
+        let mut stream = UnixStream::connect(path).map_err(|error| {
+            io::Error::new(
+                error.kind(),
+                format!("could not connect to {}: {}", path.display(), error),
+            )
+        })?;
+        writeln!(stream, "{}", command)?;
+        stream.shutdown(Shutdown::Write)?;
+        let mut line = String::new();
+        BufReader::new(stream).read_line(&mut line)?;
+        let line = line.trim_end();
+        let (status, message) = line.split_once(' ').unwrap_or((line, ""));
+        match status {
+            "ok" => Ok(message.to_owned()),
+            "error" => Err(io::Error::other(message)),
+            _ => Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("unexpected reply from the server: {}", line),
+            )),
+        }
+    }
+
+    fn serve_client<T>(stream: UnixStream, sender: Sender<T>) -> io::Result<()>
+    where
+        T: From<Request>,
+    {
+        let mut writer = stream.try_clone()?;
+        for line in BufReader::new(stream).lines() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            let reply = match line.parse::<Command>() {
+                Ok(command) => {
+                    let (reply_sender, reply_receiver) = mpsc::channel();
+                    let request = Request { command, reply: reply_sender };
+                    if sender.send(T::from(request)).is_err() {
+                        break;
+                    }
+                    reply_receiver
+                        .recv()
+                        .unwrap_or_else(|_| Err("server stopped".to_owned()))
+                },
+                Err(error) => Err(error.to_string()),
+            };
+            match reply {
+                Ok(message) if message.is_empty() => writeln!(writer, "ok")?,
+                Ok(message) => writeln!(writer, "ok {}", message)?,
+                Err(message) => writeln!(writer, "error {}", message)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(not(unix))]
+mod socket {
+    use std::{io, path::Path, sync::mpsc::Sender};
+
+    use super::{Command, Listener, Request};
+
+    fn unsupported() -> io::Error {
+        io::Error::new(
+            io::ErrorKind::Unsupported,
+            "control sockets are not supported in your platform",
+        )
+    }
+
+    pub fn listen<T>(_path: &Path, _sender: Sender<T>) -> io::Result<Listener>
+    where
+        T: From<Request> + Send + 'static,
+    {
+        Err(unsupported())
+    }
+
+    pub fn send(_path: &Path, _command: &Command) -> io::Result<String> {
+        Err(unsupported())
+    }
+}
+
+#[cfg(all(test, unix))]
+mod test {
+    use std::{env, process, sync::mpsc, thread};
+
+    use chrono::NaiveTime;
+
+    use crate::{
+        overrides::{Expiry, OverrideTarget},
+        schedule::ColorTarget,
+    };
+
+    use super::{listen, send, Command, Request};
+
+    #[test]
+    fn parse_commands() {
+        let commands = [
+            Command::Pause(Expiry::Never),
+            Command::Pause(Expiry::After(60.0)),
+            Command::Resume,
+            Command::Status,
+            Command::Profile("movie".to_owned()),
+            Command::Override {
+                target: OverrideTarget::Night,
+                expiry: Expiry::Until(
+                    NaiveTime::from_hms_opt(7, 0, 0).unwrap(),
+                ),
+            },
+            Command::Override {
+                target: OverrideTarget::Color(ColorTarget::Gamma([
+                    1.0, 0.8, 0.6,
+                ])),
+                expiry: Expiry::After(1.5),
+            },
+            Command::Override {
+                target: OverrideTarget::Color(ColorTarget::Temperature(3400.0)),
+                expiry: Expiry::Never,
+            },
+            Command::Reload,
+        ];
+        for command in commands {
+            assert_eq!(
+                command.to_string().parse::<Command>().unwrap(),
+                command
+            );
+        }
+        "override 3400K for -5".parse::<Command>().unwrap_err();
+        "override 1:-3:5".parse::<Command>().unwrap_err();
+        "pause until 25:00".parse::<Command>().unwrap_err();
+        "profile".parse::<Command>().unwrap_err();
+        "stop".parse::<Command>().unwrap_err();
+    }
+
+    #[test]
+    fn send_and_reply() {
+        let path = env::temp_dir()
+            .join(format!("circadianlight-control-{}.sock", process::id()));
+        let (sender, receiver) = mpsc::channel::<Request>();
+        let listener = listen(&path, sender).unwrap();
+        thread::spawn(move || {
+            for request in receiver {
+                let reply = match &request.command {
+                    Command::Status => Ok("paused=false".to_owned()),
+                    command => Err(format!("cannot {}", command)),
+                };
+                request.reply(reply);
+            }
+        });
+        assert_eq!(send(&path, &Command::Status).unwrap(), "paused=false");
+        let error = send(&path, &Command::Pause(Expiry::Never)).unwrap_err();
+        assert_eq!(error.to_string(), "cannot pause");
+        drop(listener);
+        assert!(!path.exists());
+    }
+}
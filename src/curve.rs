@@ -0,0 +1,200 @@
+//! Transition curves used to interpolate colors between day phases.
+
+use std::{error::Error, f64::consts::PI, fmt, str::FromStr};
+
+/// Steepness used by the sigmoid curve when none is given.
+pub const DEFAULT_SIGMOID_STEEPNESS: f64 = 10.0;
+
+/// Minimum steepness accepted for the sigmoid curve, below which the curve is
+/// linear anyway and its computation loses precision.
+pub const MIN_SIGMOID_STEEPNESS: f64 = 0.1;
+
+/// Error yielded when an invalid transition curve is parsed.
+#[derive(Debug, Clone)]
+pub struct InvalidTransitionCurve {
+    /// The given (invalid) curve.
+    pub curve: String,
+}
+
+impl fmt::Display for InvalidTransitionCurve {
+    fn fmt(&self, fmtr: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            fmtr,
+            "Invalid transition curve, expected one of linear, smoothstep, \
+             cosine, sigmoid, sigmoid:STEEPNESS (with STEEPNESS >= {}) or \
+             exponential, given: {}",
+            MIN_SIGMOID_STEEPNESS, self.curve
+        )
+    }
+}
+
+impl Error for InvalidTransitionCurve {}
+
+/// A curve mapping the progress of a transition (e.g. the dusk) in the interval
+/// `[0,1]` to the progress of the color change, also in the interval `[0,1]`.
+#[derive(Debug, Clone, Copy, PartialEq, PartialOrd, Default)]
+pub enum TransitionCurve {
+    /// Constant speed along the whole transition.
+    #[default]
+    Linear,
+    /// Hermite polynomial easing, slow at both ends.
+    Smoothstep,
+    /// Half-period cosine easing, slow at both ends.
+    Cosine,
+    /// Logistic easing with the given steepness, the greater the steepness,
+    /// the more the change concentrates in the middle of the transition.
+    Sigmoid {
+        /// Steepness of the logistic function, must be positive. Progress is
+        /// linear when it is too small to be computed.
+        steepness: f64,
+    },
+    /// Exponential easing, very slow at both ends.
+    Exponential,
+}
+
+impl TransitionCurve {
+    /// Applies this curve to the given progress of a transition in the
+    /// interval `[0,1]`.
+    pub fn apply(self, progress: f64) -> f64 {
+        let progress = progress.clamp(0.0, 1.0);
+        match self {
+            Self::Linear => progress,
+            Self::Smoothstep => progress * progress * (3.0 - 2.0 * progress),
+            Self::Cosine => (1.0 - (PI * progress).cos()) / 2.0,
+            Self::Sigmoid { steepness } => {
+                let logistic = |x: f64| {
+                    1.0 / (1.0 + (-steepness * (x - 0.5)).exp())
+                };
+                let range = logistic(1.0) - logistic(0.0);
+                if range > 0.0 {
+                    (logistic(progress) - logistic(0.0)) / range
+                } else {
+                    progress
+                }
+            },
+            Self::Exponential => {
+                let ease_in = |x: f64| (2f64.powf(10.0 * x) - 1.0) / 1023.0;
+                if progress <= 0.5 {
+                    ease_in(2.0 * progress) / 2.0
+                } else {
+                    1.0 - ease_in(2.0 - 2.0 * progress) / 2.0
+                }
+            },
+        }
+    }
+}
+
+impl fmt::Display for TransitionCurve {
+    fn fmt(&self, fmtr: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Linear => fmtr.pad("linear"),
+            Self::Smoothstep => fmtr.pad("smoothstep"),
+            Self::Cosine => fmtr.pad("cosine"),
+            Self::Sigmoid { steepness } => {
+                write!(fmtr, "sigmoid:{}", steepness)
+            },
+            Self::Exponential => fmtr.pad("exponential"),
+        }
+    }
+}
+
+impl FromStr for TransitionCurve {
+    type Err = InvalidTransitionCurve;
+
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        let error = || InvalidTransitionCurve { curve: input.to_owned() };
+        let (name, parameter) = match input.split_once(':') {
+            Some((name, parameter)) => (name, Some(parameter)),
+            None => (input, None),
+        };
+        match (name.trim().to_lowercase().as_str(), parameter) {
+            ("linear", None) => Ok(Self::Linear),
+            ("smoothstep", None) => Ok(Self::Smoothstep),
+            ("cosine", None) => Ok(Self::Cosine),
+            ("sigmoid", None) => {
+                Ok(Self::Sigmoid { steepness: DEFAULT_SIGMOID_STEEPNESS })
+            },
+            ("sigmoid", Some(steepness)) => {
+                let steepness =
+                    steepness.trim().parse::<f64>().map_err(|_| error())?;
+                let valid =
+                    steepness >= MIN_SIGMOID_STEEPNESS && steepness.is_finite();
+                if valid {
+                    Ok(Self::Sigmoid { steepness })
+                } else {
+                    Err(error())
+                }
+            },
+            ("exponential", None) => Ok(Self::Exponential),
+            _ => Err(error()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::TransitionCurve;
+
+    const EPSILON: f64 = 0.001;
+
+    const CURVES: [TransitionCurve; 5] = [
+        TransitionCurve::Linear,
+        TransitionCurve::Smoothstep,
+        TransitionCurve::Cosine,
+        TransitionCurve::Sigmoid { steepness: 10.0 },
+        TransitionCurve::Exponential,
+    ];
+
+    #[test]
+    fn curves_keep_endpoints() {
+        for curve in CURVES {
+            assert!(curve.apply(0.0).abs() < EPSILON, "{}", curve);
+            assert!((curve.apply(0.5) - 0.5).abs() < EPSILON, "{}", curve);
+            assert!((curve.apply(1.0) - 1.0).abs() < EPSILON, "{}", curve);
+        }
+    }
+
+    #[test]
+    fn curves_are_monotonic() {
+        for curve in CURVES {
+            let mut previous = curve.apply(0.0);
+            for step in 1..=100 {
+                let current = curve.apply(f64::from(step) / 100.0);
+                assert!(current >= previous, "{}", curve);
+                previous = current;
+            }
+        }
+    }
+
+    #[test]
+    fn easing_curves_are_slow_at_the_ends() {
+        for curve in &CURVES[1..] {
+            assert!(curve.apply(0.05) < 0.05, "{}", curve);
+            assert!(curve.apply(0.95) > 0.95, "{}", curve);
+        }
+    }
+
+    #[test]
+    fn flat_sigmoid_is_linear() {
+        let curve = TransitionCurve::Sigmoid { steepness: 1e-20 };
+        for progress in [0.0, 0.25, 0.5, 1.0] {
+            assert_eq!(curve.apply(progress), progress);
+        }
+    }
+
+    #[test]
+    fn parse_curves() {
+        for curve in CURVES {
+            let parsed = curve.to_string().parse::<TransitionCurve>();
+            assert_eq!(parsed.unwrap(), curve);
+        }
+        assert_eq!(
+            "sigmoid:4.5".parse::<TransitionCurve>().unwrap(),
+            TransitionCurve::Sigmoid { steepness: 4.5 },
+        );
+        "sigmoid:-1".parse::<TransitionCurve>().unwrap_err();
+        "sigmoid:1e-20".parse::<TransitionCurve>().unwrap_err();
+        "linear:2".parse::<TransitionCurve>().unwrap_err();
+        "bezier".parse::<TransitionCurve>().unwrap_err();
+    }
+}
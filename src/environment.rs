@@ -1,56 +1,813 @@
 //! Environments that depend on specific platforms.
 
 use core::fmt;
-use std::{error::Error, io};
+use std::{env, error::Error, io, str::FromStr, time::Duration};
 
+use crate::{channel::ColorAdjustment, monitor::Monitor};
+
+#[cfg(target_os = "linux")]
+mod dbus;
+#[cfg(target_os = "linux")]
+mod kms;
 mod linux;
+pub mod middleware;
+#[cfg(target_os = "linux")]
+mod ramp;
+#[cfg(target_os = "linux")]
+mod wayland;
+#[cfg(target_os = "linux")]
+mod x11;
 
-#[derive(Debug, Clone)]
-struct NoSupportedGraphicalEnv;
+/// Environment variable selecting the backend, as an alternative to the
+/// `--backend` flag.
+pub const BACKEND_VAR: &str = "CIRCADIANLIGHT_BACKEND";
+
+/// Time waited after a change of monitors for further changes, so that a
+/// monitor being plugged, which usually yields several events, is reported
+/// once.
+#[cfg(target_os = "linux")]
+const COALESCE_DELAY: Duration = Duration::from_millis(100);
+
+#[derive(Debug, Clone, Default)]
+struct NoSupportedGraphicalEnv {
+    rejections: Vec<Rejection>,
+}
 
 impl fmt::Display for NoSupportedGraphicalEnv {
     fn fmt(&self, fmtr: &mut fmt::Formatter) -> fmt::Result {
-        fmtr.pad("your platform and/or environment is not supported")
+        fmtr.pad("your platform and/or environment is not supported")?;
+        for rejection in &self.rejections {
+            write!(fmtr, "\n  {}", rejection)?;
+        }
+        Ok(())
     }
 }
 
 impl Error for NoSupportedGraphicalEnv {}
 
+/// Error yielded by a graphical environment when listing monitors, reading or
+/// applying colors fails.
+#[derive(Debug)]
+pub enum BackendError {
+    /// A command needed by the backend is not installed.
+    CommandNotFound {
+        /// Name of the missing command.
+        command: String,
+    },
+    /// The display server, or the service controlling the screens, could not
+    /// be reached.
+    DisplayUnreachable {
+        /// Why it could not be reached.
+        reason: String,
+    },
+    /// No connected monitor has the given name.
+    UnknownOutput {
+        /// The given (unknown) monitor.
+        monitor: String,
+    },
+    /// Adjusting some of the monitors failed, while the others, if any, were
+    /// adjusted.
+    PartialFailure {
+        /// Each monitor that failed, along with its error.
+        failures: Vec<(String, BackendError)>,
+    },
+    /// Any other I/O failure.
+    Io(io::Error),
+}
+
+impl BackendError {
+    /// Whether the failure may go away by itself, such that retrying later
+    /// may succeed, for instance, when the display server is restarting or
+    /// when a monitor is being plugged.
+    pub fn is_transient(&self) -> bool {
+        match self {
+            Self::CommandNotFound { .. } => false,
+            Self::DisplayUnreachable { .. } | Self::UnknownOutput { .. } => {
+                true
+            },
+            Self::PartialFailure { failures } => {
+                failures.iter().all(|(_, error)| error.is_transient())
+            },
+            Self::Io(error) => matches!(
+                error.kind(),
+                io::ErrorKind::Interrupted
+                    | io::ErrorKind::WouldBlock
+                    | io::ErrorKind::TimedOut
+                    | io::ErrorKind::ResourceBusy
+                    | io::ErrorKind::BrokenPipe
+                    | io::ErrorKind::ConnectionReset
+                    | io::ErrorKind::ConnectionAborted
+                    | io::ErrorKind::NotConnected
+            ),
+        }
+    }
+
+    /// Error of a failure of the display server, or of the service
+    /// controlling the screens.
+    pub fn unreachable<E>(error: E) -> Self
+    where
+        E: fmt::Display,
+    {
+        Self::DisplayUnreachable { reason: error.to_string() }
+    }
+
+    /// Error of an unknown monitor with the given name.
+    pub fn unknown_output<M>(monitor: M) -> Self
+    where
+        M: Into<String>,
+    {
+        Self::UnknownOutput { monitor: monitor.into() }
+    }
+}
+
+impl fmt::Display for BackendError {
+    fn fmt(&self, fmtr: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::CommandNotFound { command } => {
+                write!(fmtr, "command {} not found, is it installed?", command)
+            },
+            Self::DisplayUnreachable { reason } => {
+                write!(fmtr, "could not reach the display: {}", reason)
+            },
+            Self::UnknownOutput { monitor } => {
+                write!(fmtr, "no connected monitor named {}", monitor)
+            },
+            Self::PartialFailure { failures } => {
+                write!(fmtr, "could not adjust some of the monitors")?;
+                for (i, (monitor, error)) in failures.iter().enumerate() {
+                    let separator = if i == 0 { ':' } else { ';' };
+                    write!(fmtr, "{} {}: {}", separator, monitor, error)?;
+                }
+                Ok(())
+            },
+            Self::Io(error) => write!(fmtr, "{}", error),
+        }
+    }
+}
+
+impl Error for BackendError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            Self::Io(error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for BackendError {
+    fn from(error: io::Error) -> Self {
+        Self::Io(error)
+    }
+}
+
+impl From<BackendError> for io::Error {
+    fn from(error: BackendError) -> Self {
+        let kind = match &error {
+            BackendError::CommandNotFound { .. }
+            | BackendError::UnknownOutput { .. } => io::ErrorKind::NotFound,
+            BackendError::DisplayUnreachable { .. } => {
+                io::ErrorKind::NotConnected
+            },
+            BackendError::PartialFailure { .. } => io::ErrorKind::Other,
+            BackendError::Io(error) => error.kind(),
+        };
+        match error {
+            BackendError::Io(error) => error,
+            error => io::Error::new(kind, error),
+        }
+    }
+}
+
+/// Error yielded when an invalid backend name is parsed.
+#[derive(Debug, Clone)]
+pub struct InvalidBackend {
+    /// The given (invalid) name.
+    pub name: String,
+}
+
+impl fmt::Display for InvalidBackend {
+    fn fmt(&self, fmtr: &mut fmt::Formatter) -> fmt::Result {
+        write!(fmtr, "Invalid backend, expected one of ")?;
+        for (i, backend) in Backend::ALL.iter().enumerate() {
+            if i > 0 {
+                write!(fmtr, ", ")?;
+            }
+            write!(fmtr, "{}", backend)?;
+        }
+        write!(fmtr, ", given: {}", self.name)
+    }
+}
+
+impl Error for InvalidBackend {}
+
+/// Reason why a backend is not available in the current session.
+#[derive(Debug, Clone)]
+pub struct Rejection {
+    /// The rejected backend.
+    pub backend: Backend,
+    /// Why the backend was rejected.
+    pub reason: String,
+}
+
+impl fmt::Display for Rejection {
+    fn fmt(&self, fmtr: &mut fmt::Formatter) -> fmt::Result {
+        write!(fmtr, "{}: {}", self.backend, self.reason)
+    }
+}
+
+impl Error for Rejection {}
+
+/// A way of controlling the screen colors, that is, a kind of graphical
+/// environment.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Backend {
+    /// Wayland compositors supporting the `wlr-gamma-control-unstable-v1`
+    /// protocol, such as Sway, Hyprland and river.
+    Wlr,
+    /// The night light of GNOME or KDE Plasma, over D-Bus, in Wayland
+    /// sessions.
+    NightLight,
+    /// The RandR protocol of X servers, talked directly.
+    Randr,
+    /// The `xrandr` command of X.
+    Xrandr,
+    /// The kernel mode setting devices, when no display server is running.
+    Kms,
+}
+
+impl Backend {
+    /// All of the backends, in the order they are probed.
+    pub const ALL: [Self; 5] =
+        [Self::Wlr, Self::NightLight, Self::Randr, Self::Xrandr, Self::Kms];
+
+    /// Name of this backend, as accepted by `--backend`.
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Wlr => "wlr",
+            Self::NightLight => "night-light",
+            Self::Randr => "randr",
+            Self::Xrandr => "xrandr",
+            Self::Kms => "kms",
+        }
+    }
+
+    /// Checks whether this backend is available in the current session, by
+    /// loading its graphical environment.
+    pub fn probe(self) -> Result<(), Rejection> {
+        self.load().map(drop)
+    }
+
+    /// Loads the graphical environment of this backend, failing if the
+    /// backend is not available in the current session.
+    pub fn load(self) -> Result<BoxedGraphicalEnv, Rejection> {
+        let loaded = match self {
+            #[cfg(target_os = "linux")]
+            Self::Wlr => wayland::WlrEnv::load().map(boxed),
+            #[cfg(target_os = "linux")]
+            Self::NightLight => dbus::NightLightEnv::load().map(boxed),
+            #[cfg(target_os = "linux")]
+            Self::Randr => x11::RandrEnv::load().map(boxed),
+            Self::Xrandr => linux::XorgEnv::load().map(boxed),
+            #[cfg(target_os = "linux")]
+            Self::Kms => kms::KmsEnv::load().map(boxed),
+            #[cfg(not(target_os = "linux"))]
+            _ => Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                "not supported in your platform",
+            )),
+        };
+        loaded.map_err(|error| Rejection {
+            backend: self,
+            reason: error.to_string(),
+        })
+    }
+}
+
+impl fmt::Display for Backend {
+    fn fmt(&self, fmtr: &mut fmt::Formatter) -> fmt::Result {
+        fmtr.pad(self.name())
+    }
+}
+
+impl FromStr for Backend {
+    type Err = InvalidBackend;
+
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        let name = input.trim().to_lowercase();
+        Self::ALL
+            .into_iter()
+            .find(|backend| backend.name() == name)
+            .ok_or_else(|| InvalidBackend { name: input.to_owned() })
+    }
+}
+
+fn boxed<G>(graphical_env: G) -> BoxedGraphicalEnv
+where
+    G: GraphicalEnv + Send + Sync + 'static,
+{
+    Box::new(graphical_env)
+}
+
+/// Type of the current session, as told by `$XDG_SESSION_TYPE`, such as `x11`,
+/// `wayland` or `tty`, if known.
+fn session_type() -> Option<String> {
+    env::var("XDG_SESSION_TYPE")
+        .ok()
+        .filter(|session_type| !session_type.is_empty())
+}
+
+/// Checks whether an X server can control the screens, that is, whether
+/// `$DISPLAY` is set and the session is not a Wayland session, where X clients
+/// only reach XWayland.
+fn check_x_session() -> io::Result<()> {
+    if env::var_os("DISPLAY").is_none() {
+        return Err(io::Error::new(
+            io::ErrorKind::NotFound,
+            "$DISPLAY is not set",
+        ));
+    }
+    if session_type().as_deref() == Some("wayland") {
+        return Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "the session is a Wayland session, where X only reaches XWayland",
+        ));
+    }
+    Ok(())
+}
+
+/// Checks whether the session is a Wayland session, that is, whether
+/// `$WAYLAND_DISPLAY` is set or `$XDG_SESSION_TYPE` is `wayland`.
+#[cfg(target_os = "linux")]
+fn check_wayland_session() -> io::Result<()> {
+    if env::var_os("WAYLAND_DISPLAY").is_some()
+        || session_type().as_deref() == Some("wayland")
+    {
+        Ok(())
+    } else {
+        Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "the session is not a Wayland session",
+        ))
+    }
+}
+
+/// Function called by a watch of the monitors whenever they change, returning
+/// whether the watch should go on.
+pub type MonitorsChanged = Box<dyn FnMut() -> bool + Send>;
+
+/// Colors of a monitor captured so that they can be restored later.
+#[derive(Debug, Clone, PartialEq)]
+pub enum CapturedGamma {
+    /// Colors read as an adjustment, by backends that cannot read more.
+    Adjustment(ColorAdjustment),
+    /// Raw gamma ramps, which may not follow any adjustment, such as ramps
+    /// set by another program with a different gamma for each channel.
+    Ramps {
+        /// The ramps of the color channels, in the order: red, green, blue.
+        ramps: [Vec<u16>; 3],
+        /// The adjustment estimated from the ramps.
+        adjustment: ColorAdjustment,
+    },
+}
+
+impl CapturedGamma {
+    /// The captured colors as an adjustment, estimated from the raw ramps,
+    /// if captured as such.
+    pub fn adjustment(&self) -> ColorAdjustment {
+        match self {
+            Self::Adjustment(adjustment) | Self::Ramps { adjustment, .. } => {
+                *adjustment
+            },
+        }
+    }
+}
+
 /// Specification for a graphical environment.
 pub trait GraphicalEnv {
-    /// List all currently connected monitors' name.
-    fn list_monitors(&self) -> io::Result<Vec<String>>;
+    /// List all currently connected monitors.
+    fn list_monitors(&self) -> Result<Vec<Monitor>, BackendError>;
+
+    /// List all currently connected monitors' name. By default, monitors are
+    /// fully described and then only named, so backends whose descriptions
+    /// are slow should list the names on their own.
+    fn list_monitor_names(&self) -> Result<Vec<String>, BackendError> {
+        let monitors = self.list_monitors()?;
+        Ok(monitors.into_iter().map(|monitor| monitor.name).collect())
+    }
+
+    /// Formats the gamma correction of the given adjustment into a string that
+    /// can be used to apply color correction, or that the graphical
+    /// environment can understand, such as the exponents given to `xrandr`.
+    fn format_gamma(
+        &self,
+        adjustment: ColorAdjustment,
+    ) -> Result<String, BackendError>;
+
+    /// Reads the gamma correction and brightness currently applied to the
+    /// given monitor.
+    fn read_gamma(
+        &self,
+        monitor: &str,
+    ) -> Result<ColorAdjustment, BackendError>;
+
+    /// Captures the colors of the given monitor, so that they can be
+    /// restored by [`restore_gamma`](Self::restore_gamma). By default, they
+    /// are read as an adjustment.
+    fn capture_gamma(
+        &self,
+        monitor: &str,
+    ) -> Result<CapturedGamma, BackendError> {
+        self.read_gamma(monitor).map(CapturedGamma::Adjustment)
+    }
 
-    /// Formats a gamma color channel array into a string that can be used to
-    /// apply color correction, or that the graphical environment can
-    /// understand.
-    fn format_gamma(&self, gamma: [f64; 3]) -> io::Result<String>;
+    /// Restores the colors captured from the given monitor. By default, the
+    /// captured colors are applied as an adjustment.
+    fn restore_gamma(
+        &self,
+        monitor: &str,
+        captured: &CapturedGamma,
+    ) -> Result<(), BackendError> {
+        self.apply_gamma(captured.adjustment(), [monitor])
+    }
+
+    /// Whether applied colors persist after this environment is dropped, for
+    /// instance, after the program exits. By default, they do.
+    fn persists_gamma(&self) -> bool {
+        true
+    }
 
-    /// Applies a gamma correction to screen colors.
-    fn apply_gamma<I>(&self, gamma: [f64; 3], monitors: I) -> io::Result<()>
+    /// Watches the monitors in a background thread, calling the given function
+    /// whenever one is connected, disconnected or reconfigured, until it
+    /// returns `false`. By default, monitors cannot be watched.
+    fn watch_monitors(
+        &self,
+        _on_change: MonitorsChanged,
+    ) -> Result<(), BackendError> {
+        Err(BackendError::Io(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "the backend cannot watch monitors",
+        )))
+    }
+
+    /// Applies a gamma correction to screen colors, along with the brightness
+    /// as a multiplier over the color channels.
+    fn apply_gamma<I>(
+        &self,
+        adjustment: ColorAdjustment,
+        monitors: I,
+    ) -> Result<(), BackendError>
     where
         I: IntoIterator,
         I::Item: AsRef<str>;
 }
 
+#[allow(clippy::needless_lifetimes)]
 impl<'this, G> GraphicalEnv for &'this G
 where
     G: GraphicalEnv,
 {
-    fn list_monitors(&self) -> io::Result<Vec<String>> {
+    fn list_monitors(&self) -> Result<Vec<Monitor>, BackendError> {
+        (**self).list_monitors()
+    }
+
+    fn list_monitor_names(&self) -> Result<Vec<String>, BackendError> {
+        (**self).list_monitor_names()
+    }
+
+    fn format_gamma(
+        &self,
+        adjustment: ColorAdjustment,
+    ) -> Result<String, BackendError> {
+        (**self).format_gamma(adjustment)
+    }
+
+    fn read_gamma(
+        &self,
+        monitor: &str,
+    ) -> Result<ColorAdjustment, BackendError> {
+        (**self).read_gamma(monitor)
+    }
+
+    fn capture_gamma(
+        &self,
+        monitor: &str,
+    ) -> Result<CapturedGamma, BackendError> {
+        (**self).capture_gamma(monitor)
+    }
+
+    fn restore_gamma(
+        &self,
+        monitor: &str,
+        captured: &CapturedGamma,
+    ) -> Result<(), BackendError> {
+        (**self).restore_gamma(monitor, captured)
+    }
+
+    fn persists_gamma(&self) -> bool {
+        (**self).persists_gamma()
+    }
+
+    fn watch_monitors(
+        &self,
+        on_change: MonitorsChanged,
+    ) -> Result<(), BackendError> {
+        (**self).watch_monitors(on_change)
+    }
+
+    fn apply_gamma<I>(
+        &self,
+        adjustment: ColorAdjustment,
+        monitors: I,
+    ) -> Result<(), BackendError>
+    where
+        I: IntoIterator,
+        I::Item: AsRef<str>,
+    {
+        (**self).apply_gamma(adjustment, monitors)
+    }
+}
+
+/// Object-safe version of [`GraphicalEnv`], so that graphical environments
+/// can be chosen at runtime and stored as a [`BoxedGraphicalEnv`]. It is
+/// implemented for every thread-safe [`GraphicalEnv`], and boxed environments
+/// implement [`GraphicalEnv`] in turn.
+pub trait DynGraphicalEnv: Send + Sync {
+    /// List all currently connected monitors.
+    fn list_monitors(&self) -> Result<Vec<Monitor>, BackendError>;
+
+    /// List all currently connected monitors' name.
+    fn list_monitor_names(&self) -> Result<Vec<String>, BackendError>;
+
+    /// Formats the gamma correction of the given adjustment into a string that
+    /// the graphical environment can understand.
+    fn format_gamma(
+        &self,
+        adjustment: ColorAdjustment,
+    ) -> Result<String, BackendError>;
+
+    /// Reads the gamma correction and brightness currently applied to the
+    /// given monitor.
+    fn read_gamma(
+        &self,
+        monitor: &str,
+    ) -> Result<ColorAdjustment, BackendError>;
+
+    /// Captures the colors of the given monitor, so that they can be
+    /// restored later.
+    fn capture_gamma(
+        &self,
+        monitor: &str,
+    ) -> Result<CapturedGamma, BackendError>;
+
+    /// Restores the colors captured from the given monitor.
+    fn restore_gamma(
+        &self,
+        monitor: &str,
+        captured: &CapturedGamma,
+    ) -> Result<(), BackendError>;
+
+    /// Whether applied colors persist after this environment is dropped.
+    fn persists_gamma(&self) -> bool;
+
+    /// Watches the monitors in a background thread, calling the given function
+    /// whenever they change, until it returns `false`.
+    fn watch_monitors(
+        &self,
+        on_change: MonitorsChanged,
+    ) -> Result<(), BackendError>;
+
+    /// Applies a gamma correction to screen colors of the given monitors,
+    /// along with the brightness as a multiplier over the color channels.
+    fn apply_gamma_dyn(
+        &self,
+        adjustment: ColorAdjustment,
+        monitors: &[&str],
+    ) -> Result<(), BackendError>;
+}
+
+impl<G> DynGraphicalEnv for G
+where
+    G: GraphicalEnv + Send + Sync,
+{
+    fn list_monitors(&self) -> Result<Vec<Monitor>, BackendError> {
+        GraphicalEnv::list_monitors(self)
+    }
+
+    fn list_monitor_names(&self) -> Result<Vec<String>, BackendError> {
+        GraphicalEnv::list_monitor_names(self)
+    }
+
+    fn format_gamma(
+        &self,
+        adjustment: ColorAdjustment,
+    ) -> Result<String, BackendError> {
+        GraphicalEnv::format_gamma(self, adjustment)
+    }
+
+    fn read_gamma(
+        &self,
+        monitor: &str,
+    ) -> Result<ColorAdjustment, BackendError> {
+        GraphicalEnv::read_gamma(self, monitor)
+    }
+
+    fn capture_gamma(
+        &self,
+        monitor: &str,
+    ) -> Result<CapturedGamma, BackendError> {
+        GraphicalEnv::capture_gamma(self, monitor)
+    }
+
+    fn restore_gamma(
+        &self,
+        monitor: &str,
+        captured: &CapturedGamma,
+    ) -> Result<(), BackendError> {
+        GraphicalEnv::restore_gamma(self, monitor, captured)
+    }
+
+    fn persists_gamma(&self) -> bool {
+        GraphicalEnv::persists_gamma(self)
+    }
+
+    fn watch_monitors(
+        &self,
+        on_change: MonitorsChanged,
+    ) -> Result<(), BackendError> {
+        GraphicalEnv::watch_monitors(self, on_change)
+    }
+
+    fn apply_gamma_dyn(
+        &self,
+        adjustment: ColorAdjustment,
+        monitors: &[&str],
+    ) -> Result<(), BackendError> {
+        GraphicalEnv::apply_gamma(self, adjustment, monitors)
+    }
+}
+
+/// A graphical environment chosen at runtime.
+pub type BoxedGraphicalEnv = Box<dyn DynGraphicalEnv>;
+
+impl GraphicalEnv for BoxedGraphicalEnv {
+    fn list_monitors(&self) -> Result<Vec<Monitor>, BackendError> {
         (**self).list_monitors()
     }
 
-    fn format_gamma(&self, gamma: [f64; 3]) -> io::Result<String> {
-        (**self).format_gamma(gamma)
+    fn list_monitor_names(&self) -> Result<Vec<String>, BackendError> {
+        (**self).list_monitor_names()
+    }
+
+    fn format_gamma(
+        &self,
+        adjustment: ColorAdjustment,
+    ) -> Result<String, BackendError> {
+        (**self).format_gamma(adjustment)
+    }
+
+    fn read_gamma(
+        &self,
+        monitor: &str,
+    ) -> Result<ColorAdjustment, BackendError> {
+        (**self).read_gamma(monitor)
     }
 
-    fn apply_gamma<I>(&self, gamma: [f64; 3], monitors: I) -> io::Result<()>
+    fn capture_gamma(
+        &self,
+        monitor: &str,
+    ) -> Result<CapturedGamma, BackendError> {
+        (**self).capture_gamma(monitor)
+    }
+
+    fn restore_gamma(
+        &self,
+        monitor: &str,
+        captured: &CapturedGamma,
+    ) -> Result<(), BackendError> {
+        (**self).restore_gamma(monitor, captured)
+    }
+
+    fn persists_gamma(&self) -> bool {
+        (**self).persists_gamma()
+    }
+
+    fn watch_monitors(
+        &self,
+        on_change: MonitorsChanged,
+    ) -> Result<(), BackendError> {
+        (**self).watch_monitors(on_change)
+    }
+
+    fn apply_gamma<I>(
+        &self,
+        adjustment: ColorAdjustment,
+        monitors: I,
+    ) -> Result<(), BackendError>
     where
         I: IntoIterator,
         I::Item: AsRef<str>,
     {
-        (**self).apply_gamma(gamma, monitors)
+        let monitors = monitors.into_iter().collect::<Vec<_>>();
+        let monitors =
+            monitors.iter().map(AsRef::as_ref).collect::<Vec<&str>>();
+        (**self).apply_gamma_dyn(adjustment, &monitors)
+    }
+}
+
+/// Applies a possibly different color adjustment to each of the given
+/// monitors. Monitors sharing the same adjustment are adjusted together. A
+/// failure of some monitors does not prevent the others from being adjusted.
+pub fn apply_per_monitor<G>(
+    graphical_env: G,
+    adjustments: &[(String, ColorAdjustment)],
+) -> Result<(), BackendError>
+where
+    G: GraphicalEnv,
+{
+    let mut groups = Vec::<(ColorAdjustment, Vec<&str>)>::new();
+    for (monitor, adjustment) in adjustments {
+        match groups.iter_mut().find(|(other, _)| other == adjustment) {
+            Some((_, monitors)) => monitors.push(monitor),
+            None => groups.push((*adjustment, vec![monitor])),
+        }
+    }
+    let mut failures = Vec::new();
+    for (adjustment, monitors) in groups {
+        match graphical_env.apply_gamma(adjustment, &monitors) {
+            Ok(()) => (),
+            Err(BackendError::PartialFailure { failures: more }) => {
+                failures.extend(more)
+            },
+            Err(error) if monitors.len() == 1 => {
+                failures.push((monitors[0].to_owned(), error))
+            },
+            Err(error) => return Err(error),
+        }
+    }
+    collect_failures(failures, adjustments.len())
+}
+
+/// Applies colors to each of the given monitors in turn, with the given
+/// function, such that a failure of some monitors does not prevent the others
+/// from being adjusted.
+fn apply_each<I, F>(monitors: I, mut apply: F) -> Result<(), BackendError>
+where
+    I: IntoIterator,
+    I::Item: AsRef<str>,
+    F: FnMut(&str) -> Result<(), BackendError>,
+{
+    let mut failures = Vec::new();
+    let mut count = 0;
+    for monitor in monitors {
+        let monitor = monitor.as_ref();
+        count += 1;
+        if let Err(error) = apply(monitor) {
+            failures.push((monitor.to_owned(), error));
+        }
+    }
+    collect_failures(failures, count)
+}
+
+/// Turns the failures of some of the given number of monitors into an error,
+/// if any failed.
+fn collect_failures(
+    mut failures: Vec<(String, BackendError)>,
+    count: usize,
+) -> Result<(), BackendError> {
+    match failures.len() {
+        0 => Ok(()),
+        1 if count == 1 => Err(failures.remove(0).1),
+        _ => Err(BackendError::PartialFailure { failures }),
+    }
+}
+
+/// Delays between retries of transient backend failures, doubling after each
+/// failure, from one second up to a maximum.
+#[derive(Debug, Clone)]
+pub struct Backoff {
+    current: Duration,
+    max: Duration,
+}
+
+impl Backoff {
+    /// Delay before the first retry.
+    pub const INITIAL: Duration = Duration::from_secs(1);
+
+    /// Creates a backoff whose delays never exceed the given maximum.
+    pub fn new(max: Duration) -> Self {
+        Self { current: Self::INITIAL.min(max), max }
+    }
+
+    /// Delay before the next retry, doubling the one after it.
+    pub fn next_delay(&mut self) -> Duration {
+        let delay = self.current;
+        self.current = (self.current * 2).min(self.max);
+        delay
+    }
+
+    /// Goes back to the initial delay, after a success.
+    pub fn reset(&mut self) {
+        self.current = Self::INITIAL.min(self.max);
     }
 }
 
@@ -72,19 +829,123 @@ pub trait GraphicalEnvContext: Sized {
     /// Runs the context without any graphical environment. By default, this
     /// will yield an error tellig the current environment is not supported.
     fn without_graphical_env(self) -> io::Result<Self::Output> {
-        Err(io::Error::new(io::ErrorKind::Unsupported, NoSupportedGraphicalEnv))
+        Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            NoSupportedGraphicalEnv::default(),
+        ))
     }
 }
 
+/// Loads the graphical environment of the given backend, failing if it is
+/// not available. If no backend is given, the first available one in the
+/// order of [`Backend::ALL`] is loaded, failing with the reasons why each was
+/// rejected if none is available.
+pub fn load_graphical_env(
+    backend: Option<Backend>,
+) -> io::Result<BoxedGraphicalEnv> {
+    if let Some(backend) = backend {
+        return backend.load().map_err(|rejection| {
+            io::Error::new(
+                io::ErrorKind::Unsupported,
+                format!(
+                    "backend {} is not available: {}",
+                    rejection.backend, rejection.reason
+                ),
+            )
+        });
+    }
+    let mut rejections = Vec::new();
+    for backend in Backend::ALL {
+        match backend.load() {
+            Ok(graphical_env) => return Ok(graphical_env),
+            Err(rejection) => rejections.push(rejection),
+        }
+    }
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        NoSupportedGraphicalEnv { rejections },
+    ))
+}
+
 /// Runs the given graphical context with the OS environment, if supported,
-/// otherwise runs without environment.
+/// otherwise runs without environment. Backends are probed in the order of
+/// [`Backend::ALL`], and the first available one is used.
 pub fn with_os_graphical_env<C>(context: C) -> io::Result<C::Output>
 where
     C: GraphicalEnvContext,
 {
-    if let Some(env) = linux::XorgEnv::load()? {
-        context.with_graphical_env(env)
-    } else {
-        context.without_graphical_env()
+    with_backend(None, context)
+}
+
+/// Runs the given graphical context with the environment of the given
+/// backend, failing if it is not available. If no backend is given, the first
+/// available one is used, as in [`with_os_graphical_env`].
+pub fn with_backend<C>(
+    backend: Option<Backend>,
+    context: C,
+) -> io::Result<C::Output>
+where
+    C: GraphicalEnvContext,
+{
+    match load_graphical_env(backend) {
+        Ok(graphical_env) => context.with_graphical_env(graphical_env),
+        Err(rejected) if backend.is_none() => {
+            context.without_graphical_env().map_err(|error| {
+                let unsupported = error
+                    .get_ref()
+                    .is_some_and(|inner| inner.is::<NoSupportedGraphicalEnv>());
+                // Tells why each backend was rejected.
+                if unsupported {
+                    rejected
+                } else {
+                    error
+                }
+            })
+        },
+        Err(error) => Err(error),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::{io, time::Duration};
+
+    use super::{Backend, BackendError, Backoff};
+
+    #[test]
+    fn parse_backends() {
+        for backend in Backend::ALL {
+            let parsed = backend.to_string().parse::<Backend>();
+            assert_eq!(parsed.unwrap(), backend);
+        }
+        assert_eq!("KMS".parse::<Backend>().unwrap(), Backend::Kms);
+        "x11".parse::<Backend>().unwrap_err();
+    }
+
+    #[test]
+    fn backoff_doubles_up_to_max() {
+        let mut backoff = Backoff::new(Duration::from_secs(5));
+        let delays = (0..5)
+            .map(|_| backoff.next_delay().as_secs())
+            .collect::<Vec<_>>();
+        assert_eq!(delays, [1, 2, 4, 5, 5]);
+        backoff.reset();
+        assert_eq!(backoff.next_delay(), Duration::from_secs(1));
+    }
+
+    #[test]
+    fn transient_errors() {
+        assert!(BackendError::unreachable("connection refused").is_transient());
+        assert!(BackendError::unknown_output("HDMI-1").is_transient());
+        let missing =
+            BackendError::CommandNotFound { command: "xrandr".into() };
+        assert!(!missing.is_transient());
+        let partial = BackendError::PartialFailure {
+            failures: vec![
+                ("DP-1".into(), BackendError::unknown_output("DP-1")),
+                ("DP-2".into(), io::Error::other("denied").into()),
+            ],
+        };
+        assert!(!partial.is_transient());
     }
 }
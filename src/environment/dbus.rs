@@ -0,0 +1,413 @@
+use std::{
+    error::Error,
+    io,
+    sync::{Arc, Condvar, Mutex},
+    thread,
+    time::Duration,
+};
+
+use zbus::{
+    blocking::{fdo::DBusProxy, proxy, Connection, Proxy},
+    proxy::CacheProperties,
+};
+
+use crate::{
+    channel::ColorAdjustment,
+    monitor::Monitor,
+    temperature::{gamma_to_temperature, temperature_to_gamma},
+};
+
+use super::{check_wayland_session, BackendError, GraphicalEnv};
+
+/// Interval between requests of the same temperature, since desktops go back
+/// to their own night light schedule, or end previews, after a while.
+const REFRESH_INTERVAL: Duration = Duration::from_secs(10);
+
+/// A desktop exposing the control of its night light over D-Bus.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Desktop {
+    /// GNOME, through `org.gnome.SettingsDaemon.Color`, whose `Temperature`
+    /// property is writable.
+    Gnome,
+    /// KDE Plasma, through KWin's `org.kde.KWin.NightLight`, which previews
+    /// temperatures.
+    Kwin,
+}
+
+impl Desktop {
+    const ALL: [Self; 2] = [Self::Gnome, Self::Kwin];
+
+    fn name(self) -> &'static str {
+        match self {
+            Self::Gnome => "gnome",
+            Self::Kwin => "kwin",
+        }
+    }
+
+    fn destination(self) -> &'static str {
+        match self {
+            Self::Gnome => "org.gnome.SettingsDaemon.Color",
+            Self::Kwin => "org.kde.KWin",
+        }
+    }
+
+    fn path(self) -> &'static str {
+        match self {
+            Self::Gnome => "/org/gnome/SettingsDaemon/Color",
+            Self::Kwin => "/org/kde/KWin/NightLight",
+        }
+    }
+
+    fn interface(self) -> &'static str {
+        match self {
+            Self::Gnome => "org.gnome.SettingsDaemon.Color",
+            Self::Kwin => "org.kde.KWin.NightLight",
+        }
+    }
+
+    /// Builds a proxy to the night light, without caching properties, so
+    /// that reads see the temperature actually used by the desktop.
+    fn proxy(self, connection: &Connection) -> zbus::Result<Proxy<'static>> {
+        proxy::Builder::new(connection)
+            .destination(self.destination())?
+            .path(self.path())?
+            .interface(self.interface())?
+            .cache_properties(CacheProperties::No)
+            .build()
+    }
+
+    fn temperature(self, proxy: &Proxy) -> zbus::Result<u32> {
+        match self {
+            Self::Gnome => proxy.get_property("Temperature"),
+            Self::Kwin => proxy.get_property("currentTemperature"),
+        }
+    }
+
+    fn set_temperature(
+        self,
+        proxy: &Proxy,
+        temperature: u32,
+    ) -> zbus::Result<()> {
+        match self {
+            Self::Gnome => {
+                proxy.set_property("Temperature", temperature)?;
+                Ok(())
+            },
+            Self::Kwin => proxy.call("preview", &(temperature,)),
+        }
+    }
+
+    /// Gives the control of the temperature back to the desktop.
+    fn release(self, proxy: &Proxy, original: u32) -> zbus::Result<()> {
+        match self {
+            Self::Gnome => self.set_temperature(proxy, original),
+            Self::Kwin => proxy.call("stopPreview", &()),
+        }
+    }
+}
+
+/// Graphical environment of desktops whose screen colors can only be
+/// controlled through their night light, that is, by a color temperature for
+/// all of the screens, over D-Bus. Brightness is not supported.
+///
+/// The requested temperature is refreshed in a background thread while this
+/// environment is alive, and the desktop gets the control back once it is
+/// dropped, if any temperature was applied.
+#[derive(Debug)]
+pub struct NightLightEnv {
+    desktop: Desktop,
+    proxy: Proxy<'static>,
+    original: u32,
+    shared: Arc<(Mutex<Refresh>, Condvar)>,
+}
+
+#[derive(Debug, Default)]
+struct Refresh {
+    temperature: Option<u32>,
+    stopped: bool,
+}
+
+impl NightLightEnv {
+    /// Connects to the session bus and looks for a supported desktop, failing
+    /// if none is found. Only Wayland sessions are accepted, since in X
+    /// sessions the night light services run as well, but gamma ramps can be
+    /// set directly, with brightness and per-monitor colors.
+    pub fn load() -> io::Result<Self> {
+        check_wayland_session()?;
+        let connection = Connection::session().map_err(|error| {
+            io::Error::other(format!(
+                "could not connect to the session bus: {}",
+                error
+            ))
+        })?;
+        Self::load_with(&connection, &Desktop::ALL)
+    }
+
+    /// Looks for the first of the given desktops in the bus of the given
+    /// connection.
+    fn load_with(
+        connection: &Connection,
+        desktops: &[Desktop],
+    ) -> io::Result<Self> {
+        let bus = DBusProxy::new(connection).map_err(dbus_error)?;
+        for &desktop in desktops {
+            let destination =
+                desktop.destination().try_into().map_err(dbus_error)?;
+            if !bus.name_has_owner(destination).map_err(dbus_error)? {
+                continue;
+            }
+            let proxy = desktop.proxy(connection).map_err(dbus_error)?;
+            let Ok(original) = desktop.temperature(&proxy) else { continue };
+            let env = Self {
+                desktop,
+                proxy,
+                original,
+                shared: Arc::new((Mutex::default(), Condvar::new())),
+            };
+            env.spawn_refresher();
+            return Ok(env);
+        }
+        Err(io::Error::new(
+            io::ErrorKind::NotFound,
+            "neither the night light of GNOME nor of KWin is running",
+        ))
+    }
+
+    /// Checks that the given monitor is the pseudo-monitor standing for all
+    /// of the screens of the desktop.
+    fn check_monitor(&self, monitor: &str) -> Result<(), BackendError> {
+        if monitor == self.desktop.name() {
+            Ok(())
+        } else {
+            Err(BackendError::unknown_output(monitor))
+        }
+    }
+
+    fn spawn_refresher(&self) {
+        let desktop = self.desktop;
+        let proxy = self.proxy.clone();
+        let shared = self.shared.clone();
+        thread::spawn(move || {
+            let (refresh, condvar) = &*shared;
+            let mut refresh = refresh.lock().unwrap_or_else(|e| e.into_inner());
+            loop {
+                refresh = condvar
+                    .wait_timeout(refresh, REFRESH_INTERVAL)
+                    .unwrap_or_else(|e| e.into_inner())
+                    .0;
+                if refresh.stopped {
+                    break;
+                }
+                if let Some(temperature) = refresh.temperature {
+                    let _ = desktop.set_temperature(&proxy, temperature);
+                }
+            }
+        });
+    }
+}
+
+impl GraphicalEnv for NightLightEnv {
+    fn list_monitors(&self) -> Result<Vec<Monitor>, BackendError> {
+        Ok(vec![Monitor::new(self.desktop.name())])
+    }
+
+    fn format_gamma(
+        &self,
+        adjustment: ColorAdjustment,
+    ) -> Result<String, BackendError> {
+        Ok(format!("{:.0}K", gamma_to_temperature(adjustment.gamma)))
+    }
+
+    fn read_gamma(
+        &self,
+        monitor: &str,
+    ) -> Result<ColorAdjustment, BackendError> {
+        self.check_monitor(monitor)?;
+        let temperature = self
+            .desktop
+            .temperature(&self.proxy)
+            .map_err(BackendError::unreachable)?;
+        Ok(ColorAdjustment {
+            gamma: temperature_to_gamma(f64::from(temperature)),
+            ..ColorAdjustment::default()
+        })
+    }
+
+    fn persists_gamma(&self) -> bool {
+        false
+    }
+
+    fn apply_gamma<I>(
+        &self,
+        adjustment: ColorAdjustment,
+        monitors: I,
+    ) -> Result<(), BackendError>
+    where
+        I: IntoIterator,
+        I::Item: AsRef<str>,
+    {
+        let mut any = false;
+        for monitor in monitors {
+            self.check_monitor(monitor.as_ref())?;
+            any = true;
+        }
+        if !any {
+            return Ok(());
+        }
+        let temperature = gamma_to_temperature(adjustment.gamma).round() as u32;
+        self.desktop
+            .set_temperature(&self.proxy, temperature)
+            .map_err(BackendError::unreachable)?;
+        let (refresh, _) = &*self.shared;
+        refresh.lock().unwrap_or_else(|e| e.into_inner()).temperature =
+            Some(temperature);
+        Ok(())
+    }
+}
+
+impl Drop for NightLightEnv {
+    fn drop(&mut self) {
+        let (refresh, condvar) = &*self.shared;
+        let mut refresh = refresh.lock().unwrap_or_else(|e| e.into_inner());
+        refresh.stopped = true;
+        let applied = refresh.temperature.is_some();
+        drop(refresh);
+        condvar.notify_all();
+        // Only gives the control back if it was taken, so that probing does
+        // not end previews of other programs.
+        if applied {
+            let _ = self.desktop.release(&self.proxy, self.original);
+        }
+    }
+}
+
+fn dbus_error<E>(error: E) -> io::Error
+where
+    E: Error + Send + Sync + 'static,
+{
+    io::Error::other(error)
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::{Arc, Mutex};
+
+    use zbus::{blocking::connection, interface};
+
+    use crate::{
+        channel::ColorAdjustment,
+        environment::GraphicalEnv,
+        temperature::temperature_to_gamma,
+    };
+
+    use super::{Desktop, NightLightEnv};
+
+    struct StubColor {
+        temperature: Arc<Mutex<u32>>,
+    }
+
+    #[interface(name = "org.gnome.SettingsDaemon.Color")]
+    impl StubColor {
+        #[zbus(property)]
+        fn temperature(&self) -> u32 {
+            *self.temperature.lock().unwrap()
+        }
+
+        #[zbus(property)]
+        fn set_temperature(&mut self, temperature: u32) {
+            *self.temperature.lock().unwrap() = temperature;
+        }
+    }
+
+    struct StubNightLight {
+        temperature: Arc<Mutex<u32>>,
+        preview: Arc<Mutex<Option<u32>>>,
+    }
+
+    #[interface(name = "org.kde.KWin.NightLight")]
+    impl StubNightLight {
+        #[zbus(property, name = "currentTemperature")]
+        fn current_temperature(&self) -> u32 {
+            let preview = *self.preview.lock().unwrap();
+            preview.unwrap_or(*self.temperature.lock().unwrap())
+        }
+
+        #[zbus(name = "preview")]
+        fn preview(&mut self, temperature: u32) {
+            *self.preview.lock().unwrap() = Some(temperature);
+        }
+
+        #[zbus(name = "stopPreview")]
+        fn stop_preview(&mut self) {
+            *self.preview.lock().unwrap() = None;
+        }
+    }
+
+    /// Needs a session bus, such as `dbus-run-session -- cargo test --
+    /// --ignored`.
+    #[test]
+    #[ignore]
+    fn drive_gnome_stub() {
+        let temperature = Arc::new(Mutex::new(6500));
+        let stub = StubColor { temperature: temperature.clone() };
+        let _service = connection::Builder::session()
+            .unwrap()
+            .name("org.gnome.SettingsDaemon.Color")
+            .unwrap()
+            .serve_at("/org/gnome/SettingsDaemon/Color", stub)
+            .unwrap()
+            .build()
+            .unwrap();
+        let client = connection::Builder::session().unwrap().build().unwrap();
+        let env =
+            NightLightEnv::load_with(&client, &[Desktop::Gnome]).unwrap();
+        assert_eq!(env.list_monitor_names().unwrap(), ["gnome"]);
+        let adjustment = ColorAdjustment {
+            gamma: temperature_to_gamma(3400.0),
+            ..ColorAdjustment::default()
+        };
+        env.apply_gamma(adjustment, ["gnome"]).unwrap();
+        assert!(temperature.lock().unwrap().abs_diff(3400) <= 10);
+        let read = env.read_gamma("gnome").unwrap();
+        assert!((read.gamma[2] - adjustment.gamma[2]).abs() < 0.01);
+        drop(env);
+        assert_eq!(*temperature.lock().unwrap(), 6500);
+    }
+
+    /// Needs a session bus, such as `dbus-run-session -- cargo test --
+    /// --ignored`.
+    #[test]
+    #[ignore]
+    fn drive_kwin_stub() {
+        let temperature = Arc::new(Mutex::new(6500));
+        let preview = Arc::new(Mutex::new(None));
+        let stub = StubNightLight {
+            temperature: temperature.clone(),
+            preview: preview.clone(),
+        };
+        let _service = connection::Builder::session()
+            .unwrap()
+            .name("org.kde.KWin")
+            .unwrap()
+            .serve_at("/org/kde/KWin/NightLight", stub)
+            .unwrap()
+            .build()
+            .unwrap();
+        let client = connection::Builder::session().unwrap().build().unwrap();
+        let env =
+            NightLightEnv::load_with(&client, &[Desktop::Kwin]).unwrap();
+        assert_eq!(env.list_monitor_names().unwrap(), ["kwin"]);
+        let adjustment = ColorAdjustment {
+            gamma: temperature_to_gamma(3400.0),
+            ..ColorAdjustment::default()
+        };
+        env.apply_gamma(adjustment, ["kwin"]).unwrap();
+        let previewed = preview.lock().unwrap().unwrap();
+        assert!(previewed.abs_diff(3400) <= 10);
+        let read = env.read_gamma("kwin").unwrap();
+        assert!((read.gamma[2] - adjustment.gamma[2]).abs() < 0.01);
+        drop(env);
+        assert_eq!(*preview.lock().unwrap(), None);
+        assert_eq!(*temperature.lock().unwrap(), 6500);
+    }
+}
@@ -0,0 +1,541 @@
+use std::{
+    env,
+    fs::{self, File, OpenOptions},
+    io,
+    os::fd::{AsFd, BorrowedFd},
+    path::{Path, PathBuf},
+    thread,
+};
+
+use drm::control::{connector, crtc, Device as ControlDevice, ResourceHandle};
+use rustix::net::{
+    self,
+    netlink::{self, SocketAddrNetlink},
+    AddressFamily,
+    RecvFlags,
+    SocketFlags,
+    SocketType,
+};
+
+use crate::{
+    channel::{self, ColorAdjustment},
+    monitor::{Edid, Geometry, Monitor},
+};
+
+use super::{
+    apply_each,
+    ramp::{self, estimate_adjustment},
+    session_type,
+    BackendError,
+    CapturedGamma,
+    GraphicalEnv,
+    MonitorsChanged,
+    COALESCE_DELAY,
+};
+
+/// Directory of the DRM device nodes.
+const DRI_DIR: &str = "/dev/dri";
+
+/// Property of atomic CRTCs holding their gamma lookup table.
+const GAMMA_LUT: &str = "GAMMA_LUT";
+
+/// Property of atomic CRTCs telling the size of their gamma lookup table.
+const GAMMA_LUT_SIZE: &str = "GAMMA_LUT_SIZE";
+
+/// Property of connectors holding the EDID of the monitor.
+const EDID: &str = "EDID";
+
+/// Netlink multicast group of the uevents sent by the kernel itself, which,
+/// unlike the ones relayed by udev, need no udev daemon.
+const KERNEL_UEVENTS: u32 = 1;
+
+/// Size of the buffer receiving uevents, larger than any uevent.
+const UEVENT_BUFFER_LEN: usize = 8192;
+
+/// Graphical environment setting the gamma of CRTCs directly through the
+/// kernel mode setting devices in `/dev/dri`, for machines running without a
+/// display server, such as on a plain console. Monitors are the connected
+/// connectors, such as `eDP-1` or `HDMI-A-1`.
+///
+/// Gamma ramps are set with the legacy gamma interface when the driver
+/// supports it, otherwise with the `GAMMA_LUT` property of the CRTC. Either
+/// way, this process must be able to become the DRM master, i.e. no other
+/// program, such as a display server, can be driving the screens.
+#[derive(Debug)]
+pub struct KmsEnv {
+    cards: Vec<Card>,
+}
+
+impl KmsEnv {
+    /// Opens the DRM cards, failing if a display server is running or if no
+    /// card supporting mode setting can be opened.
+    pub fn load() -> io::Result<Self> {
+        let graphical_session = matches!(
+            session_type().as_deref(),
+            Some("x11" | "wayland" | "mir")
+        );
+        if env::var_os("DISPLAY").is_some()
+            || env::var_os("WAYLAND_DISPLAY").is_some()
+            || graphical_session
+        {
+            return Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                "a display server is running",
+            ));
+        }
+        let no_cards = || {
+            io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("no DRM card supporting mode setting in {}", DRI_DIR),
+            )
+        };
+        // An unreadable directory only rejects this backend, so that probing
+        // goes on without it.
+        let entries = match fs::read_dir(DRI_DIR) {
+            Ok(entries) => entries,
+            Err(error) if error.kind() == io::ErrorKind::NotFound => {
+                return Err(no_cards())
+            },
+            Err(error) => {
+                return Err(io::Error::new(
+                    error.kind(),
+                    format!("could not read {}: {}", DRI_DIR, error),
+                ))
+            },
+        };
+        let mut paths = Vec::new();
+        let mut last_error = None;
+        for entry in entries {
+            match entry {
+                Ok(entry) => {
+                    let name = entry.file_name();
+                    if name.to_string_lossy().starts_with("card") {
+                        paths.push(entry.path());
+                    }
+                },
+                Err(error) => {
+                    last_error = Some(io::Error::new(
+                        error.kind(),
+                        format!("could not read {}: {}", DRI_DIR, error),
+                    ))
+                },
+            }
+        }
+        paths.sort();
+        let mut cards = Vec::new();
+        for path in paths {
+            match Card::open(&path) {
+                Ok(card) if card.resource_handles().is_ok() => cards.push(card),
+                Ok(_) => (),
+                Err(error) => {
+                    last_error = Some(io::Error::new(
+                        error.kind(),
+                        format!("could not open {}: {}", path.display(), error),
+                    ))
+                },
+            }
+        }
+        match last_error {
+            Some(error) if cards.is_empty() => Err(error),
+            _ if cards.is_empty() => Err(no_cards()),
+            _ => Ok(Self { cards }),
+        }
+    }
+
+    /// Connected connectors driven by some CRTC, along with their names and
+    /// cards. Names repeated across cards are suffixed with the card.
+    fn outputs(&self) -> io::Result<Vec<Output<'_>>> {
+        let mut outputs = Vec::<Output>::new();
+        for card in &self.cards {
+            let resources = card.resource_handles()?;
+            for &connector in resources.connectors() {
+                let info = card.get_connector(connector, false)?;
+                if info.state() != drm::control::connector::State::Connected {
+                    continue;
+                }
+                let Some(encoder) = info.current_encoder() else { continue };
+                let Some(crtc) = card.get_encoder(encoder)?.crtc() else {
+                    continue;
+                };
+                let mut name = info.to_string();
+                if outputs.iter().any(|output| output.name == name) {
+                    name = format!("{}@{}", name, card.name());
+                }
+                outputs.push(Output { name, card, connector, crtc });
+            }
+        }
+        Ok(outputs)
+    }
+}
+
+impl GraphicalEnv for KmsEnv {
+    fn list_monitors(&self) -> Result<Vec<Monitor>, BackendError> {
+        let mut monitors = Vec::new();
+        for Output { name, card, connector, crtc } in self.outputs()? {
+            let info = card.get_crtc(crtc)?;
+            let (x, y) = info.position();
+            let geometry = info.mode().map(|mode| {
+                let (width, height) = mode.size();
+                Geometry {
+                    x: x as i32,
+                    y: y as i32,
+                    width: u32::from(width),
+                    height: u32::from(height),
+                }
+            });
+            let ramp_size = match info.gamma_length() as usize {
+                0 => {
+                    let lut_size = card.property(crtc, GAMMA_LUT_SIZE)?;
+                    lut_size.map(|(_, size)| size as usize)
+                },
+                size => Some(size),
+            };
+            let edid = match card.property(connector, EDID)? {
+                Some((_, 0)) | None => None,
+                Some((_, blob)) => Edid::parse(&card.get_property_blob(blob)?),
+            };
+            monitors.push(Monitor {
+                geometry,
+                ramp_size,
+                edid,
+                ..Monitor::new(name)
+            });
+        }
+        Ok(monitors)
+    }
+
+    fn list_monitor_names(&self) -> Result<Vec<String>, BackendError> {
+        let outputs = self.outputs()?;
+        Ok(outputs.into_iter().map(|output| output.name).collect())
+    }
+
+    fn format_gamma(
+        &self,
+        adjustment: ColorAdjustment,
+    ) -> Result<String, BackendError> {
+        let gamma = adjustment.gamma;
+        Ok(format!(
+            "{:.3}:{:.3}:{:.3}",
+            gamma[channel::RED],
+            gamma[channel::GREEN],
+            gamma[channel::BLUE]
+        ))
+    }
+
+    fn read_gamma(
+        &self,
+        monitor: &str,
+    ) -> Result<ColorAdjustment, BackendError> {
+        let outputs = self.outputs()?;
+        let output = find_output(&outputs, monitor)?;
+        match output.card.crtc_ramps(output.crtc)? {
+            Some([red, green, blue]) => {
+                Ok(estimate_adjustment([&red, &green, &blue]))
+            },
+            None => Ok(ColorAdjustment::default()),
+        }
+    }
+
+    fn capture_gamma(
+        &self,
+        monitor: &str,
+    ) -> Result<CapturedGamma, BackendError> {
+        let outputs = self.outputs()?;
+        let output = find_output(&outputs, monitor)?;
+        match output.card.crtc_ramps(output.crtc)? {
+            Some(ramps) => {
+                let [red, green, blue] = &ramps;
+                let adjustment = estimate_adjustment([red, green, blue]);
+                Ok(CapturedGamma::Ramps { ramps, adjustment })
+            },
+            None => Ok(CapturedGamma::Adjustment(ColorAdjustment::default())),
+        }
+    }
+
+    fn restore_gamma(
+        &self,
+        monitor: &str,
+        captured: &CapturedGamma,
+    ) -> Result<(), BackendError> {
+        let CapturedGamma::Ramps { ramps, .. } = captured else {
+            return self.apply_gamma(captured.adjustment(), [monitor]);
+        };
+        let outputs = self.outputs()?;
+        let output = find_output(&outputs, monitor)?;
+        output.card.set_crtc_ramps(output.crtc, |size| {
+            ramps.each_ref().map(|ramp| ramp::resample(ramp, size))
+        })
+    }
+
+    fn watch_monitors(
+        &self,
+        mut on_change: MonitorsChanged,
+    ) -> Result<(), BackendError> {
+        let socket = net::socket_with(
+            AddressFamily::NETLINK,
+            SocketType::DGRAM,
+            SocketFlags::CLOEXEC,
+            Some(netlink::KOBJECT_UEVENT),
+        )
+        .map_err(io::Error::from)?;
+        net::bind(&socket, &SocketAddrNetlink::new(0, KERNEL_UEVENTS))
+            .map_err(io::Error::from)?;
+        thread::spawn(move || {
+            let mut buffer = [0; UEVENT_BUFFER_LEN];
+            loop {
+                match net::recv(&socket, &mut buffer, RecvFlags::empty()) {
+                    Ok((length, _)) if is_drm_hotplug(&buffer[..length]) => {
+                        thread::sleep(COALESCE_DELAY);
+                        let flags = RecvFlags::DONTWAIT;
+                        while net::recv(&socket, &mut buffer, flags).is_ok() {}
+                        if !on_change() {
+                            break;
+                        }
+                    },
+                    Ok(_) => (),
+                    Err(error) => {
+                        let error = io::Error::from(error);
+                        eprintln!("stopped watching monitors: {}", error);
+                        break;
+                    },
+                }
+            }
+        });
+        Ok(())
+    }
+
+    fn apply_gamma<I>(
+        &self,
+        adjustment: ColorAdjustment,
+        monitors: I,
+    ) -> Result<(), BackendError>
+    where
+        I: IntoIterator,
+        I::Item: AsRef<str>,
+    {
+        let outputs = self.outputs()?;
+        apply_each(monitors, |monitor| {
+            let output = find_output(&outputs, monitor)?;
+            output.card.set_crtc_gamma(output.crtc, adjustment)
+        })
+    }
+}
+
+/// A connector driven by a CRTC of some card.
+#[derive(Debug)]
+struct Output<'card> {
+    name: String,
+    card: &'card Card,
+    connector: connector::Handle,
+    crtc: crtc::Handle,
+}
+
+fn find_output<'outputs, 'card>(
+    outputs: &'outputs [Output<'card>],
+    monitor: &str,
+) -> Result<&'outputs Output<'card>, BackendError> {
+    outputs
+        .iter()
+        .find(|output| output.name == monitor)
+        .ok_or_else(|| BackendError::unknown_output(monitor))
+}
+
+/// An opened DRM card, such as `/dev/dri/card0`.
+#[derive(Debug)]
+struct Card {
+    path: PathBuf,
+    file: File,
+}
+
+impl Card {
+    fn open(path: &Path) -> io::Result<Self> {
+        let file = OpenOptions::new().read(true).write(true).open(path)?;
+        Ok(Self { path: path.to_owned(), file })
+    }
+
+    /// Name of the device node, such as `card0`.
+    fn name(&self) -> String {
+        self.path
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_default()
+    }
+
+    /// Handle and raw value of the property of the given resource, such as a
+    /// CRTC or a connector, with the given name, if the resource has it.
+    fn property<T>(
+        &self,
+        resource: T,
+        name: &str,
+    ) -> io::Result<Option<(drm::control::property::Handle, u64)>>
+    where
+        T: ResourceHandle,
+    {
+        let properties = self.get_properties(resource)?;
+        for (&property, &value) in properties.iter() {
+            if self.get_property(property)?.name().to_bytes() == name.as_bytes()
+            {
+                return Ok(Some((property, value)));
+            }
+        }
+        Ok(None)
+    }
+
+    /// Gamma ramps currently set on the given CRTC, through its legacy gamma
+    /// or its `GAMMA_LUT`, if any is set.
+    fn crtc_ramps(
+        &self,
+        crtc: crtc::Handle,
+    ) -> io::Result<Option<[Vec<u16>; 3]>> {
+        let size = self.get_crtc(crtc)?.gamma_length() as usize;
+        if size > 0 {
+            let mut ramps = [vec![0; size], vec![0; size], vec![0; size]];
+            let [red, green, blue] = &mut ramps;
+            self.get_gamma(crtc, red, green, blue)?;
+            return Ok(Some(ramps));
+        }
+        match self.property(crtc, GAMMA_LUT)? {
+            Some((_, 0)) | None => Ok(None),
+            Some((_, blob)) => {
+                Ok(Some(decode_lut(&self.get_property_blob(blob)?)))
+            },
+        }
+    }
+
+    fn set_crtc_gamma(
+        &self,
+        crtc: crtc::Handle,
+        adjustment: ColorAdjustment,
+    ) -> Result<(), BackendError> {
+        self.set_crtc_ramps(crtc, |size| ramp::ramps(size, adjustment))
+    }
+
+    /// Sets the gamma ramps of the given CRTC, built by the given function
+    /// for the size of the ramps of the CRTC.
+    fn set_crtc_ramps<F>(
+        &self,
+        crtc: crtc::Handle,
+        ramps: F,
+    ) -> Result<(), BackendError>
+    where
+        F: Fn(usize) -> [Vec<u16>; 3],
+    {
+        let size = self.get_crtc(crtc)?.gamma_length() as usize;
+        if size > 0 {
+            let [red, green, blue] = ramps(size);
+            let result = self.set_gamma(crtc, &red, &green, &blue);
+            return result.map_err(|error| self.master_error(error));
+        }
+        let lut = self.property(crtc, GAMMA_LUT)?;
+        let lut_size = self.property(crtc, GAMMA_LUT_SIZE)?;
+        let (Some((lut, _)), Some((_, size @ 1..))) = (lut, lut_size) else {
+            return Err(BackendError::Io(io::Error::new(
+                io::ErrorKind::Unsupported,
+                format!(
+                    "the CRTCs of {} do not support gamma ramps",
+                    self.path.display()
+                ),
+            )));
+        };
+        let entries = encode_lut(&ramps(size as usize));
+        let blob = self.create_property_blob(entries.as_slice())?;
+        let result = self.set_property(crtc, lut, blob.into());
+        if let drm::control::property::Value::Blob(id) = blob {
+            self.destroy_property_blob(id)?;
+        }
+        result.map_err(|error| self.master_error(error))
+    }
+
+    /// Converts a failure to set gamma, which is denied while another
+    /// program, such as a display server in another virtual terminal, is the
+    /// DRM master.
+    fn master_error(&self, error: io::Error) -> BackendError {
+        if error.kind() == io::ErrorKind::PermissionDenied {
+            BackendError::unreachable(format!(
+                "{} is driven by another program",
+                self.path.display()
+            ))
+        } else {
+            BackendError::Io(error)
+        }
+    }
+}
+
+impl AsFd for Card {
+    fn as_fd(&self) -> BorrowedFd<'_> {
+        self.file.as_fd()
+    }
+}
+
+impl drm::Device for Card {}
+
+impl ControlDevice for Card {}
+
+/// Encodes gamma ramps as the entries of a `GAMMA_LUT` blob, that is, a
+/// `struct drm_color_lut` with red, green, blue and a reserved value for each
+/// entry.
+fn encode_lut(ramps: &[Vec<u16>; 3]) -> Vec<[u16; 4]> {
+    let [red, green, blue] = ramps;
+    red.iter()
+        .zip(green)
+        .zip(blue)
+        .map(|((&red, &green), &blue)| [red, green, blue, 0])
+        .collect()
+}
+
+/// Decodes the bytes of a `GAMMA_LUT` blob into gamma ramps.
+fn decode_lut(bytes: &[u8]) -> [Vec<u16>; 3] {
+    let mut ramps = [Vec::new(), Vec::new(), Vec::new()];
+    for entry in bytes.chunks_exact(8) {
+        for (ramp, value) in ramps.iter_mut().zip(entry.chunks_exact(2)) {
+            ramp.push(u16::from_ne_bytes([value[0], value[1]]));
+        }
+    }
+    ramps
+}
+
+/// Whether the given kernel uevent, made of a header and `KEY=VALUE` fields,
+/// all terminated by nul bytes, tells that the connectors of a DRM card
+/// changed.
+fn is_drm_hotplug(uevent: &[u8]) -> bool {
+    let fields = uevent.split(|&byte| byte == 0).collect::<Vec<_>>();
+    fields.contains(&&b"SUBSYSTEM=drm"[..])
+        && fields.contains(&&b"HOTPLUG=1"[..])
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{channel::ColorAdjustment, environment::ramp};
+
+    use super::{decode_lut, encode_lut, is_drm_hotplug};
+
+    #[test]
+    fn encode_and_decode_lut() {
+        let adjustment = ColorAdjustment {
+            gamma: [1.0, 0.8, 0.6],
+            brightness: 0.9,
+            base_gamma: 1.0,
+        };
+        let ramps = ramp::ramps(256, adjustment);
+        let entries = encode_lut(&ramps);
+        assert_eq!(entries.len(), 256);
+        assert_eq!(entries[255][3], 0);
+        let bytes = entries
+            .iter()
+            .flatten()
+            .flat_map(|value| value.to_ne_bytes())
+            .collect::<Vec<_>>();
+        assert_eq!(decode_lut(&bytes), ramps);
+    }
+
+    #[test]
+    fn detect_drm_hotplug() {
+        let hotplug = b"change@/devices/pci0000:00/0000:00:02.0/drm/card0\0\
+            ACTION=change\0DEVPATH=/devices/pci0000:00/0000:00:02.0/drm/card0\0\
+            SUBSYSTEM=drm\0HOTPLUG=1\0DEVNAME=dri/card0\0SEQNUM=4242\0";
+        assert!(is_drm_hotplug(hotplug));
+        let other = b"add@/devices/virtual/net/tun0\0ACTION=add\0\
+            SUBSYSTEM=net\0SEQNUM=4243\0";
+        assert!(!is_drm_hotplug(other));
+    }
+}
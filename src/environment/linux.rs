@@ -1,11 +1,23 @@
 use std::{
+    env,
+    ffi::OsStr,
     io,
     process::{Command, Stdio},
 };
 
-use crate::channel;
+use crate::{
+    channel::{self, ColorAdjustment},
+    monitor::{Edid, Geometry, Monitor},
+};
+
+use super::{check_x_session, collect_failures, BackendError, GraphicalEnv};
 
-use super::GraphicalEnv;
+/// Name of the command used to control the screens.
+const XRANDR: &str = "xrandr";
+
+/// Greatest exponent given to `xrandr --gamma`, for channels whose multiplier
+/// cannot be approached otherwise.
+const MAX_EXPONENT: f64 = 10.0;
 
 #[derive(Debug, Clone)]
 pub struct XorgEnv {
@@ -13,50 +25,415 @@ pub struct XorgEnv {
 }
 
 impl XorgEnv {
-    pub fn load() -> io::Result<Option<Self>> {
-        if cfg!(target_os = "linux") {
-            Ok(Some(Self { _priv: () }))
-        } else {
-            Ok(None)
+    /// Checks that an X server is running and that the `xrandr` command is
+    /// found in `$PATH`.
+    pub fn load() -> io::Result<Self> {
+        if !cfg!(target_os = "linux") {
+            return Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                "not supported in your platform",
+            ));
+        }
+        check_x_session()?;
+        let found = env::var_os("PATH").is_some_and(|path| {
+            env::split_paths(&path).any(|dir| dir.join(XRANDR).is_file())
+        });
+        if !found {
+            return Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                "the xrandr command is not found",
+            ));
         }
+        Ok(Self { _priv: () })
     }
 }
 
 impl GraphicalEnv for XorgEnv {
-    fn list_monitors(&self) -> io::Result<Vec<String>> {
-        let output = Command::new("xrandr")
-            .arg("--listmonitors")
-            .stdout(Stdio::piped())
-            .stderr(Stdio::inherit())
-            .output()?;
-        Ok(String::from_utf8_lossy(&output.stdout)
-            .lines()
-            .skip(1)
-            .filter_map(|line| line.rsplit_once(' '))
-            .map(|(_, monitor)| monitor.to_owned())
-            .collect())
+    fn list_monitors(&self) -> Result<Vec<Monitor>, BackendError> {
+        let (stdout, _) = run_xrandr(["--verbose"])?;
+        Ok(parse_verbose_monitors(&stdout))
     }
 
-    fn format_gamma(&self, gamma: [f64; 3]) -> io::Result<String> {
-        Ok(format!("{:.3}:{:.3}:{:.3}", gamma[channel:: RED], gamma[channel::GREEN], gamma[channel::BLUE]))
+    fn list_monitor_names(&self) -> Result<Vec<String>, BackendError> {
+        // Without --verbose nor probing the outputs again, only the names and
+        // geometries are listed, but much faster.
+        let (stdout, _) = run_xrandr(["--current"])?;
+        let monitors = parse_verbose_monitors(&stdout);
+        Ok(monitors.into_iter().map(|monitor| monitor.name).collect())
     }
 
-    fn apply_gamma<I>(&self, gamma: [f64; 3], monitors: I) -> io::Result<()>
+    fn format_gamma(
+        &self,
+        adjustment: ColorAdjustment,
+    ) -> Result<String, BackendError> {
+        // The exponents actually given to `xrandr --gamma`.
+        let gamma = channel::map_channel_vector(adjustment.gamma, |gamma| {
+            xrandr_exponent(gamma, adjustment.base_gamma)
+        });
+        Ok(format!(
+            "{:.3}:{:.3}:{:.3}",
+            gamma[channel::RED],
+            gamma[channel::GREEN],
+            gamma[channel::BLUE]
+        ))
+    }
+
+    fn read_gamma(
+        &self,
+        monitor: &str,
+    ) -> Result<ColorAdjustment, BackendError> {
+        let (stdout, _) = run_xrandr(["--verbose"])?;
+        parse_verbose_gamma(&stdout, monitor)
+    }
+
+    fn apply_gamma<I>(
+        &self,
+        adjustment: ColorAdjustment,
+        monitors: I,
+    ) -> Result<(), BackendError>
     where
         I: IntoIterator,
         I::Item: AsRef<str>,
     {
-        let formatted_gamma = self.format_gamma(gamma)?;
-        let mut command = Command::new("xrandr");
-        command.stderr(Stdio::inherit());
-        for monitor in monitors {
-            command
-                .arg("--output")
-                .arg(monitor.as_ref())
-                .arg("--gamma")
-                .arg(&formatted_gamma);
-        }
-        command.output()?;
-        Ok(())
+        let formatted_gamma = self.format_gamma(adjustment)?;
+        let formatted_brightness = format!("{:.3}", adjustment.brightness);
+        let monitors = monitors
+            .into_iter()
+            .map(|monitor| monitor.as_ref().to_owned())
+            .collect::<Vec<_>>();
+        let mut args = Vec::new();
+        for monitor in &monitors {
+            args.extend([
+                "--output",
+                monitor,
+                "--gamma",
+                &formatted_gamma,
+                "--brightness",
+                &formatted_brightness,
+            ]);
+        }
+        let (_, stderr) = run_xrandr(args)?;
+        // xrandr only warns about unknown outputs, adjusting the others.
+        let missing = missing_outputs(&stderr);
+        if missing.is_empty() && !stderr.is_empty() {
+            eprintln!("xrandr: {}", stderr);
+        }
+        let failures = missing
+            .into_iter()
+            .map(|monitor| {
+                (monitor.to_owned(), BackendError::unknown_output(monitor))
+            })
+            .collect();
+        collect_failures(failures, monitors.len())
+    }
+}
+
+/// Runs `xrandr` with the given arguments, yielding its standard output and
+/// its standard error, failing if it cannot be run or if it exits with an
+/// error.
+fn run_xrandr<I>(args: I) -> Result<(String, String), BackendError>
+where
+    I: IntoIterator,
+    I::Item: AsRef<OsStr>,
+{
+    let output = Command::new(XRANDR)
+        .args(args)
+        .stdin(Stdio::null())
+        .output()
+        .map_err(|error| match error.kind() {
+            io::ErrorKind::NotFound => {
+                BackendError::CommandNotFound { command: XRANDR.to_owned() }
+            },
+            _ => BackendError::Io(error),
+        })?;
+    let stdout = String::from_utf8_lossy(&output.stdout).into_owned();
+    let stderr = String::from_utf8_lossy(&output.stderr).trim().to_owned();
+    if output.status.success() {
+        Ok((stdout, stderr))
+    } else if stderr.contains("Can't open display") {
+        Err(BackendError::unreachable(stderr))
+    } else {
+        Err(BackendError::Io(io::Error::other(format!(
+            "{} failed with {}: {}",
+            XRANDR, output.status, stderr
+        ))))
+    }
+}
+
+/// Outputs reported as not found in the standard error of `xrandr`, in lines
+/// such as `warning: output HDMI-2 not found; ignoring`.
+fn missing_outputs(stderr: &str) -> Vec<&str> {
+    stderr
+        .lines()
+        .filter_map(|line| {
+            line.trim()
+                .strip_prefix("warning: output ")?
+                .strip_suffix(" not found; ignoring")
+        })
+        .collect()
+}
+
+/// Finds the active monitors in the output of `xrandr --verbose`, where each
+/// output starts with an unindented line such as `eDP-1 connected primary
+/// 1920x1080+0+0 (0x48) ...`, followed by indented properties, among which
+/// the EDID as hexadecimal lines after an `EDID:` line.
+fn parse_verbose_monitors(output: &str) -> Vec<Monitor> {
+    let mut monitors = Vec::<Monitor>::new();
+    let mut active = false;
+    let mut edid = None::<String>;
+    for line in output.lines() {
+        if !line.starts_with(char::is_whitespace) {
+            finish_edid(&mut monitors, active, &mut edid);
+            let mut words = line.split_whitespace();
+            let name = words.next().unwrap_or_default();
+            active = false;
+            if words.next() != Some("connected") {
+                continue;
+            }
+            let mut word = words.next();
+            let primary = word == Some("primary");
+            if primary {
+                word = words.next();
+            }
+            if let Some(geometry) = word.and_then(parse_geometry) {
+                active = true;
+                monitors.push(Monitor {
+                    primary,
+                    geometry: Some(geometry),
+                    ..Monitor::new(name)
+                });
+            }
+            continue;
+        }
+        let line = line.trim();
+        match &mut edid {
+            Some(hex) if line.bytes().all(|byte| byte.is_ascii_hexdigit()) => {
+                hex.push_str(line)
+            },
+            _ if line == "EDID:" => edid = Some(String::new()),
+            _ => finish_edid(&mut monitors, active, &mut edid),
+        }
+    }
+    finish_edid(&mut monitors, active, &mut edid);
+    monitors
+}
+
+/// Decodes the hexadecimal EDID collected so far, if any, into the last
+/// monitor, if it is active.
+fn finish_edid(
+    monitors: &mut [Monitor],
+    active: bool,
+    edid: &mut Option<String>,
+) {
+    let Some(hex) = edid.take() else { return };
+    let Some(monitor) = monitors.last_mut().filter(|_| active) else { return };
+    let bytes = (0..hex.len() / 2)
+        .map(|i| u8::from_str_radix(&hex[i * 2..i * 2 + 2], 16))
+        .collect::<Result<Vec<_>, _>>();
+    monitor.edid = bytes.ok().and_then(|bytes| Edid::parse(&bytes));
+}
+
+/// Parses a geometry such as `1920x1080+0+0` or `1280x1024-1280+0`.
+fn parse_geometry(text: &str) -> Option<Geometry> {
+    let (width, rest) = text.split_once('x')?;
+    let (height, position) = rest.split_at(rest.find(['+', '-'])?);
+    let (x, y) = position.split_at(position[1..].find(['+', '-'])? + 1);
+    Some(Geometry {
+        x: x.parse().ok()?,
+        y: y.parse().ok()?,
+        width: width.parse().ok()?,
+        height: height.parse().ok()?,
+    })
+}
+
+/// Exponent given to `xrandr --gamma` for a color channel with the given
+/// multiplier and base gamma. Since xrandr cannot scale channels, the exponent
+/// is the one putting the middle of the curve of the channel where the
+/// multiplier would put it.
+fn xrandr_exponent(multiplier: f64, base_gamma: f64) -> f64 {
+    // 0.5 ^ (1 / exponent) = multiplier * 0.5 ^ (1 / base_gamma)
+    let inverse = 1.0 / base_gamma - multiplier.log2();
+    1.0 / inverse.max(1.0 / MAX_EXPONENT)
+}
+
+/// Multiplier of a color channel given the exponent of its curve told by
+/// xrandr, the inverse of [`xrandr_exponent`] with a straight base curve.
+fn xrandr_multiplier(exponent: f64) -> f64 {
+    (1.0 - 1.0 / exponent).exp2()
+}
+
+/// Finds the gamma and brightness of the given monitor in the output of
+/// `xrandr --verbose`, where each output starts with an unindented line with
+/// its name, followed by indented properties such as `Gamma: 1.0:0.8:0.6`
+/// and `Brightness: 0.90`. The exponents told by xrandr are converted to
+/// multipliers.
+fn parse_verbose_gamma(
+    output: &str,
+    monitor: &str,
+) -> Result<ColorAdjustment, BackendError> {
+    let invalid = |message: String| {
+        BackendError::Io(io::Error::new(io::ErrorKind::InvalidData, message))
+    };
+    let mut lines = output
+        .lines()
+        .skip_while(|line| line.split_whitespace().next() != Some(monitor));
+    if lines.next().is_none() {
+        return Err(BackendError::unknown_output(monitor));
+    }
+    let lines = lines.take_while(|line| line.starts_with(char::is_whitespace));
+    let mut gamma = None;
+    let mut brightness = None;
+    for line in lines {
+        match line.trim().split_once(':') {
+            Some(("Gamma", value)) => {
+                let mut channels = value.trim().split(':').map(str::parse);
+                let mut next = || match channels.next() {
+                    Some(Ok(channel)) => Ok(channel),
+                    _ => Err(invalid(format!("invalid gamma: {}", value))),
+                };
+                gamma = Some([next()?, next()?, next()?]);
+            },
+            Some(("Brightness", value)) => {
+                brightness = Some(value.trim().parse().map_err(|_| {
+                    invalid(format!("invalid brightness: {}", value))
+                })?);
+            },
+            _ => (),
+        }
+    }
+    match (gamma, brightness) {
+        (Some(exponents), Some(brightness)) => Ok(ColorAdjustment {
+            gamma: channel::map_channel_vector(exponents, xrandr_multiplier),
+            brightness,
+            base_gamma: 1.0,
+        }),
+        _ => Err(invalid(format!(
+            "could not read the gamma of monitor {}",
+            monitor
+        ))),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        channel::ColorAdjustment,
+        environment::{BackendError, GraphicalEnv},
+        monitor::{self, Geometry},
+    };
+
+    use super::{
+        missing_outputs,
+        parse_verbose_gamma,
+        parse_verbose_monitors,
+        xrandr_exponent,
+        xrandr_multiplier,
+        XorgEnv,
+    };
+
+    const EPSILON: f64 = 0.001;
+
+    const OUTPUT: &str = "\
+Screen 0: minimum 320 x 200, current 3840 x 1080, maximum 16384 x 16384
+eDP-1 connected primary 1920x1080+0+0 (0x48) normal (normal left inverted \
+right x axis y axis) 344mm x 194mm
+\tIdentifier: 0x42
+\tGamma:      1.0:1.0:1.0
+\tBrightness: 1.0
+  1920x1080 (0x48) 138.500MHz +HSync -VSync *current +preferred
+HDMI-1 connected 1920x1080+1920+0 (0x4a) normal (normal left inverted \
+right x axis y axis) 527mm x 296mm
+\tGamma:      1.0:0.77:0.59
+\tBrightness: 0.80
+";
+
+    #[test]
+    fn parse_gamma_of_monitors() {
+        let adjustment = parse_verbose_gamma(OUTPUT, "eDP-1").unwrap();
+        assert_eq!(adjustment.gamma, [1.0, 1.0, 1.0]);
+        assert_eq!(adjustment.brightness, 1.0);
+        let adjustment = parse_verbose_gamma(OUTPUT, "HDMI-1").unwrap();
+        assert_eq!(adjustment.gamma[0], 1.0);
+        assert!((adjustment.gamma[1] - 0.813).abs() < EPSILON);
+        assert!((adjustment.gamma[2] - 0.618).abs() < EPSILON);
+        assert_eq!(adjustment.brightness, 0.8);
+        let error = parse_verbose_gamma(OUTPUT, "DP-1").unwrap_err();
+        assert!(matches!(error, BackendError::UnknownOutput { .. }));
+    }
+
+    #[test]
+    fn approach_multipliers_with_exponents() {
+        assert_eq!(xrandr_exponent(1.0, 1.0), 1.0);
+        assert_eq!(xrandr_exponent(1.0, 1.2), 1.2);
+        let exponent = xrandr_exponent(0.45, 1.0);
+        assert!((exponent - 0.465).abs() < EPSILON);
+        assert!((0.5f64.powf(1.0 / exponent) - 0.45 * 0.5).abs() < EPSILON);
+        assert!((xrandr_multiplier(exponent) - 0.45).abs() < EPSILON);
+        assert_eq!(xrandr_exponent(4.0, 1.0), 10.0);
+    }
+
+    #[test]
+    fn format_exponents() {
+        let env = XorgEnv { _priv: () };
+        let adjustment = ColorAdjustment {
+            gamma: [1.0, 0.45, 1.0],
+            brightness: 0.9,
+            base_gamma: 1.0,
+        };
+        let formatted = env.format_gamma(adjustment).unwrap();
+        assert_eq!(formatted, "1.000:0.465:1.000");
+        let adjustment = ColorAdjustment { base_gamma: 1.2, ..adjustment };
+        let formatted = env.format_gamma(adjustment).unwrap();
+        assert_eq!(formatted, "1.200:0.504:1.200");
+    }
+
+    #[test]
+    fn parse_monitors() {
+        let monitors = parse_verbose_monitors(OUTPUT);
+        let names =
+            monitors.iter().map(|monitor| &monitor.name).collect::<Vec<_>>();
+        assert_eq!(names, ["eDP-1", "HDMI-1"]);
+        assert!(monitors[0].primary);
+        assert!(!monitors[1].primary);
+        assert_eq!(
+            monitors[1].geometry,
+            Some(Geometry { x: 1920, y: 0, width: 1920, height: 1080 })
+        );
+        assert_eq!(monitors[0].edid, None);
+        assert_eq!(monitors[0].ramp_size, None);
+    }
+
+    #[test]
+    fn parse_monitor_edid() {
+        let edid = monitor::test::edid("DEL", 0xa0ec, 0, None, Some("7YRZ"));
+        let mut output = String::from(
+            "DP-1 connected 2560x1440+0+0 (0x50) normal 597mm x 336mm\n\
+             \tEDID: \n",
+        );
+        for line in edid.chunks(16) {
+            output.push_str("\t\t");
+            for byte in line {
+                output.push_str(&format!("{:02x}", byte));
+            }
+            output.push('\n');
+        }
+        output.push_str(
+            "\tGamma:      1.0:1.0:1.0\n\
+             DP-2 disconnected (normal left inverted right x axis y axis)\n\
+             HDMI-1 connected (normal left inverted right x axis y axis)\n",
+        );
+        let monitors = parse_verbose_monitors(&output);
+        assert_eq!(monitors.len(), 1);
+        assert_eq!(monitors[0].serial(), Some("7YRZ"));
+        assert_eq!(monitors[0].edid.as_ref().unwrap().vendor, "DEL");
+    }
+
+    #[test]
+    fn find_missing_outputs() {
+        let stderr = "warning: output DP-3 not found; ignoring\n\
+                      warning: output HDMI-2 not found; ignoring\n\
+                      xrandr: Need crtc to set gamma on.";
+        assert_eq!(missing_outputs(stderr), ["DP-3", "HDMI-2"]);
+        assert!(missing_outputs("").is_empty());
     }
 }
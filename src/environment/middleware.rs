@@ -0,0 +1,477 @@
+//! Graphical environments wrapping other ones, changing or observing how they
+//! are used, such as by logging every change of colors, or by not changing
+//! colors at all. They can be stacked, and boxed into a
+//! [`BoxedGraphicalEnv`](super::BoxedGraphicalEnv).
+
+use std::sync::{Mutex, PoisonError};
+
+use crate::{
+    channel::ColorAdjustment,
+    config::FadeConfig,
+    fade,
+    monitor::Monitor,
+};
+
+use super::{BackendError, CapturedGamma, GraphicalEnv, MonitorsChanged};
+
+/// Logs every change of colors of the wrapped environment, along with its
+/// failures, to the standard error.
+#[derive(Debug, Clone)]
+pub struct Logging<G> {
+    inner: G,
+}
+
+impl<G> Logging<G> {
+    /// Wraps the given graphical environment.
+    pub fn new(inner: G) -> Self {
+        Self { inner }
+    }
+
+    /// Unwraps the graphical environment.
+    pub fn into_inner(self) -> G {
+        self.inner
+    }
+}
+
+impl<G> GraphicalEnv for Logging<G>
+where
+    G: GraphicalEnv,
+{
+    fn list_monitors(&self) -> Result<Vec<Monitor>, BackendError> {
+        let result = self.inner.list_monitors();
+        match &result {
+            Ok(monitors) => {
+                let names = monitors
+                    .iter()
+                    .map(|monitor| monitor.name.as_str())
+                    .collect::<Vec<_>>();
+                eprintln!("monitors: {}", names.join(" "))
+            },
+            Err(error) => eprintln!("could not list monitors: {}", error),
+        }
+        result
+    }
+
+    fn list_monitor_names(&self) -> Result<Vec<String>, BackendError> {
+        let result = self.inner.list_monitor_names();
+        match &result {
+            Ok(names) => eprintln!("monitors: {}", names.join(" ")),
+            Err(error) => eprintln!("could not list monitors: {}", error),
+        }
+        result
+    }
+
+    fn format_gamma(
+        &self,
+        adjustment: ColorAdjustment,
+    ) -> Result<String, BackendError> {
+        self.inner.format_gamma(adjustment)
+    }
+
+    fn read_gamma(
+        &self,
+        monitor: &str,
+    ) -> Result<ColorAdjustment, BackendError> {
+        let result = self.inner.read_gamma(monitor);
+        if let Err(error) = &result {
+            eprintln!("could not read the gamma of {}: {}", monitor, error);
+        }
+        result
+    }
+
+    fn capture_gamma(
+        &self,
+        monitor: &str,
+    ) -> Result<CapturedGamma, BackendError> {
+        let result = self.inner.capture_gamma(monitor);
+        if let Err(error) = &result {
+            eprintln!("could not capture the gamma of {}: {}", monitor, error);
+        }
+        result
+    }
+
+    fn restore_gamma(
+        &self,
+        monitor: &str,
+        captured: &CapturedGamma,
+    ) -> Result<(), BackendError> {
+        let result = self.inner.restore_gamma(monitor, captured);
+        match &result {
+            Ok(()) => eprintln!("restored the gamma of {}", monitor),
+            Err(error) => eprintln!(
+                "could not restore the gamma of {}: {}",
+                monitor, error
+            ),
+        }
+        result
+    }
+
+    fn persists_gamma(&self) -> bool {
+        self.inner.persists_gamma()
+    }
+
+    fn watch_monitors(
+        &self,
+        on_change: MonitorsChanged,
+    ) -> Result<(), BackendError> {
+        self.inner.watch_monitors(on_change)
+    }
+
+    fn apply_gamma<I>(
+        &self,
+        adjustment: ColorAdjustment,
+        monitors: I,
+    ) -> Result<(), BackendError>
+    where
+        I: IntoIterator,
+        I::Item: AsRef<str>,
+    {
+        let monitors = monitors.into_iter().collect::<Vec<_>>();
+        let names = monitors.iter().map(AsRef::as_ref).collect::<Vec<_>>();
+        let gamma = self.inner.format_gamma(adjustment)?;
+        let result = self.inner.apply_gamma(adjustment, &names);
+        match &result {
+            Ok(()) => eprintln!(
+                "applied {} with brightness {:.3} to {}",
+                gamma,
+                adjustment.brightness,
+                names.join(" ")
+            ),
+            Err(error) => eprintln!(
+                "could not apply {} to {}: {}",
+                gamma,
+                names.join(" "),
+                error
+            ),
+        }
+        result
+    }
+}
+
+/// Never changes the colors of the wrapped environment, only recording them,
+/// such that reading the gamma of a monitor yields the last one applied to
+/// it.
+#[derive(Debug)]
+pub struct DryRun<G> {
+    inner: G,
+    applied: Mutex<Vec<(String, ColorAdjustment)>>,
+}
+
+impl<G> DryRun<G> {
+    /// Wraps the given graphical environment.
+    pub fn new(inner: G) -> Self {
+        Self { inner, applied: Mutex::default() }
+    }
+
+    /// Last colors applied to each monitor.
+    pub fn applied(&self) -> Vec<(String, ColorAdjustment)> {
+        self.applied.lock().unwrap_or_else(PoisonError::into_inner).clone()
+    }
+
+    /// Unwraps the graphical environment.
+    pub fn into_inner(self) -> G {
+        self.inner
+    }
+}
+
+impl<G> GraphicalEnv for DryRun<G>
+where
+    G: GraphicalEnv,
+{
+    fn list_monitors(&self) -> Result<Vec<Monitor>, BackendError> {
+        self.inner.list_monitors()
+    }
+
+    fn list_monitor_names(&self) -> Result<Vec<String>, BackendError> {
+        self.inner.list_monitor_names()
+    }
+
+    fn format_gamma(
+        &self,
+        adjustment: ColorAdjustment,
+    ) -> Result<String, BackendError> {
+        self.inner.format_gamma(adjustment)
+    }
+
+    fn read_gamma(
+        &self,
+        monitor: &str,
+    ) -> Result<ColorAdjustment, BackendError> {
+        match find_recorded(&self.applied, monitor) {
+            Some(adjustment) => Ok(adjustment),
+            None => self.inner.read_gamma(monitor),
+        }
+    }
+
+    fn capture_gamma(
+        &self,
+        monitor: &str,
+    ) -> Result<CapturedGamma, BackendError> {
+        match find_recorded(&self.applied, monitor) {
+            Some(adjustment) => Ok(CapturedGamma::Adjustment(adjustment)),
+            None => self.inner.capture_gamma(monitor),
+        }
+    }
+
+    fn restore_gamma(
+        &self,
+        monitor: &str,
+        captured: &CapturedGamma,
+    ) -> Result<(), BackendError> {
+        record(&self.applied, captured.adjustment(), [monitor]);
+        Ok(())
+    }
+
+    fn persists_gamma(&self) -> bool {
+        // Nothing is lost on exit, since nothing is ever changed.
+        true
+    }
+
+    fn watch_monitors(
+        &self,
+        on_change: MonitorsChanged,
+    ) -> Result<(), BackendError> {
+        self.inner.watch_monitors(on_change)
+    }
+
+    fn apply_gamma<I>(
+        &self,
+        adjustment: ColorAdjustment,
+        monitors: I,
+    ) -> Result<(), BackendError>
+    where
+        I: IntoIterator,
+        I::Item: AsRef<str>,
+    {
+        record(&self.applied, adjustment, monitors);
+        Ok(())
+    }
+}
+
+/// Fades the colors of the wrapped environment to the applied ones, instead
+/// of changing them abruptly. Applying colors blocks until the fade is
+/// finished.
+#[derive(Debug)]
+pub struct Fading<G> {
+    inner: G,
+    config: FadeConfig,
+    current: Mutex<Vec<(String, ColorAdjustment)>>,
+}
+
+impl<G> Fading<G> {
+    /// Wraps the given graphical environment, fading with the given
+    /// configuration.
+    pub fn new(inner: G, config: FadeConfig) -> Self {
+        Self { inner, config, current: Mutex::default() }
+    }
+
+    /// Unwraps the graphical environment.
+    pub fn into_inner(self) -> G {
+        self.inner
+    }
+}
+
+impl<G> GraphicalEnv for Fading<G>
+where
+    G: GraphicalEnv,
+{
+    fn list_monitors(&self) -> Result<Vec<Monitor>, BackendError> {
+        self.inner.list_monitors()
+    }
+
+    fn list_monitor_names(&self) -> Result<Vec<String>, BackendError> {
+        self.inner.list_monitor_names()
+    }
+
+    fn format_gamma(
+        &self,
+        adjustment: ColorAdjustment,
+    ) -> Result<String, BackendError> {
+        self.inner.format_gamma(adjustment)
+    }
+
+    fn read_gamma(
+        &self,
+        monitor: &str,
+    ) -> Result<ColorAdjustment, BackendError> {
+        match find_recorded(&self.current, monitor) {
+            Some(adjustment) => Ok(adjustment),
+            None => self.inner.read_gamma(monitor),
+        }
+    }
+
+    fn capture_gamma(
+        &self,
+        monitor: &str,
+    ) -> Result<CapturedGamma, BackendError> {
+        match find_recorded(&self.current, monitor) {
+            Some(adjustment) => Ok(CapturedGamma::Adjustment(adjustment)),
+            None => self.inner.capture_gamma(monitor),
+        }
+    }
+
+    fn restore_gamma(
+        &self,
+        monitor: &str,
+        captured: &CapturedGamma,
+    ) -> Result<(), BackendError> {
+        // Raw ramps cannot be faded to, so colors are restored at once.
+        self.inner.restore_gamma(monitor, captured)?;
+        record(&self.current, captured.adjustment(), [monitor]);
+        Ok(())
+    }
+
+    fn persists_gamma(&self) -> bool {
+        self.inner.persists_gamma()
+    }
+
+    fn watch_monitors(
+        &self,
+        on_change: MonitorsChanged,
+    ) -> Result<(), BackendError> {
+        self.inner.watch_monitors(on_change)
+    }
+
+    fn apply_gamma<I>(
+        &self,
+        adjustment: ColorAdjustment,
+        monitors: I,
+    ) -> Result<(), BackendError>
+    where
+        I: IntoIterator,
+        I::Item: AsRef<str>,
+    {
+        // Colors last applied through this wrapper are preferred, since some
+        // environments cannot read their gamma back.
+        let from = monitors
+            .into_iter()
+            .map(|monitor| {
+                let monitor = monitor.as_ref().to_owned();
+                let adjustment =
+                    self.read_gamma(&monitor).unwrap_or_default();
+                (monitor, adjustment)
+            })
+            .collect::<Vec<_>>();
+        let targets = from
+            .iter()
+            .map(|(monitor, _)| (monitor.clone(), adjustment))
+            .collect::<Vec<_>>();
+        fade::fade_to(&self.inner, from, &targets, self.config)?;
+        let names = targets.into_iter().map(|(monitor, _)| monitor);
+        record(&self.current, adjustment, names);
+        Ok(())
+    }
+}
+
+fn find_recorded(
+    recorded: &Mutex<Vec<(String, ColorAdjustment)>>,
+    monitor: &str,
+) -> Option<ColorAdjustment> {
+    recorded
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .iter()
+        .find(|(other, _)| other == monitor)
+        .map(|(_, adjustment)| *adjustment)
+}
+
+fn record<I>(
+    recorded: &Mutex<Vec<(String, ColorAdjustment)>>,
+    adjustment: ColorAdjustment,
+    monitors: I,
+) where
+    I: IntoIterator,
+    I::Item: AsRef<str>,
+{
+    let mut recorded = recorded.lock().unwrap_or_else(PoisonError::into_inner);
+    for monitor in monitors {
+        let monitor = monitor.as_ref();
+        match recorded.iter_mut().find(|(other, _)| other == monitor) {
+            Some((_, previous)) => *previous = adjustment,
+            None => recorded.push((monitor.to_owned(), adjustment)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::io;
+
+    use crate::{
+        channel::ColorAdjustment,
+        config::FadeConfig,
+        environment::{BackendError, BoxedGraphicalEnv, GraphicalEnv},
+        monitor::Monitor,
+    };
+
+    use super::{DryRun, Fading, Logging};
+
+    /// Environment with fixed monitors, failing to apply any colors.
+    #[derive(Debug)]
+    struct Fixed;
+
+    impl GraphicalEnv for Fixed {
+        fn list_monitors(&self) -> Result<Vec<Monitor>, BackendError> {
+            Ok(vec![Monitor::new("eDP-1"), Monitor::new("HDMI-1")])
+        }
+
+        fn format_gamma(
+            &self,
+            adjustment: ColorAdjustment,
+        ) -> Result<String, BackendError> {
+            let gamma = adjustment.gamma;
+            Ok(format!("{}:{}:{}", gamma[0], gamma[1], gamma[2]))
+        }
+
+        fn read_gamma(
+            &self,
+            _monitor: &str,
+        ) -> Result<ColorAdjustment, BackendError> {
+            Ok(ColorAdjustment::default())
+        }
+
+        fn apply_gamma<I>(
+            &self,
+            _adjustment: ColorAdjustment,
+            _monitors: I,
+        ) -> Result<(), BackendError>
+        where
+            I: IntoIterator,
+            I::Item: AsRef<str>,
+        {
+            Err(io::Error::other("colors cannot be changed").into())
+        }
+    }
+
+    #[test]
+    fn dry_run_records_colors() {
+        let env = DryRun::new(Fixed);
+        let warm = ColorAdjustment {
+            gamma: [1.0, 0.8, 0.6],
+            brightness: 0.9,
+            base_gamma: 1.0,
+        };
+        env.apply_gamma(warm, ["eDP-1"]).unwrap();
+        assert_eq!(env.read_gamma("eDP-1").unwrap(), warm);
+        let neutral = ColorAdjustment::default();
+        assert_eq!(env.read_gamma("HDMI-1").unwrap(), neutral);
+        assert_eq!(env.applied(), [("eDP-1".to_owned(), warm)]);
+    }
+
+    #[test]
+    fn stack_boxed_middlewares() {
+        let config = FadeConfig::new(0.0, 20.0).unwrap();
+        let env: BoxedGraphicalEnv =
+            Box::new(Logging::new(Fading::new(DryRun::new(Fixed), config)));
+        let monitors = env.list_monitors().unwrap();
+        let warm = ColorAdjustment {
+            gamma: [1.0, 0.7, 0.5],
+            brightness: 1.0,
+            base_gamma: 1.0,
+        };
+        env.apply_gamma(warm, &monitors).unwrap();
+        assert_eq!(env.read_gamma("HDMI-1").unwrap(), warm);
+        let failing: BoxedGraphicalEnv = Box::new(Logging::new(Fixed));
+        failing.apply_gamma(warm, &monitors).unwrap_err();
+    }
+}
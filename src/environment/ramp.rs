@@ -0,0 +1,134 @@
+//! Gamma ramps, that is, lookup tables mapping each input level of a color
+//! channel to an output level, as set by backends on CRTCs.
+
+use crate::channel::{self, ColorAdjustment};
+
+/// Builds the ramp of a color channel with the given size, raising the input
+/// to the inverse of the given base gamma, as `xrandr --gamma` does, and then
+/// scaling it by the given multiplier of the channel and by the brightness.
+pub fn channel_ramp(
+    size: usize,
+    multiplier: f64,
+    brightness: f64,
+    base_gamma: f64,
+) -> Vec<u16> {
+    let last = size.saturating_sub(1).max(1) as f64;
+    (0..size)
+        .map(|i| {
+            let input = (i as f64 / last).powf(1.0 / base_gamma);
+            let value = input * multiplier * brightness;
+            (value.clamp(0.0, 1.0) * f64::from(u16::MAX)).round() as u16
+        })
+        .collect()
+}
+
+/// Builds the ramps of the color channels with the given size for the given
+/// adjustment, in the order: red, green, blue.
+pub fn ramps(size: usize, adjustment: ColorAdjustment) -> [Vec<u16>; 3] {
+    channel::map_channel_vector(adjustment.gamma, |multiplier| {
+        channel_ramp(
+            size,
+            multiplier,
+            adjustment.brightness,
+            adjustment.base_gamma,
+        )
+    })
+}
+
+/// Resamples the given ramp to the given size, such as to restore a captured
+/// ramp on a CRTC whose ramps changed size, taking the nearest value.
+pub fn resample(ramp: &[u16], size: usize) -> Vec<u16> {
+    if ramp.len() == size || ramp.is_empty() {
+        return ramp.to_vec();
+    }
+    let last = size.saturating_sub(1).max(1) as f64;
+    let ramp_last = (ramp.len() - 1) as f64;
+    (0..size)
+        .map(|i| ramp[(i as f64 / last * ramp_last).round() as usize])
+        .collect()
+}
+
+/// Estimates the adjustment that produced the given ramps, the inverse of
+/// [`ramps`]: the last value of each ramp is its multiplier times the
+/// brightness, the brightness being the greatest of them, and the base gamma
+/// is found from the middle value of the brightest ramp.
+pub fn estimate_adjustment(ramps: [&[u16]; 3]) -> ColorAdjustment {
+    let size = ramps[channel::RED].len();
+    if size < 3 || ramps.iter().any(|ramp| ramp.len() != size) {
+        return ColorAdjustment::default();
+    }
+    let max = f64::from(u16::MAX);
+    let last = channel::map_channel_vector(ramps, |ramp| {
+        f64::from(ramp[size - 1]) / max
+    });
+    let brightest = (0..last.len())
+        .max_by(|&i, &j| last[i].total_cmp(&last[j]))
+        .unwrap_or(channel::RED);
+    let brightness = last[brightest];
+    if brightness <= 0.0 {
+        return ColorAdjustment::default();
+    }
+    let middle = size / 2;
+    let input = middle as f64 / (size - 1) as f64;
+    let output = f64::from(ramps[brightest][middle]) / max / brightness;
+    let base_gamma = if output > 0.0 && output < 1.0 {
+        input.ln() / output.ln()
+    } else {
+        1.0
+    };
+    ColorAdjustment {
+        gamma: last.map(|value| value / brightness),
+        brightness,
+        base_gamma,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::channel::ColorAdjustment;
+
+    use super::{estimate_adjustment, ramps, resample};
+
+    const EPSILON: f64 = 0.01;
+
+    #[test]
+    fn build_ramps_from_multipliers() {
+        let adjustment = ColorAdjustment {
+            gamma: [1.0, 0.65, 0.45],
+            brightness: 0.8,
+            base_gamma: 1.0,
+        };
+        let [red, green, blue] = ramps(256, adjustment);
+        assert_eq!(red[0], 0);
+        assert_eq!(red[255], 52428);
+        assert_eq!(green[255], 34078);
+        assert_eq!(blue[255], 23593);
+        // Without a base gamma, each ramp is a straight line.
+        let expected = 0.45 * 0.8 * 128.0 / 255.0 * f64::from(u16::MAX);
+        assert_eq!(blue[128], expected.round() as u16);
+    }
+
+    #[test]
+    fn resample_ramps() {
+        let ramp = [0, 100, 200, 300];
+        assert_eq!(resample(&ramp, 4), ramp);
+        assert_eq!(resample(&ramp, 7), [0, 100, 100, 200, 200, 300, 300]);
+        assert_eq!(resample(&ramp, 2), [0, 300]);
+    }
+
+    #[test]
+    fn estimate_adjustment_of_ramps() {
+        let adjustment = ColorAdjustment {
+            gamma: [1.0, 0.7, 0.5],
+            brightness: 0.8,
+            base_gamma: 1.2,
+        };
+        let [red, green, blue] = ramps(256, adjustment);
+        let estimated = estimate_adjustment([&red, &green, &blue]);
+        assert!((estimated.brightness - 0.8).abs() < EPSILON);
+        assert!((estimated.base_gamma - 1.2).abs() < EPSILON);
+        assert!((estimated.gamma[0] - 1.0).abs() < EPSILON);
+        assert!((estimated.gamma[1] - 0.7).abs() < EPSILON);
+        assert!((estimated.gamma[2] - 0.5).abs() < EPSILON);
+    }
+}
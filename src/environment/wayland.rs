@@ -0,0 +1,470 @@
+use std::{
+    env,
+    error::Error,
+    fs::{self, File, OpenOptions},
+    io::{self, Seek, Write},
+    os::fd::AsFd,
+    path::PathBuf,
+    process,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Mutex,
+    },
+    thread,
+};
+
+use wayland_client::{
+    globals::{registry_queue_init, GlobalListContents},
+    protocol::{
+        wl_output::{self, WlOutput},
+        wl_registry::{self, WlRegistry},
+    },
+    Connection,
+    Dispatch,
+    EventQueue,
+    Proxy,
+    QueueHandle,
+    WEnum,
+};
+use wayland_protocols_wlr::gamma_control::v1::client::{
+    zwlr_gamma_control_manager_v1::ZwlrGammaControlManagerV1,
+    zwlr_gamma_control_v1::{self, ZwlrGammaControlV1},
+};
+
+use crate::{
+    channel::{self, ColorAdjustment},
+    monitor::{Geometry, Monitor},
+};
+
+use super::{
+    apply_each,
+    ramp,
+    BackendError,
+    GraphicalEnv,
+    MonitorsChanged,
+    COALESCE_DELAY,
+};
+
+/// Greatest version of `wl_output` used, the first one telling output names.
+const OUTPUT_VERSION: u32 = 4;
+
+/// Graphical environment of Wayland compositors supporting the
+/// `wlr-gamma-control-unstable-v1` protocol, such as Sway, Hyprland and river.
+///
+/// Compositors restore the original gamma of an output as soon as its gamma
+/// control is destroyed, which happens when this environment is dropped, so
+/// colors only stick while it is alive.
+#[derive(Debug)]
+pub struct WlrEnv {
+    inner: Mutex<Inner>,
+}
+
+#[derive(Debug)]
+struct Inner {
+    _connection: Connection,
+    queue: EventQueue<State>,
+    state: State,
+}
+
+impl WlrEnv {
+    /// Connects to the compositor in `$WAYLAND_DISPLAY`, failing if it is not
+    /// set or if the compositor does not support the gamma control protocol.
+    pub fn load() -> io::Result<Self> {
+        if env::var_os("WAYLAND_DISPLAY").is_none() {
+            return Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                "$WAYLAND_DISPLAY is not set",
+            ));
+        }
+        let connection =
+            Connection::connect_to_env().map_err(wayland_error)?;
+        let (globals, mut queue) =
+            registry_queue_init::<State>(&connection).map_err(wayland_error)?;
+        let handle = queue.handle();
+        let manager = globals.bind(&handle, 1..=1, ()).map_err(|_| {
+            io::Error::new(
+                io::ErrorKind::Unsupported,
+                "the compositor does not support wlr-gamma-control-unstable-v1",
+            )
+        })?;
+        let mut state = State { manager, outputs: Vec::new() };
+        globals.contents().with_list(|list| {
+            for global in list {
+                if global.interface == WlOutput::interface().name {
+                    state.bind_output(
+                        globals.registry(),
+                        global.name,
+                        global.version,
+                        &handle,
+                    );
+                }
+            }
+        });
+        queue.roundtrip(&mut state).map_err(wayland_error)?;
+        let inner = Inner { _connection: connection, queue, state };
+        Ok(Self { inner: Mutex::new(inner) })
+    }
+
+    /// Runs the given function on the current state, after dispatching
+    /// pending events, such as outputs being added or removed.
+    fn with_state<F, T>(&self, function: F) -> Result<T, BackendError>
+    where
+        F: FnOnce(&mut State) -> Result<T, BackendError>,
+    {
+        let mut inner = self.inner.lock().unwrap_or_else(|error| {
+            self.inner.clear_poison();
+            error.into_inner()
+        });
+        let Inner { queue, state, .. } = &mut *inner;
+        queue.roundtrip(state).map_err(BackendError::unreachable)?;
+        if state.outputs.iter().any(|output| output.size == 0 && !output.failed)
+        {
+            // Outputs just bound tell their names and gamma sizes only in the
+            // next roundtrip.
+            queue.roundtrip(state).map_err(BackendError::unreachable)?;
+        }
+        let output = function(state)?;
+        queue.roundtrip(state).map_err(BackendError::unreachable)?;
+        Ok(output)
+    }
+}
+
+impl GraphicalEnv for WlrEnv {
+    fn list_monitors(&self) -> Result<Vec<Monitor>, BackendError> {
+        self.with_state(|state| {
+            Ok(state.outputs.iter().map(Output::describe).collect())
+        })
+    }
+
+    fn format_gamma(
+        &self,
+        adjustment: ColorAdjustment,
+    ) -> Result<String, BackendError> {
+        let gamma = adjustment.gamma;
+        Ok(format!(
+            "{:.3}:{:.3}:{:.3}",
+            gamma[channel::RED],
+            gamma[channel::GREEN],
+            gamma[channel::BLUE]
+        ))
+    }
+
+    fn read_gamma(
+        &self,
+        monitor: &str,
+    ) -> Result<ColorAdjustment, BackendError> {
+        // The protocol cannot read gamma tables, but outputs get their
+        // original ones back once released, which are assumed neutral.
+        self.with_state(|state| state.output(monitor).map(drop))?;
+        Ok(ColorAdjustment::default())
+    }
+
+    fn persists_gamma(&self) -> bool {
+        false
+    }
+
+    fn watch_monitors(
+        &self,
+        on_change: MonitorsChanged,
+    ) -> Result<(), BackendError> {
+        // Globals are watched through a connection of their own, since the
+        // connection used for requests is only dispatched when requesting.
+        let connection =
+            Connection::connect_to_env().map_err(BackendError::unreachable)?;
+        let (globals, mut queue) =
+            registry_queue_init::<OutputWatch>(&connection)
+                .map_err(BackendError::unreachable)?;
+        let outputs = globals.contents().with_list(|list| {
+            list.iter()
+                .filter(|global| global.interface == WlOutput::interface().name)
+                .map(|global| global.name)
+                .collect()
+        });
+        let mut watch = OutputWatch { outputs, changed: false, on_change };
+        thread::spawn(move || loop {
+            if let Err(error) = queue.blocking_dispatch(&mut watch) {
+                eprintln!("stopped watching monitors: {}", error);
+                break;
+            }
+            if watch.changed {
+                thread::sleep(COALESCE_DELAY);
+                if let Err(error) = queue.roundtrip(&mut watch) {
+                    eprintln!("stopped watching monitors: {}", error);
+                    break;
+                }
+                watch.changed = false;
+                if !(watch.on_change)() {
+                    break;
+                }
+            }
+        });
+        Ok(())
+    }
+
+    fn apply_gamma<I>(
+        &self,
+        adjustment: ColorAdjustment,
+        monitors: I,
+    ) -> Result<(), BackendError>
+    where
+        I: IntoIterator,
+        I::Item: AsRef<str>,
+    {
+        self.with_state(|state| {
+            apply_each(monitors, |monitor| {
+                let output = state.output(monitor)?;
+                let file = ramp_file(adjustment, output.size)?;
+                output.control.set_gamma(file.as_fd());
+                Ok(())
+            })
+        })
+    }
+}
+
+fn wayland_error<E>(error: E) -> io::Error
+where
+    E: Error + Send + Sync + 'static,
+{
+    io::Error::other(error)
+}
+
+/// Writes the gamma ramps of the given size for the given adjustment into an
+/// anonymous file, as expected by the gamma control protocol: all the red
+/// values, then all the green ones, and then all the blue ones.
+fn ramp_file(adjustment: ColorAdjustment, size: usize) -> io::Result<File> {
+    static COUNTER: AtomicUsize = AtomicUsize::new(0);
+    let directory = env::var_os("XDG_RUNTIME_DIR")
+        .map(PathBuf::from)
+        .unwrap_or_else(env::temp_dir);
+    let path = directory.join(format!(
+        "circadianlight-gamma-{}-{}",
+        process::id(),
+        COUNTER.fetch_add(1, Ordering::Relaxed)
+    ));
+    let mut file =
+        OpenOptions::new().read(true).write(true).create_new(true).open(&path)?;
+    fs::remove_file(&path)?;
+    let mut bytes = Vec::with_capacity(size * 3 * 2);
+    for value in ramp::ramps(size, adjustment).iter().flatten() {
+        bytes.extend_from_slice(&value.to_ne_bytes());
+    }
+    file.write_all(&bytes)?;
+    file.rewind()?;
+    Ok(file)
+}
+
+#[derive(Debug)]
+struct State {
+    manager: ZwlrGammaControlManagerV1,
+    outputs: Vec<Output>,
+}
+
+impl State {
+    fn bind_output(
+        &mut self,
+        registry: &WlRegistry,
+        global: u32,
+        version: u32,
+        handle: &QueueHandle<Self>,
+    ) {
+        let proxy = registry.bind::<WlOutput, _, _>(
+            global,
+            version.min(OUTPUT_VERSION),
+            handle,
+            global,
+        );
+        let control = self.manager.get_gamma_control(&proxy, handle, global);
+        self.outputs.push(Output {
+            global,
+            proxy,
+            control,
+            name: None,
+            position: None,
+            mode: None,
+            size: 0,
+            failed: false,
+        });
+    }
+
+    fn output_mut(&mut self, global: u32) -> Option<&mut Output> {
+        self.outputs.iter_mut().find(|output| output.global == global)
+    }
+
+    /// Output with the given name, if it can be controlled.
+    fn output(&self, monitor: &str) -> Result<&Output, BackendError> {
+        let output = self
+            .outputs
+            .iter()
+            .find(|output| output.name() == monitor)
+            .ok_or_else(|| BackendError::unknown_output(monitor))?;
+        if output.failed || output.size == 0 {
+            return Err(BackendError::Io(io::Error::other(format!(
+                "the gamma of output {} cannot be controlled, another program \
+                 may be controlling it",
+                monitor
+            ))));
+        }
+        Ok(output)
+    }
+}
+
+#[derive(Debug)]
+struct Output {
+    global: u32,
+    proxy: WlOutput,
+    control: ZwlrGammaControlV1,
+    name: Option<String>,
+    position: Option<(i32, i32)>,
+    mode: Option<(i32, i32)>,
+    size: usize,
+    failed: bool,
+}
+
+impl Output {
+    /// Name of the output, or a name made from its global if the compositor
+    /// does not tell it.
+    fn name(&self) -> String {
+        let global = self.global;
+        self.name.clone().unwrap_or_else(|| format!("wl_output-{}", global))
+    }
+
+    /// Describes this output with what the compositor told about it.
+    fn describe(&self) -> Monitor {
+        let geometry = match (self.position, self.mode) {
+            (Some((x, y)), Some((width, height))) => Some(Geometry {
+                x,
+                y,
+                width: width.max(0) as u32,
+                height: height.max(0) as u32,
+            }),
+            _ => None,
+        };
+        Monitor {
+            geometry,
+            ramp_size: Some(self.size).filter(|&size| size > 0),
+            ..Monitor::new(self.name())
+        }
+    }
+}
+
+impl Dispatch<WlRegistry, GlobalListContents> for State {
+    fn event(
+        state: &mut Self,
+        registry: &WlRegistry,
+        event: wl_registry::Event,
+        _data: &GlobalListContents,
+        _connection: &Connection,
+        handle: &QueueHandle<Self>,
+    ) {
+        match event {
+            wl_registry::Event::Global { name, interface, version }
+                if interface == WlOutput::interface().name =>
+            {
+                state.bind_output(registry, name, version, handle);
+            },
+            wl_registry::Event::GlobalRemove { name } => {
+                state.outputs.retain(|output| {
+                    if output.global != name {
+                        return true;
+                    }
+                    output.control.destroy();
+                    if output.proxy.version() >= 3 {
+                        output.proxy.release();
+                    }
+                    false
+                });
+            },
+            _ => (),
+        }
+    }
+}
+
+/// State of a watch of the outputs, knowing only the globals of the outputs.
+struct OutputWatch {
+    outputs: Vec<u32>,
+    changed: bool,
+    on_change: MonitorsChanged,
+}
+
+impl Dispatch<WlRegistry, GlobalListContents> for OutputWatch {
+    fn event(
+        watch: &mut Self,
+        _registry: &WlRegistry,
+        event: wl_registry::Event,
+        _data: &GlobalListContents,
+        _connection: &Connection,
+        _handle: &QueueHandle<Self>,
+    ) {
+        match event {
+            wl_registry::Event::Global { name, interface, .. }
+                if interface == WlOutput::interface().name =>
+            {
+                watch.outputs.push(name);
+                watch.changed = true;
+            },
+            wl_registry::Event::GlobalRemove { name } => {
+                let count = watch.outputs.len();
+                watch.outputs.retain(|&output| output != name);
+                watch.changed |= watch.outputs.len() != count;
+            },
+            _ => (),
+        }
+    }
+}
+
+impl Dispatch<WlOutput, u32> for State {
+    fn event(
+        state: &mut Self,
+        _output: &WlOutput,
+        event: wl_output::Event,
+        global: &u32,
+        _connection: &Connection,
+        _handle: &QueueHandle<Self>,
+    ) {
+        let Some(output) = state.output_mut(*global) else { return };
+        match event {
+            wl_output::Event::Name { name } => output.name = Some(name),
+            wl_output::Event::Geometry { x, y, .. } => {
+                output.position = Some((x, y))
+            },
+            wl_output::Event::Mode {
+                flags: WEnum::Value(flags), width, height, ..
+            } if flags.contains(wl_output::Mode::Current) => {
+                output.mode = Some((width, height));
+            },
+            _ => (),
+        }
+    }
+}
+
+impl Dispatch<ZwlrGammaControlManagerV1, ()> for State {
+    fn event(
+        _state: &mut Self,
+        _manager: &ZwlrGammaControlManagerV1,
+        _event: <ZwlrGammaControlManagerV1 as Proxy>::Event,
+        _data: &(),
+        _connection: &Connection,
+        _handle: &QueueHandle<Self>,
+    ) {
+    }
+}
+
+impl Dispatch<ZwlrGammaControlV1, u32> for State {
+    fn event(
+        state: &mut Self,
+        _control: &ZwlrGammaControlV1,
+        event: zwlr_gamma_control_v1::Event,
+        global: &u32,
+        _connection: &Connection,
+        _handle: &QueueHandle<Self>,
+    ) {
+        let Some(output) = state.output_mut(*global) else { return };
+        match event {
+            zwlr_gamma_control_v1::Event::GammaSize { size } => {
+                output.size = size as usize;
+            },
+            zwlr_gamma_control_v1::Event::Failed => output.failed = true,
+            _ => (),
+        }
+    }
+}
@@ -0,0 +1,430 @@
+use std::{error::Error, io, thread};
+
+use x11rb::{
+    connection::Connection,
+    errors::ReplyError,
+    protocol::{
+        randr::{self, ConnectionExt as _},
+        xproto::{Atom, AtomEnum, ConnectionExt as _, Window},
+        Event,
+    },
+    rust_connection::RustConnection,
+};
+
+use crate::{
+    channel::{self, ColorAdjustment},
+    monitor::{Edid, Geometry, Monitor},
+};
+
+use super::{
+    apply_each,
+    check_x_session,
+    ramp::{self, estimate_adjustment},
+    BackendError,
+    CapturedGamma,
+    GraphicalEnv,
+    MonitorsChanged,
+    COALESCE_DELAY,
+};
+
+/// Minimum version of RandR supporting CRTC gamma ramps.
+const MIN_VERSION: (u32, u32) = (1, 2);
+
+/// Name of the output property holding the EDID of the monitor.
+const EDID_PROPERTY: &[u8] = b"EDID";
+
+/// Greatest length of EDID read, in 32-bit units, enough for the base block
+/// and three extension blocks.
+const EDID_MAX_LEN: u32 = 128;
+
+/// Graphical environment talking the X11 RandR protocol directly, setting the
+/// gamma ramps of the CRTCs driving each output.
+#[derive(Debug)]
+pub struct RandrEnv {
+    connection: RustConnection,
+    root: Window,
+}
+
+impl RandrEnv {
+    /// Connects to the X server in `$DISPLAY`, failing if it is not set, if
+    /// the session is not an X session or if the server does not support
+    /// RandR 1.2 or later.
+    pub fn load() -> io::Result<Self> {
+        check_x_session()?;
+        let (connection, screen) = x11rb::connect(None).map_err(|error| {
+            io::Error::other(format!(
+                "could not connect to the X server: {}",
+                error
+            ))
+        })?;
+        let root = connection.setup().roots[screen].root;
+        let version = connection
+            .randr_query_version(MIN_VERSION.0, MIN_VERSION.1)
+            .map_err(x11_error)?
+            .reply()
+            .map_err(x11_error)?;
+        if (version.major_version, version.minor_version) < MIN_VERSION {
+            return Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                format!(
+                    "the X server supports RandR {}.{}, but {}.{} is needed",
+                    version.major_version,
+                    version.minor_version,
+                    MIN_VERSION.0,
+                    MIN_VERSION.1
+                ),
+            ));
+        }
+        Ok(Self { connection, root })
+    }
+
+    /// Connected outputs driven by some CRTC.
+    fn outputs(&self) -> Result<Vec<Output>, BackendError> {
+        let resources = self
+            .connection
+            .randr_get_screen_resources_current(self.root)
+            .map_err(BackendError::unreachable)?
+            .reply()
+            .map_err(reply_error)?;
+        let mut outputs = Vec::new();
+        for output in resources.outputs {
+            let info = self
+                .connection
+                .randr_get_output_info(output, resources.config_timestamp)
+                .map_err(BackendError::unreachable)?
+                .reply()
+                .map_err(reply_error)?;
+            if info.connection == randr::Connection::CONNECTED
+                && info.crtc != x11rb::NONE
+            {
+                let name = String::from_utf8_lossy(&info.name).into_owned();
+                outputs.push(Output { name, output, crtc: info.crtc });
+            }
+        }
+        Ok(outputs)
+    }
+
+    /// Gamma ramps currently set on the given CRTC, in the order: red, green,
+    /// blue.
+    fn crtc_ramps(
+        &self,
+        crtc: randr::Crtc,
+    ) -> Result<[Vec<u16>; 3], BackendError> {
+        let ramps = self
+            .connection
+            .randr_get_crtc_gamma(crtc)
+            .map_err(BackendError::unreachable)?
+            .reply()
+            .map_err(reply_error)?;
+        Ok([ramps.red, ramps.green, ramps.blue])
+    }
+
+    /// Size of the gamma ramps of the given CRTC.
+    fn crtc_gamma_size(&self, crtc: randr::Crtc) -> Result<u16, BackendError> {
+        let size = self
+            .connection
+            .randr_get_crtc_gamma_size(crtc)
+            .map_err(BackendError::unreachable)?
+            .reply()
+            .map_err(reply_error)?
+            .size;
+        Ok(size)
+    }
+
+    /// Describes the given output, telling whether it is the given primary
+    /// output, and reading its EDID from the property with the given atom,
+    /// if it exists.
+    fn describe(
+        &self,
+        output: Output,
+        primary: randr::Output,
+        edid_atom: Atom,
+    ) -> Result<Monitor, BackendError> {
+        let crtc = self
+            .connection
+            .randr_get_crtc_info(output.crtc, x11rb::CURRENT_TIME)
+            .map_err(BackendError::unreachable)?
+            .reply()
+            .map_err(reply_error)?;
+        let ramp_size = self.crtc_gamma_size(output.crtc)?;
+        let mut edid = None;
+        if edid_atom != x11rb::NONE {
+            let property = self
+                .connection
+                .randr_get_output_property(
+                    output.output,
+                    edid_atom,
+                    AtomEnum::ANY,
+                    0,
+                    EDID_MAX_LEN,
+                    false,
+                    false,
+                )
+                .map_err(BackendError::unreachable)?
+                .reply()
+                .map_err(reply_error)?;
+            edid = Edid::parse(&property.data);
+        }
+        Ok(Monitor {
+            primary: output.output == primary,
+            geometry: Some(Geometry {
+                x: i32::from(crtc.x),
+                y: i32::from(crtc.y),
+                width: u32::from(crtc.width),
+                height: u32::from(crtc.height),
+            }),
+            ramp_size: Some(usize::from(ramp_size)),
+            edid,
+            ..Monitor::new(output.name)
+        })
+    }
+}
+
+impl GraphicalEnv for RandrEnv {
+    fn list_monitors(&self) -> Result<Vec<Monitor>, BackendError> {
+        let primary = self
+            .connection
+            .randr_get_output_primary(self.root)
+            .map_err(BackendError::unreachable)?
+            .reply()
+            .map_err(reply_error)?
+            .output;
+        let edid_atom = self
+            .connection
+            .intern_atom(true, EDID_PROPERTY)
+            .map_err(BackendError::unreachable)?
+            .reply()
+            .map_err(reply_error)?
+            .atom;
+        self.outputs()?
+            .into_iter()
+            .map(|output| self.describe(output, primary, edid_atom))
+            .collect()
+    }
+
+    fn list_monitor_names(&self) -> Result<Vec<String>, BackendError> {
+        let outputs = self.outputs()?;
+        Ok(outputs.into_iter().map(|output| output.name).collect())
+    }
+
+    fn format_gamma(
+        &self,
+        adjustment: ColorAdjustment,
+    ) -> Result<String, BackendError> {
+        let gamma = adjustment.gamma;
+        Ok(format!(
+            "{:.3}:{:.3}:{:.3}",
+            gamma[channel::RED],
+            gamma[channel::GREEN],
+            gamma[channel::BLUE]
+        ))
+    }
+
+    fn read_gamma(
+        &self,
+        monitor: &str,
+    ) -> Result<ColorAdjustment, BackendError> {
+        let crtc = find_crtc(&self.outputs()?, monitor)?;
+        let [red, green, blue] = &self.crtc_ramps(crtc)?;
+        Ok(estimate_adjustment([red, green, blue]))
+    }
+
+    fn capture_gamma(
+        &self,
+        monitor: &str,
+    ) -> Result<CapturedGamma, BackendError> {
+        let crtc = find_crtc(&self.outputs()?, monitor)?;
+        let ramps = self.crtc_ramps(crtc)?;
+        let [red, green, blue] = &ramps;
+        let adjustment = estimate_adjustment([red, green, blue]);
+        Ok(CapturedGamma::Ramps { ramps, adjustment })
+    }
+
+    fn restore_gamma(
+        &self,
+        monitor: &str,
+        captured: &CapturedGamma,
+    ) -> Result<(), BackendError> {
+        let CapturedGamma::Ramps { ramps, .. } = captured else {
+            return self.apply_gamma(captured.adjustment(), [monitor]);
+        };
+        let crtc = find_crtc(&self.outputs()?, monitor)?;
+        let size = usize::from(self.crtc_gamma_size(crtc)?);
+        let [red, green, blue] =
+            ramps.each_ref().map(|ramp| ramp::resample(ramp, size));
+        self.connection
+            .randr_set_crtc_gamma(crtc, &red, &green, &blue)
+            .map_err(BackendError::unreachable)?
+            .check()
+            .map_err(reply_error)?;
+        self.connection.flush().map_err(BackendError::unreachable)
+    }
+
+    fn watch_monitors(
+        &self,
+        mut on_change: MonitorsChanged,
+    ) -> Result<(), BackendError> {
+        // Events are read from a connection of their own, so that they do not
+        // pile up in the connection used for requests.
+        let (connection, screen) =
+            x11rb::connect(None).map_err(BackendError::unreachable)?;
+        let root = connection.setup().roots[screen].root;
+        connection
+            .randr_select_input(
+                root,
+                randr::NotifyMask::SCREEN_CHANGE
+                    | randr::NotifyMask::OUTPUT_CHANGE,
+            )
+            .map_err(BackendError::unreachable)?
+            .check()
+            .map_err(reply_error)?;
+        thread::spawn(move || loop {
+            match connection.wait_for_event() {
+                Ok(
+                    Event::RandrScreenChangeNotify(_)
+                    | Event::RandrNotify(_),
+                ) => {
+                    thread::sleep(COALESCE_DELAY);
+                    while let Ok(Some(_)) = connection.poll_for_event() {}
+                    if !on_change() {
+                        break;
+                    }
+                },
+                Ok(_) => (),
+                Err(error) => {
+                    eprintln!("stopped watching monitors: {}", error);
+                    break;
+                },
+            }
+        });
+        Ok(())
+    }
+
+    fn apply_gamma<I>(
+        &self,
+        adjustment: ColorAdjustment,
+        monitors: I,
+    ) -> Result<(), BackendError>
+    where
+        I: IntoIterator,
+        I::Item: AsRef<str>,
+    {
+        let outputs = self.outputs()?;
+        apply_each(monitors, |monitor| {
+            let crtc = find_crtc(&outputs, monitor)?;
+            let size = self.crtc_gamma_size(crtc)?;
+            let ramps = ramp::ramps(usize::from(size), adjustment);
+            self.connection
+                .randr_set_crtc_gamma(
+                    crtc,
+                    &ramps[channel::RED],
+                    &ramps[channel::GREEN],
+                    &ramps[channel::BLUE],
+                )
+                .map_err(BackendError::unreachable)?
+                .check()
+                .map_err(reply_error)
+        })?;
+        self.connection.flush().map_err(BackendError::unreachable)
+    }
+}
+
+/// A connected output driven by a CRTC.
+#[derive(Debug, Clone)]
+struct Output {
+    name: String,
+    output: randr::Output,
+    crtc: randr::Crtc,
+}
+
+/// CRTC driving the output with the given name, among the given outputs.
+fn find_crtc(
+    outputs: &[Output],
+    monitor: &str,
+) -> Result<randr::Crtc, BackendError> {
+    outputs
+        .iter()
+        .find(|output| output.name == monitor)
+        .map(|output| output.crtc)
+        .ok_or_else(|| BackendError::unknown_output(monitor))
+}
+
+/// Converts the failure of a request: a broken connection means the server is
+/// unreachable, while errors replied by the server are other failures.
+fn reply_error(error: ReplyError) -> BackendError {
+    match error {
+        ReplyError::ConnectionError(error) => BackendError::unreachable(error),
+        ReplyError::X11Error(error) => BackendError::Io(io::Error::other(
+            format!("X request failed: {:?}", error.error_kind),
+        )),
+    }
+}
+
+fn x11_error<E>(error: E) -> io::Error
+where
+    E: Error + Send + Sync + 'static,
+{
+    io::Error::other(error)
+}
+
+#[cfg(test)]
+mod test {
+    use x11rb::protocol::randr::ConnectionExt as _;
+
+    use crate::{channel::ColorAdjustment, environment::GraphicalEnv};
+
+    use super::RandrEnv;
+
+    const EPSILON: f64 = 0.01;
+
+    /// Needs an X server, such as `xvfb-run cargo test -- --ignored`.
+    #[test]
+    #[ignore]
+    fn apply_and_read_gamma() {
+        let env = RandrEnv::load().unwrap();
+        let monitors = env.list_monitor_names().unwrap();
+        assert!(!monitors.is_empty());
+        let adjustment = ColorAdjustment {
+            gamma: [1.0, 0.8, 0.6],
+            brightness: 0.9,
+            base_gamma: 1.0,
+        };
+        env.apply_gamma(adjustment, &monitors).unwrap();
+        let read = env.read_gamma(&monitors[0]).unwrap();
+        assert!((read.brightness - 0.9).abs() < EPSILON);
+        assert!((read.gamma[2] - 0.6).abs() < EPSILON);
+        env.apply_gamma(ColorAdjustment::default(), &monitors).unwrap();
+    }
+
+    /// Needs an X server, such as `xvfb-run cargo test -- --ignored`.
+    #[test]
+    #[ignore]
+    fn capture_and_restore_ramps() {
+        let env = RandrEnv::load().unwrap();
+        let outputs = env.outputs().unwrap();
+        let crtc = outputs[0].crtc;
+        let size = usize::from(env.crtc_gamma_size(crtc).unwrap());
+        // A different gamma for each channel, as `xrandr --gamma 1:0.8:0.6`
+        // would set, which no adjustment reproduces.
+        let ramps = [1.0, 0.8, 0.6].map(|gamma: f64| {
+            (0..size)
+                .map(|i| {
+                    let input = i as f64 / (size - 1) as f64;
+                    (input.powf(1.0 / gamma) * f64::from(u16::MAX)) as u16
+                })
+                .collect::<Vec<_>>()
+        });
+        let [red, green, blue] = &ramps;
+        env.connection
+            .randr_set_crtc_gamma(crtc, red, green, blue)
+            .unwrap()
+            .check()
+            .unwrap();
+        let monitor = &outputs[0].name;
+        let captured = env.capture_gamma(monitor).unwrap();
+        env.apply_gamma(ColorAdjustment::default(), [monitor]).unwrap();
+        env.restore_gamma(monitor, &captured).unwrap();
+        assert_eq!(env.crtc_ramps(crtc).unwrap(), ramps);
+        env.apply_gamma(ColorAdjustment::default(), [monitor]).unwrap();
+    }
+}
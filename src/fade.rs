@@ -0,0 +1,152 @@
+//! Fades from the current screen colors to new ones, so that changes, such as
+//! on startup or when the profile changes, are not abrupt.
+
+use std::{
+    io,
+    thread,
+    time::{Duration, Instant},
+};
+
+use crate::{
+    channel::ColorAdjustment,
+    config::FadeConfig,
+    environment::{self, GraphicalEnv},
+    schedule::Interpolate,
+};
+
+/// A fade from the colors of each monitor at some moment to a target.
+#[derive(Debug, Clone)]
+pub struct Fade {
+    from: Vec<(String, ColorAdjustment)>,
+    start: Instant,
+    duration: Duration,
+}
+
+impl Fade {
+    /// Starts a fade at the given moment, lasting the given duration, from
+    /// the given colors of each monitor.
+    pub fn new(
+        from: Vec<(String, ColorAdjustment)>,
+        start: Instant,
+        duration: Duration,
+    ) -> Self {
+        Self { from, start, duration }
+    }
+
+    /// Progress of this fade at the given moment, in the interval `[0,1]`.
+    pub fn progress(&self, now: Instant) -> f64 {
+        if self.duration.is_zero() {
+            return 1.0;
+        }
+        let elapsed = now.saturating_duration_since(self.start);
+        (elapsed.as_secs_f64() / self.duration.as_secs_f64()).min(1.0)
+    }
+
+    /// Whether this fade has reached its target at the given moment.
+    pub fn is_finished(&self, now: Instant) -> bool {
+        self.progress(now) >= 1.0
+    }
+
+    /// Colors of the given monitor at the given moment of this fade towards
+    /// the given target. Monitors whose colors were not known at the start
+    /// go straight to the target.
+    pub fn adjustment(
+        &self,
+        monitor: &str,
+        target: ColorAdjustment,
+        now: Instant,
+    ) -> ColorAdjustment {
+        let progress = self.progress(now);
+        let from = self
+            .from
+            .iter()
+            .find(|(other, _)| other == monitor)
+            .map(|(_, adjustment)| *adjustment);
+        match from {
+            Some(from) if progress < 1.0 => from.interpolate(target, progress),
+            _ => target,
+        }
+    }
+
+    /// Colors of each monitor at the given moment of this fade towards its
+    /// given target.
+    pub fn adjustments(
+        &self,
+        targets: &[(String, ColorAdjustment)],
+        now: Instant,
+    ) -> Vec<(String, ColorAdjustment)> {
+        targets
+            .iter()
+            .map(|(monitor, target)| {
+                (monitor.clone(), self.adjustment(monitor, *target, now))
+            })
+            .collect()
+    }
+}
+
+/// Fades the monitors from their given current colors to their given targets,
+/// blocking until the fade is finished.
+pub fn fade_to<G>(
+    graphical_env: G,
+    from: Vec<(String, ColorAdjustment)>,
+    targets: &[(String, ColorAdjustment)],
+    config: FadeConfig,
+) -> io::Result<()>
+where
+    G: GraphicalEnv,
+{
+    let fade = Fade::new(from, Instant::now(), config.duration());
+    loop {
+        let now = Instant::now();
+        let adjustments = fade.adjustments(targets, now);
+        environment::apply_per_monitor(&graphical_env, &adjustments)?;
+        if fade.is_finished(now) {
+            break Ok(());
+        }
+        thread::sleep(config.step());
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::{Duration, Instant};
+
+    use crate::channel::ColorAdjustment;
+
+    use super::Fade;
+
+    const EPSILON: f64 = 0.0001;
+
+    #[test]
+    fn fade_towards_target() {
+        let start = Instant::now();
+        let from = ColorAdjustment {
+            gamma: [1.0, 0.6, 0.4],
+            brightness: 0.8,
+            base_gamma: 1.0,
+        };
+        let target = ColorAdjustment::default();
+        let fade = Fade::new(
+            vec![("eDP-1".to_owned(), from)],
+            start,
+            Duration::from_secs(2),
+        );
+        let middle = start + Duration::from_secs(1);
+        let half = fade.adjustment("eDP-1", target, middle);
+        assert!((half.gamma[1] - 0.8).abs() < EPSILON);
+        assert!((half.gamma[2] - 0.7).abs() < EPSILON);
+        assert!((half.brightness - 0.9).abs() < EPSILON);
+        assert_eq!(fade.adjustment("eDP-1", target, start), from);
+        assert_eq!(fade.adjustment("HDMI-1", target, start), target);
+        let end = start + Duration::from_secs(3);
+        assert!(fade.is_finished(end));
+        assert_eq!(fade.adjustment("eDP-1", target, end), target);
+    }
+
+    #[test]
+    fn zero_duration_finishes_immediately() {
+        let start = Instant::now();
+        let fade = Fade::new(Vec::new(), start, Duration::ZERO);
+        assert!(fade.is_finished(start));
+    }
+}
@@ -5,6 +5,7 @@ use chrono::Timelike;
 
 /// Converts a `chrono` time-like object into a compressed `24h` day hour in the
 /// interval `[0,1)`.
+#[allow(clippy::needless_maybe_sized)]
 pub fn timelike_to_hours<T>(timelike: &T) -> f64
 where
     T: Timelike + ?Sized,
@@ -18,6 +19,10 @@ where
 /// A day phase.
 #[derive(Debug, Clone, Copy, PartialEq, PartialOrd)]
 pub enum DayPhase {
+    /// A part of the transition from night to day, with an indication of where
+    /// this transition is, given `0` for just after night, `1` for starting
+    /// day (i.e. in the interval `[0, 1)`.
+    Dawn(f64),
     /// Any part of the bright sun day phase.
     Day,
     /// A part of the transition from day to night, with an indication of where
@@ -35,67 +40,38 @@ impl DayPhase {
         hour_config: HourConfig,
         current_hour: f64,
     ) -> Self {
-        if hour_config.day_start() <= hour_config.dusk_start()
-            && hour_config.dusk_start() <= hour_config.night_start()
-        {
-            if current_hour >= hour_config.day_start()
-                && current_hour < hour_config.dusk_start()
-            {
-                Self::Day
-            } else if current_hour >= hour_config.dusk_start()
-                && current_hour < hour_config.night_start()
-            {
-                Self::Dusk(
-                    (current_hour - hour_config.dusk_start())
-                        / (hour_config.night_start()
-                            - hour_config.dusk_start()),
-                )
-            } else {
-                Self::Night
-            }
-        } else if hour_config.night_start() <= hour_config.day_start()
-            && hour_config.day_start() <= hour_config.dusk_start()
-        {
-            if current_hour >= hour_config.day_start()
-                && current_hour < hour_config.dusk_start()
-            {
-                Self::Day
-            } else if current_hour >= hour_config.dusk_start() {
-                Self::Dusk(
-                    (current_hour - hour_config.dusk_start())
-                        / (1.0 + hour_config.night_start()
-                            - hour_config.dusk_start()),
-                )
-            } else if current_hour < hour_config.night_start() {
-                Self::Dusk(
-                    (1.0 + current_hour - hour_config.dusk_start())
-                        / (1.0 + hour_config.night_start()
-                            - hour_config.dusk_start()),
-                )
-            } else {
-                Self::Night
-            }
-        } else if hour_config.dusk_start() <= hour_config.night_start()
-            && hour_config.night_start() <= hour_config.day_start()
-        {
-            if current_hour >= hour_config.day_start()
-                || current_hour < hour_config.dusk_start()
-            {
-                Self::Day
-            } else if current_hour >= hour_config.dusk_start()
-                && current_hour < hour_config.night_start()
-            {
-                Self::Dusk(
-                    (current_hour - hour_config.dusk_start())
-                        / (hour_config.night_start()
-                            - hour_config.dusk_start()),
-                )
-            } else {
-                Self::Night
-            }
-        } else {
+        let dawn_start = hour_config.dawn_start();
+        let day_start = cycle_distance(dawn_start, hour_config.day_start());
+        let dusk_start = cycle_distance(dawn_start, hour_config.dusk_start());
+        let night_start =
+            cycle_distance(dawn_start, hour_config.night_start());
+        let current_hour = cycle_distance(dawn_start, current_hour);
+
+        if day_start > dusk_start || dusk_start > night_start {
             panic!("Incorrect hour configuration")
         }
+
+        if current_hour < day_start {
+            Self::Dawn(current_hour / day_start)
+        } else if current_hour < dusk_start {
+            Self::Day
+        } else if current_hour < night_start {
+            Self::Dusk(
+                (current_hour - dusk_start) / (night_start - dusk_start),
+            )
+        } else {
+            Self::Night
+        }
+    }
+}
+
+/// Distance going forward from hour `start` to hour `end`, both compressed in
+/// the interval `[0,1)`, wrapping around `24h` if needed.
+pub fn cycle_distance(start: f64, end: f64) -> f64 {
+    if end >= start {
+        end - start
+    } else {
+        1.0 + end - start
     }
 }
 
@@ -107,6 +83,16 @@ mod test {
 
     const EPSILON: f64 = 0.01;
 
+    #[test]
+    fn day_phase_from_current_hour_is_dawn() {
+        match DayPhase::from_current_hour(HourConfig::default(), 4.5 / 24.0) {
+            DayPhase::Dawn(scale) => {
+                assert!((scale - (4.5 - 4.0) / (5.0 - 4.0)).abs() < EPSILON)
+            },
+            value => panic!("Expected dawn, found {:?}", value),
+        }
+    }
+
     #[test]
     fn day_phase_from_current_hour_is_day() {
         assert_eq!(
@@ -140,7 +126,8 @@ mod test {
     #[test]
     fn chaotic_day_phae_order() {
         let config =
-            HourConfig::new(10.0 / 24.0, 19.0 / 24.0, 1.0 / 24.0).unwrap();
+            HourConfig::new(10.0 / 24.0, 19.0 / 24.0, 1.0 / 24.0, 8.0 / 24.0)
+                .unwrap();
         assert_eq!(
             DayPhase::from_current_hour(config, 1.1 / 24.0),
             DayPhase::Night,
@@ -157,5 +144,26 @@ mod test {
             DayPhase::from_current_hour(config, 0.0 / 24.0),
             DayPhase::Dusk(_)
         ));
+        assert!(matches!(
+            DayPhase::from_current_hour(config, 9.0 / 24.0),
+            DayPhase::Dawn(_)
+        ));
+    }
+
+    #[test]
+    fn dawn_wrapping_around_midnight() {
+        let config =
+            HourConfig::new(2.0 / 24.0, 14.0 / 24.0, 18.0 / 24.0, 23.0 / 24.0)
+                .unwrap();
+        match DayPhase::from_current_hour(config, 1.0 / 24.0) {
+            DayPhase::Dawn(scale) => {
+                assert!((scale - 2.0 / 3.0).abs() < EPSILON)
+            },
+            value => panic!("Expected dawn, found {:?}", value),
+        }
+        assert_eq!(
+            DayPhase::from_current_hour(config, 20.0 / 24.0),
+            DayPhase::Night,
+        );
     }
 }
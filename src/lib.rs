@@ -4,6 +4,16 @@
 
 pub mod hour;
 pub mod channel;
+pub mod curve;
+pub mod schedule;
+pub mod solar;
+pub mod temperature;
 pub mod config;
+pub mod watch;
+pub mod signal;
+pub mod fade;
+pub mod overrides;
+pub mod control;
+pub mod monitor;
 pub mod environment;
 pub mod cli;
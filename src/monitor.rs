@@ -0,0 +1,222 @@
+//! Descriptions of the monitors connected to the graphical environment.
+
+use core::fmt;
+
+use serde::Serialize;
+
+/// The fixed header starting every EDID blob.
+const EDID_HEADER: [u8; 8] = [0x00, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0x00];
+
+/// Length of the base block of an EDID blob.
+const EDID_BLOCK_LEN: usize = 128;
+
+/// Offset of the first of the four display descriptors in the base block.
+const DESCRIPTORS_OFFSET: usize = 54;
+
+/// Length of each display descriptor.
+const DESCRIPTOR_LEN: usize = 18;
+
+/// Tag of the display descriptor holding the serial number as text.
+const SERIAL_TAG: u8 = 0xff;
+
+/// Tag of the display descriptor holding the name of the model.
+const NAME_TAG: u8 = 0xfc;
+
+/// A monitor connected to the graphical environment, as described by its
+/// backend. Besides the name, each piece of information is only available
+/// with some of the backends.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Monitor {
+    /// Name of the connector or output, such as `eDP-1` or `HDMI-A-1`, used
+    /// to select the monitor.
+    pub name: String,
+    /// Whether this is the primary monitor.
+    pub primary: bool,
+    /// Position and size of the monitor in the screen, if known.
+    pub geometry: Option<Geometry>,
+    /// Number of entries of each of the gamma ramps of the monitor, if known.
+    pub ramp_size: Option<usize>,
+    /// Identification of the monitor read from its EDID, if known.
+    pub edid: Option<Edid>,
+}
+
+impl Monitor {
+    /// Description of a monitor of which only the name is known.
+    pub fn new<S>(name: S) -> Self
+    where
+        S: Into<String>,
+    {
+        Self {
+            name: name.into(),
+            primary: false,
+            geometry: None,
+            ramp_size: None,
+            edid: None,
+        }
+    }
+
+    /// Serial number of the monitor, if known, which, unlike the name, stays
+    /// the same regardless of the connector it is plugged into.
+    pub fn serial(&self) -> Option<&str> {
+        self.edid.as_ref()?.serial.as_deref()
+    }
+}
+
+impl AsRef<str> for Monitor {
+    fn as_ref(&self) -> &str {
+        &self.name
+    }
+}
+
+/// Position and size of a monitor in the screen, in pixels.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct Geometry {
+    /// Horizontal position of the left edge.
+    pub x: i32,
+    /// Vertical position of the top edge.
+    pub y: i32,
+    /// Width of the current mode.
+    pub width: u32,
+    /// Height of the current mode.
+    pub height: u32,
+}
+
+impl fmt::Display for Geometry {
+    fn fmt(&self, fmtr: &mut fmt::Formatter) -> fmt::Result {
+        write!(fmtr, "{}x{}{:+}{:+}", self.width, self.height, self.x, self.y)
+    }
+}
+
+/// Identification of a monitor, read from its EDID (Extended Display
+/// Identification Data).
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Edid {
+    /// Three-letter PNP code of the manufacturer, such as `DEL` or `SAM`.
+    pub vendor: String,
+    /// Product code given by the manufacturer.
+    pub product: u16,
+    /// Name of the model, such as `DELL U2720Q`, if the EDID tells it.
+    pub model: Option<String>,
+    /// Serial number, if the EDID tells it, either as text or as a number.
+    pub serial: Option<String>,
+}
+
+impl Edid {
+    /// Parses the base block of the given EDID blob, failing if it is too
+    /// short or does not start with the EDID header.
+    pub fn parse(bytes: &[u8]) -> Option<Self> {
+        let block = bytes.get(..EDID_BLOCK_LEN)?;
+        if block[..EDID_HEADER.len()] != EDID_HEADER {
+            return None;
+        }
+        let vendor_code = u16::from_be_bytes([block[8], block[9]]);
+        let vendor = [10, 5, 0]
+            .into_iter()
+            .map(|shift| {
+                let letter = (vendor_code >> shift) & 0x1f;
+                char::from(b'A' - 1 + letter as u8)
+            })
+            .collect();
+        let product = u16::from_le_bytes([block[10], block[11]]);
+        let serial_number =
+            u32::from_le_bytes([block[12], block[13], block[14], block[15]]);
+        let mut model = None;
+        let mut serial = None;
+        let descriptors = block[DESCRIPTORS_OFFSET..]
+            .chunks_exact(DESCRIPTOR_LEN)
+            .filter(|descriptor| descriptor[..3] == [0, 0, 0]);
+        for descriptor in descriptors {
+            match descriptor[3] {
+                NAME_TAG => model = descriptor_text(descriptor),
+                SERIAL_TAG => serial = descriptor_text(descriptor),
+                _ => (),
+            }
+        }
+        if serial.is_none() && serial_number != 0 {
+            serial = Some(serial_number.to_string());
+        }
+        Some(Self { vendor, product, model, serial })
+    }
+}
+
+/// Text of a display descriptor, terminated by a line feed and padded with
+/// spaces, if not empty.
+fn descriptor_text(descriptor: &[u8]) -> Option<String> {
+    let text = &descriptor[5..];
+    let end = text.iter().position(|&byte| byte == b'\n').unwrap_or(text.len());
+    let text = String::from_utf8_lossy(&text[..end]).trim().to_owned();
+    Some(text).filter(|text| !text.is_empty())
+}
+
+#[cfg(test)]
+pub(crate) mod test {
+    use super::{Edid, Geometry};
+
+    /// Builds the base block of an EDID with the given vendor code, product,
+    /// numeric serial, and name and serial descriptors.
+    pub(crate) fn edid(
+        vendor: &str,
+        product: u16,
+        serial_number: u32,
+        name: Option<&str>,
+        serial: Option<&str>,
+    ) -> Vec<u8> {
+        let mut bytes = vec![0; 128];
+        bytes[..8].copy_from_slice(&super::EDID_HEADER);
+        let vendor = vendor
+            .bytes()
+            .fold(0, |code, letter| code << 5 | u16::from(letter - b'A' + 1));
+        bytes[8..10].copy_from_slice(&vendor.to_be_bytes());
+        bytes[10..12].copy_from_slice(&product.to_le_bytes());
+        bytes[12..16].copy_from_slice(&serial_number.to_le_bytes());
+        let descriptors =
+            [(super::NAME_TAG, name), (super::SERIAL_TAG, serial)];
+        for (i, (tag, text)) in descriptors.into_iter().enumerate() {
+            let Some(text) = text else { continue };
+            let start = 72 + i * 18;
+            let descriptor = &mut bytes[start..start + 18];
+            descriptor[3] = tag;
+            descriptor[5..].fill(b' ');
+            descriptor[5..5 + text.len()].copy_from_slice(text.as_bytes());
+            if text.len() < 13 {
+                descriptor[5 + text.len()] = b'\n';
+            }
+        }
+        bytes
+    }
+
+    #[test]
+    fn parse_edid() {
+        let bytes =
+            edid("DEL", 0xa0ec, 1234, Some("DELL U2720Q"), Some("7YRZ"));
+        let parsed = Edid::parse(&bytes).unwrap();
+        assert_eq!(parsed.vendor, "DEL");
+        assert_eq!(parsed.product, 0xa0ec);
+        assert_eq!(parsed.model.as_deref(), Some("DELL U2720Q"));
+        assert_eq!(parsed.serial.as_deref(), Some("7YRZ"));
+    }
+
+    #[test]
+    fn parse_edid_with_numeric_serial() {
+        let parsed = Edid::parse(&edid("BOE", 0x0747, 42, None, None)).unwrap();
+        assert_eq!(parsed.vendor, "BOE");
+        assert_eq!(parsed.model, None);
+        assert_eq!(parsed.serial.as_deref(), Some("42"));
+        let parsed = Edid::parse(&edid("BOE", 0x0747, 0, None, None)).unwrap();
+        assert_eq!(parsed.serial, None);
+    }
+
+    #[test]
+    fn reject_invalid_edid() {
+        assert_eq!(Edid::parse(&[0; 128]), None);
+        assert_eq!(Edid::parse(&edid("DEL", 1, 1, None, None)[..64]), None);
+    }
+
+    #[test]
+    fn format_geometry() {
+        let geometry = Geometry { x: 1920, y: 0, width: 2560, height: 1440 };
+        assert_eq!(geometry.to_string(), "2560x1440+1920+0");
+        let geometry = Geometry { x: -1280, y: 0, width: 1280, height: 1024 };
+        assert_eq!(geometry.to_string(), "1280x1024-1280+0");
+    }
+}
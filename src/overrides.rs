@@ -0,0 +1,330 @@
+//! Temporary overrides of the schedule, such as pauses, which expire on their
+//! own and are persisted across restarts of the server.
+
+use std::{
+    env,
+    error::Error,
+    fmt,
+    fs,
+    io,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+use chrono::{DateTime, Duration, Local, NaiveTime};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    channel::ColorAdjustment,
+    config::{file::APP_DIR, Config},
+    schedule::ColorTarget,
+};
+
+/// Name of the file holding the current override, in the state directory of
+/// this application.
+pub const STATE_FILE_NAME: &str = "override.toml";
+
+/// Error yielded when an invalid override target is parsed.
+#[derive(Debug, Clone)]
+pub struct InvalidOverrideTarget {
+    /// The given (invalid) target.
+    pub target: String,
+}
+
+impl fmt::Display for InvalidOverrideTarget {
+    fn fmt(&self, fmtr: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            fmtr,
+            "Invalid override target, expected one of neutral, night, day, \
+             red:green:blue or KELVINK, given: {}",
+            self.target
+        )
+    }
+}
+
+impl Error for InvalidOverrideTarget {}
+
+/// Error yielded when an expiry cannot be resolved to a moment, such as a
+/// number of minutes too far in the future.
+#[derive(Debug, Clone)]
+pub struct InvalidExpiry {
+    /// The given (invalid) number of minutes.
+    pub minutes: f64,
+}
+
+impl fmt::Display for InvalidExpiry {
+    fn fmt(&self, fmtr: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            fmtr,
+            "Invalid expiry, expected a moment that can be represented, given \
+             minutes: {}",
+            self.minutes
+        )
+    }
+}
+
+impl Error for InvalidExpiry {}
+
+/// Target of the screen colors while the schedule is overriden.
+#[derive(Debug, Clone, Copy, PartialEq, PartialOrd)]
+pub enum OverrideTarget {
+    /// Neutral colors and full brightness, i.e. the schedule is paused.
+    Neutral,
+    /// Colors and brightness of the night.
+    Night,
+    /// Colors and brightness of the day.
+    Day,
+    /// The given colors, with the scheduled brightness.
+    Color(ColorTarget),
+}
+
+impl OverrideTarget {
+    /// Adjustment of the screen colors for this target, given the
+    /// configuration and the scheduled adjustment.
+    pub fn adjustment(
+        self,
+        config: &Config,
+        scheduled: ColorAdjustment,
+    ) -> ColorAdjustment {
+        match self {
+            Self::Neutral => ColorAdjustment::default(),
+            Self::Night => ColorAdjustment {
+                gamma: config.night_target().gamma(),
+                brightness: config.brightness.night(),
+                ..scheduled
+            },
+            Self::Day => ColorAdjustment {
+                gamma: config.day_target().gamma(),
+                brightness: config.brightness.day(),
+                ..scheduled
+            },
+            Self::Color(target) => {
+                ColorAdjustment { gamma: target.gamma(), ..scheduled }
+            },
+        }
+    }
+}
+
+impl fmt::Display for OverrideTarget {
+    fn fmt(&self, fmtr: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Neutral => fmtr.pad("neutral"),
+            Self::Night => fmtr.pad("night"),
+            Self::Day => fmtr.pad("day"),
+            Self::Color(target) => write!(fmtr, "{}", target),
+        }
+    }
+}
+
+impl FromStr for OverrideTarget {
+    type Err = InvalidOverrideTarget;
+
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        match input.trim().to_lowercase().as_str() {
+            "neutral" => Ok(Self::Neutral),
+            "night" => Ok(Self::Night),
+            "day" => Ok(Self::Day),
+            _ => input.parse().map(Self::Color).map_err(|_| {
+                InvalidOverrideTarget { target: input.to_owned() }
+            }),
+        }
+    }
+}
+
+/// When an override expires, relative to the moment it is requested.
+#[derive(Debug, Clone, Copy, PartialEq, PartialOrd)]
+pub enum Expiry {
+    /// The override lasts until it is explicitly ended.
+    Never,
+    /// The override lasts for the given number of minutes.
+    After(f64),
+    /// The override lasts until the next occurrence of the given hour.
+    Until(NaiveTime),
+}
+
+impl Expiry {
+    /// Moment of the expiry, if any, for an override requested at the given
+    /// moment.
+    pub fn resolve(
+        self,
+        now: DateTime<Local>,
+    ) -> Result<Option<DateTime<Local>>, InvalidExpiry> {
+        match self {
+            Self::Never => Ok(None),
+            Self::After(minutes) => {
+                Duration::try_milliseconds((minutes * 60e3) as i64)
+                    .and_then(|duration| now.checked_add_signed(duration))
+                    .map(Some)
+                    .ok_or(InvalidExpiry { minutes })
+            },
+            Self::Until(time) => {
+                let mut date = now.date_naive();
+                if time <= now.time() {
+                    let Some(next) = date.succ_opt() else { return Ok(None) };
+                    date = next;
+                }
+                Ok(date.and_time(time).and_local_timezone(Local).earliest())
+            },
+        }
+    }
+}
+
+/// An override of the schedule with an optional expiry.
+#[derive(Debug, Clone, Copy, PartialEq, PartialOrd)]
+pub struct Override {
+    /// Target of the screen colors during the override.
+    pub target: OverrideTarget,
+    /// Moment when the override expires, if any.
+    pub until: Option<DateTime<Local>>,
+}
+
+impl Override {
+    /// Whether this override is still in effect at the given moment.
+    pub fn is_active(&self, now: DateTime<Local>) -> bool {
+        self.until.is_none_or(|until| now < until)
+    }
+
+    /// Loads the override persisted at the given path, if any.
+    pub fn load<P>(path: P) -> io::Result<Option<Self>>
+    where
+        P: AsRef<Path>,
+    {
+        let source = match fs::read_to_string(path) {
+            Ok(source) => source,
+            Err(error) if error.kind() == io::ErrorKind::NotFound => {
+                return Ok(None)
+            },
+            Err(error) => return Err(error),
+        };
+        let invalid = |error: &dyn fmt::Display| {
+            io::Error::new(io::ErrorKind::InvalidData, error.to_string())
+        };
+        let state = toml::from_str::<OverrideState>(&source)
+            .map_err(|error| invalid(&error))?;
+        let target = state.target.parse().map_err(|error| invalid(&error))?;
+        let until = match state.until {
+            Some(until) => Some(
+                DateTime::parse_from_rfc3339(&until)
+                    .map_err(|error| invalid(&error))?
+                    .with_timezone(&Local),
+            ),
+            None => None,
+        };
+        Ok(Some(Self { target, until }))
+    }
+
+    /// Persists this override at the given path, creating parent directories
+    /// as needed.
+    pub fn save<P>(&self, path: P) -> io::Result<()>
+    where
+        P: AsRef<Path>,
+    {
+        let path = path.as_ref();
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let state = OverrideState {
+            target: self.target.to_string(),
+            until: self.until.map(|until| until.to_rfc3339()),
+        };
+        let contents = toml::to_string(&state).map_err(|error| {
+            io::Error::new(io::ErrorKind::InvalidData, error.to_string())
+        })?;
+        fs::write(path, contents)
+    }
+
+    /// Removes the override persisted at the given path, if any.
+    pub fn clear<P>(path: P) -> io::Result<()>
+    where
+        P: AsRef<Path>,
+    {
+        match fs::remove_file(path) {
+            Err(error) if error.kind() != io::ErrorKind::NotFound => Err(error),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Default path of the file holding the current override, that is,
+/// `$XDG_STATE_HOME/circadianlight/override.toml`, falling back to
+/// `$HOME/.local/state/circadianlight/override.toml`.
+pub fn default_state_path() -> Option<PathBuf> {
+    let state_home = env::var_os("XDG_STATE_HOME")
+        .map(PathBuf::from)
+        .filter(|path| path.is_absolute())
+        .or_else(|| {
+            env::var_os("HOME")
+                .map(|home| PathBuf::from(home).join(".local").join("state"))
+        })?;
+    Some(state_home.join(APP_DIR).join(STATE_FILE_NAME))
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct OverrideState {
+    target: String,
+    until: Option<String>,
+}
+
+#[cfg(test)]
+mod test {
+    use std::{env, process};
+
+    use chrono::{Local, NaiveTime, TimeZone, Timelike};
+
+    use crate::schedule::ColorTarget;
+
+    use super::{Expiry, Override, OverrideTarget};
+
+    #[test]
+    fn parse_targets() {
+        let targets = [
+            OverrideTarget::Neutral,
+            OverrideTarget::Night,
+            OverrideTarget::Day,
+            OverrideTarget::Color(ColorTarget::Temperature(3400.0)),
+            OverrideTarget::Color(ColorTarget::Gamma([1.0, 0.8, 0.6])),
+        ];
+        for target in targets {
+            let parsed = target.to_string().parse::<OverrideTarget>();
+            assert_eq!(parsed.unwrap(), target);
+        }
+        "dusk".parse::<OverrideTarget>().unwrap_err();
+    }
+
+    #[test]
+    fn expiry_until_next_occurrence() {
+        let now = Local.with_ymd_and_hms(2024, 3, 10, 22, 30, 0).unwrap();
+        let seven = NaiveTime::from_hms_opt(7, 0, 0).unwrap();
+        let until = Expiry::Until(seven).resolve(now).unwrap().unwrap();
+        assert_eq!(until.date_naive(), now.date_naive().succ_opt().unwrap());
+        assert_eq!(until.hour(), 7);
+        let eleven = NaiveTime::from_hms_opt(23, 0, 0).unwrap();
+        let until = Expiry::Until(eleven).resolve(now).unwrap().unwrap();
+        assert_eq!(until.date_naive(), now.date_naive());
+        let until = Expiry::After(90.0).resolve(now).unwrap().unwrap();
+        assert_eq!(until - now, chrono::Duration::minutes(90));
+        assert_eq!(Expiry::Never.resolve(now).unwrap(), None);
+    }
+
+    #[test]
+    fn error_when_expiry_is_too_far() {
+        let now = Local.with_ymd_and_hms(2024, 3, 10, 22, 30, 0).unwrap();
+        Expiry::After(1e15).resolve(now).unwrap_err();
+        Expiry::After(f64::MAX).resolve(now).unwrap_err();
+    }
+
+    #[test]
+    fn persist_override() {
+        let path = env::temp_dir()
+            .join(format!("circadianlight-override-{}", process::id()))
+            .join("override.toml");
+        let until = Local.with_ymd_and_hms(2024, 3, 11, 7, 0, 0).unwrap();
+        let saved =
+            Override { target: OverrideTarget::Night, until: Some(until) };
+        saved.save(&path).unwrap();
+        assert_eq!(Override::load(&path).unwrap(), Some(saved));
+        Override::clear(&path).unwrap();
+        assert_eq!(Override::load(&path).unwrap(), None);
+        Override::clear(&path).unwrap();
+    }
+}
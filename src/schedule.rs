@@ -0,0 +1,443 @@
+//! Keyframe-based schedules of the gamma color spectrum (or of any other
+//! interpolable value, such as the brightness) along the day.
+
+use std::{error::Error, fmt, str::FromStr};
+
+use crate::{
+    config::{HourConfig, RejectedValue},
+    curve::TransitionCurve,
+    hour::cycle_distance,
+    temperature::{temperature_to_gamma, MAX_TEMPERATURE, MIN_TEMPERATURE},
+};
+
+/// Error yielded when an invalid set of keyframes is given during the creation
+/// of a [`Schedule`].
+#[derive(Debug, Clone)]
+pub struct InvalidSchedule {
+    /// Given hours of the keyframes, divided por 24h (expected in the interval
+    /// `[0,1)`).
+    pub times: Vec<f64>,
+}
+
+impl fmt::Display for InvalidSchedule {
+    fn fmt(&self, fmtr: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            fmtr,
+            "Invalid schedule, expected at least one keyframe, with distinct \
+             hours; on an interval [0.0, 1.0), given keyframe hours: {:?}",
+            self.times
+        )
+    }
+}
+
+impl Error for InvalidSchedule {}
+
+impl RejectedValue for InvalidSchedule {}
+
+/// Error yielded when an invalid color target is parsed.
+#[derive(Debug, Clone)]
+pub struct InvalidColorTarget {
+    /// The given (invalid) target.
+    pub target: String,
+}
+
+impl fmt::Display for InvalidColorTarget {
+    fn fmt(&self, fmtr: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            fmtr,
+            "Invalid color target, expected red:green:blue, with each channel \
+             in the interval [0.0, 1.0], or KELVINK, with KELVIN in the \
+             interval [{}, {}], given: {}",
+            MIN_TEMPERATURE, MAX_TEMPERATURE, self.target
+        )
+    }
+}
+
+impl Error for InvalidColorTarget {}
+
+/// A value that can be interpolated between keyframes of a [`Schedule`].
+pub trait Interpolate: Copy {
+    /// Interpolates from this value to another one, given the progress in the
+    /// interval `[0,1]`.
+    fn interpolate(self, other: Self, scale: f64) -> Self;
+}
+
+impl Interpolate for f64 {
+    fn interpolate(self, other: Self, scale: f64) -> Self {
+        self + (other - self) * scale
+    }
+}
+
+/// A target of the gamma color spectrum.
+#[derive(Debug, Clone, Copy, PartialEq, PartialOrd)]
+pub enum ColorTarget {
+    /// Gamma of the color channels, in the order: red, green, blue.
+    Gamma([f64; 3]),
+    /// Color temperature, in Kelvin.
+    Temperature(f64),
+}
+
+impl ColorTarget {
+    /// Creates a target of the gamma of the color channels, in the order:
+    /// red, green, blue, each of which must be in the interval `[0,1]`.
+    pub fn channels(gamma: [f64; 3]) -> Result<Self, InvalidColorTarget> {
+        if gamma.iter().all(|channel| (0.0..=1.0).contains(channel)) {
+            Ok(Self::Gamma(gamma))
+        } else {
+            let [red, green, blue] = gamma;
+            let target = format!("{}:{}:{}", red, green, blue);
+            Err(InvalidColorTarget { target })
+        }
+    }
+
+    /// Creates a color temperature target, given the temperature in Kelvin,
+    /// which must be in the supported range of temperatures.
+    pub fn temperature(temperature: f64) -> Result<Self, InvalidColorTarget> {
+        if (MIN_TEMPERATURE..=MAX_TEMPERATURE).contains(&temperature) {
+            Ok(Self::Temperature(temperature))
+        } else {
+            Err(InvalidColorTarget { target: format!("{}K", temperature) })
+        }
+    }
+
+    /// Gamma of the color channels corresponding to this target, in the order:
+    /// red, green, blue.
+    pub fn gamma(self) -> [f64; 3] {
+        match self {
+            Self::Gamma(gamma) => gamma,
+            Self::Temperature(temperature) => temperature_to_gamma(temperature),
+        }
+    }
+}
+
+impl fmt::Display for ColorTarget {
+    fn fmt(&self, fmtr: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Gamma([red, green, blue]) => {
+                write!(fmtr, "{}:{}:{}", red, green, blue)
+            },
+            Self::Temperature(temperature) => write!(fmtr, "{}K", temperature),
+        }
+    }
+}
+
+impl FromStr for ColorTarget {
+    type Err = InvalidColorTarget;
+
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        let error = || InvalidColorTarget { target: input.to_owned() };
+        if let Some(temperature) = input.strip_suffix(['K', 'k']) {
+            let temperature = temperature.parse().map_err(|_| error())?;
+            return Self::temperature(temperature).map_err(|_| error());
+        }
+        let channels = input
+            .split(':')
+            .map(|channel| channel.parse().map_err(|_| error()))
+            .collect::<Result<Vec<_>, _>>()?;
+        match channels[..] {
+            [red, green, blue] => {
+                Self::channels([red, green, blue]).map_err(|_| error())
+            },
+            _ => Err(error()),
+        }
+    }
+}
+
+impl Interpolate for ColorTarget {
+    /// Two temperatures are interpolated in the temperature space, otherwise
+    /// color channels are interpolated.
+    fn interpolate(self, other: Self, scale: f64) -> Self {
+        match (self, other) {
+            (Self::Temperature(start), Self::Temperature(end)) => {
+                Self::Temperature(start.interpolate(end, scale))
+            },
+            _ => {
+                let mut gamma = self.gamma();
+                for (channel, target) in gamma.iter_mut().zip(other.gamma()) {
+                    *channel = channel.interpolate(target, scale);
+                }
+                Self::Gamma(gamma)
+            },
+        }
+    }
+}
+
+/// A point in the day where the gamma color spectrum (or another value)
+/// reaches a given target.
+#[derive(Debug, Clone, Copy, PartialEq, PartialOrd)]
+pub struct Keyframe<T = ColorTarget> {
+    /// Hour of the keyframe, compressed `24h` into the interval `[0,1)`.
+    pub time: f64,
+    /// Target of the color spectrum (or of another value).
+    pub target: T,
+}
+
+/// A schedule of the gamma color spectrum (or of another value) made of
+/// keyframes along the day, interpolating between consecutive keyframes and
+/// wrapping around `24h`.
+#[derive(Debug, Clone, PartialEq, PartialOrd)]
+pub struct Schedule<T = ColorTarget> {
+    keyframes: Vec<Keyframe<T>>,
+    curve: TransitionCurve,
+}
+
+impl<T> Schedule<T>
+where
+    T: Interpolate,
+{
+    /// Creates a schedule from the given keyframes, in any order, linearly
+    /// interpolated. At least one keyframe must be given, and their hours must
+    /// be distinct and in the interval `[0,1)`.
+    pub fn new(
+        mut keyframes: Vec<Keyframe<T>>,
+    ) -> Result<Self, InvalidSchedule> {
+        keyframes.sort_by(|left, right| left.time.total_cmp(&right.time));
+        let out_of_range = keyframes
+            .iter()
+            .any(|keyframe| !(0.0..1.0).contains(&keyframe.time));
+        let duplicated =
+            keyframes.windows(2).any(|pair| pair[0].time == pair[1].time);
+        if keyframes.is_empty() || out_of_range || duplicated {
+            Err(InvalidSchedule {
+                times: keyframes.iter().map(|keyframe| keyframe.time).collect(),
+            })
+        } else {
+            Ok(Self { keyframes, curve: TransitionCurve::Linear })
+        }
+    }
+
+    /// Creates a schedule from day phases and the targets of the night and of
+    /// the day, such that the day target is reached during the day, the night
+    /// target is reached during the night, and they are interpolated during
+    /// the dawn and the dusk.
+    pub fn from_hours(
+        hour_config: HourConfig,
+        night: T,
+        day: T,
+    ) -> Self {
+        // Inserted in the cycle order, which wraps around 24h at most once.
+        // Starting from the keyframe after the wrap sorts them by hour, while
+        // phases starting at the same hour keep the cycle order, even across
+        // midnight, unlike a sort by hour.
+        let mut keyframes = vec![
+            Keyframe { time: hour_config.dawn_start(), target: night },
+            Keyframe { time: hour_config.day_start(), target: day },
+            Keyframe { time: hour_config.dusk_start(), target: day },
+            Keyframe { time: hour_config.night_start(), target: night },
+        ];
+        let wrap = (1..keyframes.len())
+            .find(|&i| keyframes[i].time < keyframes[i - 1].time)
+            .unwrap_or(0);
+        keyframes.rotate_left(wrap);
+        Self { keyframes, curve: TransitionCurve::Linear }
+    }
+
+    /// Creates a schedule where the target is the same along the whole day.
+    pub fn constant(target: T) -> Self {
+        Self {
+            keyframes: vec![Keyframe { time: 0.0, target }],
+            curve: TransitionCurve::Linear,
+        }
+    }
+
+    /// Replaces the curve used to interpolate between consecutive keyframes.
+    pub fn with_curve(mut self, curve: TransitionCurve) -> Self {
+        self.curve = curve;
+        self
+    }
+
+    /// Keyframes of this schedule, sorted by their hour.
+    pub fn keyframes(&self) -> &[Keyframe<T>] {
+        &self.keyframes
+    }
+
+    /// Curve used to interpolate between consecutive keyframes.
+    pub fn curve(&self) -> TransitionCurve {
+        self.curve
+    }
+
+    /// Computes the target given the current hour compressed in the interval
+    /// `[0,1)` (where `1 = 24h`).
+    pub fn target_at(&self, current_hour: f64) -> T {
+        let count = self.keyframes.len();
+        let index = self
+            .keyframes
+            .iter()
+            .rposition(|keyframe| keyframe.time <= current_hour)
+            .unwrap_or(count - 1);
+        let start = self.keyframes[index];
+        let end = self.keyframes[(index + 1) % count];
+        let span = cycle_distance(start.time, end.time);
+        if span <= 0.0 {
+            return start.target;
+        }
+        let scale =
+            self.curve.apply(cycle_distance(start.time, current_hour) / span);
+        start.target.interpolate(end.target, scale)
+    }
+}
+
+impl Schedule {
+    /// Computes the gamma of the color channels given the current hour
+    /// compressed in the interval `[0,1)` (where `1 = 24h`).
+    pub fn gamma_at(&self, current_hour: f64) -> [f64; 3] {
+        self.target_at(current_hour).gamma()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        channel::{linear_channel_function, map_channel_vector},
+        config::{ChannelConfig, HourConfig},
+        temperature::temperature_to_gamma,
+    };
+
+    use super::{ColorTarget, Interpolate, Keyframe, Schedule};
+
+    const EPSILON: f64 = 0.01;
+
+    fn keyframe(time: f64, gamma: [f64; 3]) -> Keyframe {
+        Keyframe { time, target: ColorTarget::Gamma(gamma) }
+    }
+
+    #[test]
+    fn error_when_keyframes_are_invalid() {
+        Schedule::<f64>::new(Vec::new()).unwrap_err();
+        Schedule::new(vec![keyframe(1.5, [1.0; 3])]).unwrap_err();
+        Schedule::new(vec![keyframe(0.5, [1.0; 3]), keyframe(0.5, [0.5; 3])])
+            .unwrap_err();
+    }
+
+    #[test]
+    fn from_hours_matches_linear_channel_function() {
+        let channels = [
+            ChannelConfig::new(1.0, 1.0).unwrap(),
+            ChannelConfig::new(0.6, 1.0).unwrap(),
+            ChannelConfig::new(0.3, 0.9).unwrap(),
+        ];
+        let min = map_channel_vector(channels, ChannelConfig::min);
+        let max = map_channel_vector(channels, ChannelConfig::max);
+        let schedule = Schedule::from_hours(
+            HourConfig::default(),
+            ColorTarget::Gamma(min),
+            ColorTarget::Gamma(max),
+        );
+        for hour in [0.5, 4.5, 6.0, 12.0, 17.0, 19.0, 21.5, 23.9] {
+            let gamma = schedule.gamma_at(hour / 24.0);
+            for (channel, config) in gamma.into_iter().zip(channels) {
+                let expected = linear_channel_function(
+                    config,
+                    HourConfig::default(),
+                )(hour / 24.0);
+                assert!((channel - expected).abs() < EPSILON);
+            }
+        }
+    }
+
+    #[test]
+    fn from_hours_keeps_cycle_order_of_wrapped_ties() {
+        // Night and dawn start along with the day, after a dusk across
+        // midnight.
+        let (six, twenty) = (6.0 / 24.0, 20.0 / 24.0);
+        let hours = HourConfig::new(six, twenty, six, six).unwrap();
+        let schedule = Schedule::from_hours(hours, 0.0, 1.0);
+        let times = schedule
+            .keyframes()
+            .iter()
+            .map(|keyframe| keyframe.time)
+            .collect::<Vec<_>>();
+        assert_eq!(times, [six, six, six, twenty]);
+        assert!((schedule.target_at(12.0 / 24.0) - 1.0).abs() < EPSILON);
+        assert!((schedule.target_at(6.0 / 24.0) - 1.0).abs() < EPSILON);
+        assert!((schedule.target_at(1.0 / 24.0) - 0.5).abs() < EPSILON);
+    }
+
+    #[test]
+    fn interpolates_wrapping_around_midnight() {
+        let schedule = Schedule::new(vec![
+            keyframe(13.0 / 24.0, [1.0, 0.9, 0.9]),
+            keyframe(22.0 / 24.0, [1.0, 0.7, 0.5]),
+            keyframe(1.0 / 24.0, [1.0, 0.5, 0.3]),
+        ])
+        .unwrap();
+
+        let gamma = schedule.gamma_at(23.5 / 24.0);
+        assert!((gamma[1] - 0.6).abs() < EPSILON);
+        assert!((gamma[2] - 0.4).abs() < EPSILON);
+
+        let gamma = schedule.gamma_at(7.0 / 24.0);
+        assert!((gamma[1] - 0.7).abs() < EPSILON);
+        assert!((gamma[2] - 0.6).abs() < EPSILON);
+
+        let gamma = schedule.gamma_at(0.0);
+        assert!(gamma[2] > 0.3 + EPSILON && gamma[2] < 0.4);
+    }
+
+    #[test]
+    fn single_keyframe_is_constant() {
+        let schedule =
+            Schedule::new(vec![keyframe(0.25, [1.0, 0.8, 0.6])]).unwrap();
+        assert_eq!(schedule.gamma_at(0.1), [1.0, 0.8, 0.6]);
+        assert_eq!(schedule.gamma_at(0.9), [1.0, 0.8, 0.6]);
+    }
+
+    #[test]
+    fn interpolates_temperatures_in_temperature_space() {
+        let schedule = Schedule::from_hours(
+            HourConfig::default(),
+            ColorTarget::Temperature(3400.0),
+            ColorTarget::Temperature(6500.0),
+        );
+        match schedule.target_at(19.0 / 24.0) {
+            ColorTarget::Temperature(temperature) => {
+                assert!((temperature - 4950.0).abs() < EPSILON)
+            },
+            target => panic!("Expected temperature, found {:?}", target),
+        }
+        let gamma = schedule.gamma_at(19.0 / 24.0);
+        let expected = temperature_to_gamma(4950.0);
+        for (channel, expected) in gamma.into_iter().zip(expected) {
+            assert!((channel - expected).abs() < EPSILON);
+        }
+    }
+
+    #[test]
+    fn mixed_targets_interpolate_channels() {
+        let target = ColorTarget::Temperature(6500.0)
+            .interpolate(ColorTarget::Gamma([1.0, 0.6, 0.4]), 0.5);
+        match target {
+            ColorTarget::Gamma(gamma) => {
+                assert!((gamma[1] - 0.8).abs() < EPSILON);
+                assert!((gamma[2] - 0.7).abs() < EPSILON);
+            },
+            target => panic!("Expected gamma, found {:?}", target),
+        }
+    }
+
+    #[test]
+    fn parse_color_targets() {
+        assert_eq!(
+            "1.0:0.9:0.9".parse::<ColorTarget>().unwrap(),
+            ColorTarget::Gamma([1.0, 0.9, 0.9]),
+        );
+        assert_eq!(
+            "3400K".parse::<ColorTarget>().unwrap(),
+            ColorTarget::Temperature(3400.0),
+        );
+        "50K".parse::<ColorTarget>().unwrap_err();
+        "-3400K".parse::<ColorTarget>().unwrap_err();
+        "NaNK".parse::<ColorTarget>().unwrap_err();
+        "1.0:0.9".parse::<ColorTarget>().unwrap_err();
+        "nan:-3:5".parse::<ColorTarget>().unwrap_err();
+        "1.0:1.4:1.0".parse::<ColorTarget>().unwrap_err();
+    }
+
+    #[test]
+    fn schedule_of_other_values() {
+        let schedule = Schedule::from_hours(HourConfig::default(), 0.7, 1.0);
+        assert!((schedule.target_at(12.0 / 24.0) - 1.0).abs() < EPSILON);
+        assert!((schedule.target_at(19.0 / 24.0) - 0.85).abs() < EPSILON);
+        assert!((schedule.target_at(2.0 / 24.0) - 0.7).abs() < EPSILON);
+    }
+}
@@ -0,0 +1,84 @@
+//! Handling of signals asking the process to terminate, so that the screen
+//! colors can be restored before exiting.
+
+use std::{io, sync::mpsc::Sender};
+
+/// Waits for SIGINT, SIGTERM and SIGHUP in a background thread, sending a
+/// message made by the given function through the given sender every time one
+/// of them is received, instead of terminating the process.
+pub fn forward_termination<T, F>(
+    sender: Sender<T>,
+    message: F,
+) -> io::Result<()>
+where
+    T: Send + 'static,
+    F: FnMut() -> T + Send + 'static,
+{
+    os::forward_termination(sender, message)
+}
+
+#[cfg(unix)]
+mod os {
+    use std::{io, sync::mpsc::Sender, thread};
+
+    use signal_hook::{
+        consts::{SIGHUP, SIGINT, SIGTERM},
+        iterator::Signals,
+    };
+
+    pub fn forward_termination<T, F>(
+        sender: Sender<T>,
+        mut message: F,
+    ) -> io::Result<()>
+    where
+        T: Send + 'static,
+        F: FnMut() -> T + Send + 'static,
+    {
+        let mut signals = Signals::new([SIGINT, SIGTERM, SIGHUP])?;
+        thread::spawn(move || {
+            for _ in signals.forever() {
+                if sender.send(message()).is_err() {
+                    break;
+                }
+            }
+        });
+        Ok(())
+    }
+}
+
+#[cfg(not(unix))]
+mod os {
+    use std::{io, sync::mpsc::Sender};
+
+    pub fn forward_termination<T, F>(
+        _sender: Sender<T>,
+        _message: F,
+    ) -> io::Result<()>
+    where
+        T: Send + 'static,
+        F: FnMut() -> T + Send + 'static,
+    {
+        Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "handling signals is not supported in your platform",
+        ))
+    }
+}
+
+#[cfg(all(test, unix))]
+mod test {
+    use std::{sync::mpsc, time::Duration};
+
+    use signal_hook::{consts::SIGHUP, low_level};
+
+    use super::forward_termination;
+
+    #[test]
+    fn forward_signals_as_messages() {
+        let (sender, receiver) = mpsc::channel();
+        forward_termination(sender, || "terminate").unwrap();
+        low_level::raise(SIGHUP).unwrap();
+        let message = receiver.recv_timeout(Duration::from_secs(5)).unwrap();
+        assert_eq!(message, "terminate");
+    }
+}
@@ -0,0 +1,403 @@
+//! Utilities related to the position of the sun, such as sunrise, sunset and
+//! twilights, for a given location on Earth and a given date.
+
+use std::{error::Error, fmt, str::FromStr};
+
+use chrono::{DateTime, FixedOffset, NaiveDate, TimeZone, Utc};
+
+use crate::config::RejectedValue;
+
+/// Julian day of the J2000 epoch (January 1st, 2000, at 12:00 UTC).
+const J2000: f64 = 2451545.0;
+
+/// Obliquity of the Earth's axis, in degrees.
+const OBLIQUITY: f64 = 23.4397;
+
+/// Elevation of the center of the sun at sunrise and sunset, in degrees,
+/// accounting for atmospheric refraction and the sun's apparent radius.
+pub const HORIZON_ELEVATION: f64 = -0.833;
+
+/// Error yielded when an invalid location is given during the creation of a
+/// [`Location`].
+#[derive(Debug, Clone)]
+pub struct InvalidLocation {
+    /// Given latitude, in degrees (expected in the interval `[-90,90]`).
+    pub latitude: f64,
+    /// Given longitude, in degrees (expected in the interval `[-180,180]`).
+    pub longitude: f64,
+}
+
+impl fmt::Display for InvalidLocation {
+    fn fmt(&self, fmtr: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            fmtr,
+            "Invalid location, expected latitude in the interval [-90, 90] \
+             and longitude in the interval [-180, 180], given latitude: {}, \
+             longitude: {}",
+            self.latitude, self.longitude
+        )
+    }
+}
+
+impl Error for InvalidLocation {}
+
+/// Values are in the order: latitude, longitude.
+impl RejectedValue for InvalidLocation {
+    fn rejects(&self, index: usize) -> bool {
+        match index {
+            0 => !(-90.0..=90.0).contains(&self.latitude),
+            _ => !(-180.0..=180.0).contains(&self.longitude),
+        }
+    }
+}
+
+/// Error yielded when an invalid twilight kind is parsed.
+#[derive(Debug, Clone)]
+pub struct InvalidTwilight {
+    /// The given (invalid) twilight.
+    pub twilight: String,
+}
+
+impl fmt::Display for InvalidTwilight {
+    fn fmt(&self, fmtr: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            fmtr,
+            "Invalid twilight, expected civil or nautical, given: {}",
+            self.twilight
+        )
+    }
+}
+
+impl Error for InvalidTwilight {}
+
+/// A location on Earth.
+#[derive(Debug, Clone, Copy, PartialEq, PartialOrd)]
+pub struct Location {
+    latitude: f64,
+    longitude: f64,
+}
+
+impl Location {
+    /// Creates a location given its latitude (positive to the north) and its
+    /// longitude (positive to the east), both in degrees.
+    pub fn new(latitude: f64, longitude: f64) -> Result<Self, InvalidLocation> {
+        if (-90.0..=90.0).contains(&latitude)
+            && (-180.0..=180.0).contains(&longitude)
+        {
+            Ok(Self { latitude, longitude })
+        } else {
+            Err(InvalidLocation { latitude, longitude })
+        }
+    }
+
+    /// Latitude in degrees, positive to the north.
+    pub fn latitude(self) -> f64 {
+        self.latitude
+    }
+
+    /// Longitude in degrees, positive to the east.
+    pub fn longitude(self) -> f64 {
+        self.longitude
+    }
+}
+
+/// A kind of twilight, i.e. the period in which the sun is below the horizon,
+/// but above some elevation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default)]
+pub enum Twilight {
+    /// Twilight with the sun above `6°` below the horizon.
+    #[default]
+    Civil,
+    /// Twilight with the sun above `12°` below the horizon.
+    Nautical,
+}
+
+impl Twilight {
+    /// Elevation of the sun, in degrees, where this twilight ends.
+    pub fn elevation(self) -> f64 {
+        match self {
+            Self::Civil => -6.0,
+            Self::Nautical => -12.0,
+        }
+    }
+}
+
+impl fmt::Display for Twilight {
+    fn fmt(&self, fmtr: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Civil => fmtr.pad("civil"),
+            Self::Nautical => fmtr.pad("nautical"),
+        }
+    }
+}
+
+impl FromStr for Twilight {
+    type Err = InvalidTwilight;
+
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        match input.trim().to_lowercase().as_str() {
+            "civil" => Ok(Self::Civil),
+            "nautical" => Ok(Self::Nautical),
+            _ => Err(InvalidTwilight { twilight: input.to_owned() }),
+        }
+    }
+}
+
+/// How the sun crosses a given elevation along a day.
+#[derive(Debug, Clone, Copy, PartialEq, PartialOrd)]
+pub enum Crossing {
+    /// The sun goes above the elevation at `rise` and below it at `set`, both
+    /// local hours compressed in the interval `[0,1)`.
+    Times {
+        /// Hour in which the sun goes above the elevation.
+        rise: f64,
+        /// Hour in which the sun goes below the elevation.
+        set: f64,
+    },
+    /// The sun never goes below the elevation (e.g. polar day).
+    AlwaysAbove,
+    /// The sun never goes above the elevation (e.g. polar night).
+    AlwaysBelow,
+}
+
+/// Solar events of a given day at a given location.
+#[derive(Debug, Clone, Copy, PartialEq, PartialOrd)]
+pub struct SolarEvents {
+    /// Local hour in which the sun is at its highest, compressed in the
+    /// interval `[0,1)`.
+    pub noon: f64,
+    /// Sunrise and sunset.
+    pub sun: Crossing,
+    /// Start of the civil dawn and end of the civil dusk.
+    pub civil: Crossing,
+    /// Start of the nautical dawn and end of the nautical dusk.
+    pub nautical: Crossing,
+}
+
+impl SolarEvents {
+    /// Computes the solar events at the given location, for the given date,
+    /// with hours in the local time given by the offset from UTC.
+    pub fn compute(
+        location: Location,
+        date: NaiveDate,
+        utc_offset: FixedOffset,
+    ) -> Self {
+        let position = SolarDay::new(location, date);
+        let local = |julian: f64| {
+            let utc_hour = (julian + 0.5).rem_euclid(1.0);
+            let offset = f64::from(utc_offset.local_minus_utc()) / 86400.0;
+            (utc_hour + offset).rem_euclid(1.0)
+        };
+        let crossing = |elevation: f64| match position.hour_angle(elevation) {
+            Some(hour_angle) => Crossing::Times {
+                rise: local(position.transit - hour_angle / 360.0),
+                set: local(position.transit + hour_angle / 360.0),
+            },
+            None if position.max_elevation() < elevation => {
+                Crossing::AlwaysBelow
+            },
+            None => Crossing::AlwaysAbove,
+        };
+        Self {
+            noon: local(position.transit),
+            sun: crossing(HORIZON_ELEVATION),
+            civil: crossing(Twilight::Civil.elevation()),
+            nautical: crossing(Twilight::Nautical.elevation()),
+        }
+    }
+
+    /// Start of the dawn and end of the dusk of the given twilight kind.
+    pub fn twilight(&self, twilight: Twilight) -> Crossing {
+        match twilight {
+            Twilight::Civil => self.civil,
+            Twilight::Nautical => self.nautical,
+        }
+    }
+}
+
+/// Computes the elevation of the sun, in degrees, at the given location and at
+/// the given instant. Positive values are above the horizon.
+pub fn elevation<Tz>(location: Location, time: &DateTime<Tz>) -> f64
+where
+    Tz: TimeZone,
+{
+    let epoch = Utc
+        .with_ymd_and_hms(2000, 1, 1, 12, 0, 0)
+        .single()
+        .expect("valid date");
+    let elapsed = time.with_timezone(&Utc) - epoch;
+    let days = elapsed.num_milliseconds() as f64 / 86_400_000.0;
+    let (_, ecliptic_longitude) = ecliptic_coordinates(days);
+    let obliquity = OBLIQUITY.to_radians();
+    let declination = (ecliptic_longitude.sin() * obliquity.sin()).asin();
+    let right_ascension = (ecliptic_longitude.sin() * obliquity.cos())
+        .atan2(ecliptic_longitude.cos());
+    let sidereal_time =
+        (280.16 + 360.9856235 * days + location.longitude).to_radians();
+    let hour_angle = sidereal_time - right_ascension;
+    let latitude = location.latitude.to_radians();
+    (latitude.sin() * declination.sin()
+        + latitude.cos() * declination.cos() * hour_angle.cos())
+    .asin()
+    .to_degrees()
+}
+
+/// Computes the mean anomaly and the ecliptic longitude of the sun, both in
+/// radians, given the days since the J2000 epoch.
+fn ecliptic_coordinates(days: f64) -> (f64, f64) {
+    let anomaly = (357.5291 + 0.98560028 * days).rem_euclid(360.0);
+    let anomaly_rad = anomaly.to_radians();
+    let center = 1.9148 * anomaly_rad.sin()
+        + 0.02 * (2.0 * anomaly_rad).sin()
+        + 0.0003 * (3.0 * anomaly_rad).sin();
+    let ecliptic_longitude = (anomaly + center + 180.0 + 102.9372)
+        .rem_euclid(360.0)
+        .to_radians();
+    (anomaly_rad, ecliptic_longitude)
+}
+
+/// Position of the sun along a day, using the sunrise equation.
+#[derive(Debug, Clone, Copy)]
+struct SolarDay {
+    latitude: f64,
+    declination: f64,
+    transit: f64,
+}
+
+impl SolarDay {
+    fn new(location: Location, date: NaiveDate) -> Self {
+        let epoch = NaiveDate::from_ymd_opt(2000, 1, 1).expect("valid date");
+        let days = (date - epoch).num_days() as f64;
+        let mean_solar_time = days + 0.0008 - location.longitude / 360.0;
+        let (anomaly, ecliptic_longitude) =
+            ecliptic_coordinates(mean_solar_time);
+        let transit = J2000 + mean_solar_time + 0.0053 * anomaly.sin()
+            - 0.0069 * (2.0 * ecliptic_longitude).sin();
+        let declination = (ecliptic_longitude.sin()
+            * OBLIQUITY.to_radians().sin())
+        .asin();
+        Self { latitude: location.latitude.to_radians(), declination, transit }
+    }
+
+    /// Maximum elevation of the sun along the day, in degrees.
+    fn max_elevation(self) -> f64 {
+        90.0 - (self.latitude - self.declination).abs().to_degrees()
+    }
+
+    /// Hour angle, in degrees, in which the sun crosses the given elevation,
+    /// if it crosses it at all.
+    fn hour_angle(self, elevation: f64) -> Option<f64> {
+        let cosine = (elevation.to_radians().sin()
+            - self.latitude.sin() * self.declination.sin())
+            / (self.latitude.cos() * self.declination.cos());
+        if (-1.0..=1.0).contains(&cosine) {
+            Some(cosine.acos().to_degrees())
+        } else {
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use chrono::{FixedOffset, NaiveDate, TimeZone, Utc};
+
+    use super::{elevation, Crossing, Location, SolarEvents};
+
+    /// Five minutes, compressed in the interval `[0,1)`.
+    const EPSILON: f64 = 5.0 / (24.0 * 60.0);
+
+    fn hour(hours: f64, minutes: f64) -> f64 {
+        (hours + minutes / 60.0) / 24.0
+    }
+
+    #[test]
+    fn invalid_location() {
+        Location::new(91.0, 0.0).unwrap_err();
+        Location::new(0.0, -181.0).unwrap_err();
+    }
+
+    #[test]
+    fn london_at_summer_solstice() {
+        let events = SolarEvents::compute(
+            Location::new(51.5074, -0.1278).unwrap(),
+            NaiveDate::from_ymd_opt(2024, 6, 21).unwrap(),
+            FixedOffset::east_opt(3600).unwrap(),
+        );
+        match events.sun {
+            Crossing::Times { rise, set } => {
+                assert!((rise - hour(4.0, 43.0)).abs() < EPSILON);
+                assert!((set - hour(21.0, 21.0)).abs() < EPSILON);
+            },
+            crossing => panic!("Expected times, found {:?}", crossing),
+        }
+        match events.civil {
+            Crossing::Times { rise, set } => {
+                assert!((rise - hour(3.0, 56.0)).abs() < EPSILON);
+                assert!((set - hour(22.0, 8.0)).abs() < EPSILON);
+            },
+            crossing => panic!("Expected times, found {:?}", crossing),
+        }
+        assert!(matches!(events.nautical, Crossing::Times { .. }));
+        assert!((events.noon - hour(13.0, 2.0)).abs() < EPSILON);
+    }
+
+    #[test]
+    fn sao_paulo_at_summer_solstice() {
+        let events = SolarEvents::compute(
+            Location::new(-23.5505, -46.6333).unwrap(),
+            NaiveDate::from_ymd_opt(2024, 12, 21).unwrap(),
+            FixedOffset::west_opt(3 * 3600).unwrap(),
+        );
+        match events.sun {
+            Crossing::Times { rise, set } => {
+                assert!((rise - hour(5.0, 16.0)).abs() < EPSILON);
+                assert!((set - hour(18.0, 54.0)).abs() < EPSILON);
+            },
+            crossing => panic!("Expected times, found {:?}", crossing),
+        }
+    }
+
+    #[test]
+    fn polar_day_and_night() {
+        let tromso = Location::new(69.6492, 18.9553).unwrap();
+        let offset = FixedOffset::east_opt(3600).unwrap();
+
+        let summer = SolarEvents::compute(
+            tromso,
+            NaiveDate::from_ymd_opt(2024, 6, 21).unwrap(),
+            offset,
+        );
+        assert_eq!(summer.sun, Crossing::AlwaysAbove);
+        assert_eq!(summer.civil, Crossing::AlwaysAbove);
+
+        let winter = SolarEvents::compute(
+            tromso,
+            NaiveDate::from_ymd_opt(2024, 12, 21).unwrap(),
+            offset,
+        );
+        assert_eq!(winter.sun, Crossing::AlwaysBelow);
+        assert!(matches!(winter.civil, Crossing::Times { .. }));
+
+        let svalbard = SolarEvents::compute(
+            Location::new(78.2232, 15.6267).unwrap(),
+            NaiveDate::from_ymd_opt(2024, 12, 21).unwrap(),
+            offset,
+        );
+        assert_eq!(svalbard.civil, Crossing::AlwaysBelow);
+    }
+
+    #[test]
+    fn elevation_in_london() {
+        let london = Location::new(51.5074, -0.1278).unwrap();
+        let noon = Utc.with_ymd_and_hms(2024, 6, 21, 12, 2, 0).unwrap();
+        assert!((elevation(london, &noon) - 61.9).abs() < 0.5);
+        let midnight = Utc.with_ymd_and_hms(2024, 6, 21, 0, 2, 0).unwrap();
+        assert!((elevation(london, &midnight) + 15.0).abs() < 0.5);
+        let sunset = FixedOffset::east_opt(3600)
+            .unwrap()
+            .with_ymd_and_hms(2024, 6, 21, 21, 21, 0)
+            .unwrap();
+        assert!((elevation(london, &sunset) + 0.833).abs() < 0.5);
+    }
+}
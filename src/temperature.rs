@@ -0,0 +1,126 @@
+//! Utilities related to color temperatures, in Kelvin.
+
+use crate::channel::{self, map_channel_vector};
+
+/// Minimum supported color temperature, in Kelvin.
+pub const MIN_TEMPERATURE: f64 = 1000.0;
+/// Maximum supported color temperature, in Kelvin.
+pub const MAX_TEMPERATURE: f64 = 25000.0;
+/// Color temperature of the usual screen white point (D65), which yields full
+/// intensity for all of the color channels, in Kelvin.
+pub const NEUTRAL_TEMPERATURE: f64 = 6500.0;
+
+/// Computes the color of a black body at the given temperature in Kelvin, as
+/// color channels in the interval `[0,1]`, in the order: red, green, blue.
+/// This is an approximation fitted to the CIE 1964 color matching functions,
+/// not normalized to any white point.
+pub fn blackbody(temperature: f64) -> [f64; 3] {
+    let temperature =
+        temperature.clamp(MIN_TEMPERATURE, MAX_TEMPERATURE) / 100.0;
+    let red = if temperature <= 66.0 {
+        255.0
+    } else {
+        329.698727446 * (temperature - 60.0).powf(-0.1332047592)
+    };
+    let green = if temperature <= 66.0 {
+        99.4708025861 * temperature.ln() - 161.1195681661
+    } else {
+        288.1221695283 * (temperature - 60.0).powf(-0.0755148492)
+    };
+    let blue = if temperature >= 66.0 {
+        255.0
+    } else if temperature <= 19.0 {
+        0.0
+    } else {
+        138.5177312231 * (temperature - 10.0).ln() - 305.0447927307
+    };
+    map_channel_vector([red, green, blue], |channel| {
+        (channel / 255.0).clamp(0.0, 1.0)
+    })
+}
+
+/// Converts a color temperature in Kelvin to the gamma of the color channels,
+/// in the order: red, green, blue, such that the neutral temperature yields
+/// `1.0` for all channels, and warmer temperatures reduce green and blue.
+pub fn temperature_to_gamma(temperature: f64) -> [f64; 3] {
+    let color = blackbody(temperature);
+    let white_point = blackbody(NEUTRAL_TEMPERATURE);
+    let mut gamma = [0.0; 3];
+    for ((channel, value), white) in
+        gamma.iter_mut().zip(color).zip(white_point)
+    {
+        *channel = (value / white).clamp(0.0, 1.0);
+    }
+    gamma
+}
+
+/// Finds the color temperature in Kelvin, between the minimum and the
+/// neutral temperatures, whose gamma best matches the given gamma of the color
+/// channels, relative to the red channel. This is the inverse of
+/// [`temperature_to_gamma`] for gamma it produces.
+pub fn gamma_to_temperature(gamma: [f64; 3]) -> f64 {
+    let red = gamma[channel::RED].max(f64::EPSILON);
+    let error = |temperature: f64| {
+        let candidate = temperature_to_gamma(temperature);
+        [channel::GREEN, channel::BLUE]
+            .into_iter()
+            .map(|i| (candidate[i] - gamma[i] / red).powi(2))
+            .sum::<f64>()
+    };
+    let mut best = (NEUTRAL_TEMPERATURE, error(NEUTRAL_TEMPERATURE));
+    let mut temperature = MIN_TEMPERATURE;
+    while temperature < NEUTRAL_TEMPERATURE {
+        let candidate = (temperature, error(temperature));
+        if candidate.1 < best.1 {
+            best = candidate;
+        }
+        temperature += 10.0;
+    }
+    best.0
+}
+
+#[cfg(test)]
+mod test {
+    use super::{
+        gamma_to_temperature,
+        temperature_to_gamma,
+        NEUTRAL_TEMPERATURE,
+    };
+
+    const EPSILON: f64 = 0.02;
+
+    #[test]
+    fn neutral_temperature_is_white() {
+        let gamma = temperature_to_gamma(NEUTRAL_TEMPERATURE);
+        for channel in gamma {
+            assert!((channel - 1.0).abs() < EPSILON);
+        }
+    }
+
+    #[test]
+    fn warm_temperature_is_red() {
+        let gamma = temperature_to_gamma(3400.0);
+        assert!((gamma[0] - 1.0).abs() < EPSILON);
+        assert!((gamma[1] - 0.75).abs() < EPSILON);
+        assert!((gamma[2] - 0.54).abs() < EPSILON);
+    }
+
+    #[test]
+    fn warmer_temperatures_reduce_blue_and_green() {
+        let mut previous = temperature_to_gamma(NEUTRAL_TEMPERATURE);
+        for temperature in (1000..6500).step_by(500).rev() {
+            let gamma = temperature_to_gamma(f64::from(temperature));
+            assert!(gamma[1] <= previous[1] && gamma[2] <= previous[2]);
+            previous = gamma;
+        }
+    }
+
+    #[test]
+    fn temperature_from_gamma() {
+        for temperature in [1900.0, 3400.0, 4500.0, NEUTRAL_TEMPERATURE] {
+            let gamma = temperature_to_gamma(temperature);
+            assert!((gamma_to_temperature(gamma) - temperature).abs() <= 10.0);
+        }
+        assert_eq!(gamma_to_temperature([1.0; 3]), NEUTRAL_TEMPERATURE);
+    }
+}
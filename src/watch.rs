@@ -0,0 +1,143 @@
+//! Watching of files for changes, such as the configuration file.
+
+use std::{io, path::Path, sync::mpsc::Sender, thread, time::Duration};
+
+/// Time waited after a change for further changes, so that a file saved in
+/// several steps (as editors often do) yields a single notification.
+const COALESCE_DELAY: Duration = Duration::from_millis(100);
+
+/// Watches the given file for changes in a background thread. Every time the
+/// file is written, created, replaced or removed, a message made by the given
+/// function is sent through the given sender. The parent directory is watched
+/// instead of the file itself, so that editors replacing the file do not break
+/// the watch.
+pub fn watch_file<T, F>(
+    path: &Path,
+    sender: Sender<T>,
+    mut message: F,
+) -> io::Result<()>
+where
+    T: Send + 'static,
+    F: FnMut() -> T + Send + 'static,
+{
+    let directory = match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    };
+    let file_name = path.file_name().ok_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("{} is not a file path", path.display()),
+        )
+    })?;
+    let mut watcher = os::Watcher::new(directory)?;
+    let file_name = file_name.to_owned();
+    thread::spawn(move || loop {
+        match watcher.wait_change(&file_name) {
+            Ok(()) => {
+                thread::sleep(COALESCE_DELAY);
+                watcher.discard_changes();
+                if sender.send(message()).is_err() {
+                    break;
+                }
+            },
+            Err(error) => {
+                eprintln!("stopped watching for changes: {}", error);
+                break;
+            },
+        }
+    });
+    Ok(())
+}
+
+#[cfg(target_os = "linux")]
+mod os {
+    use std::{ffi::OsStr, io, path::Path};
+
+    use inotify::{Inotify, WatchMask};
+
+    pub struct Watcher {
+        inotify: Inotify,
+        buffer: [u8; 4096],
+    }
+
+    impl Watcher {
+        pub fn new(directory: &Path) -> io::Result<Self> {
+            let inotify = Inotify::init()?;
+            inotify.watches().add(
+                directory,
+                WatchMask::CLOSE_WRITE
+                    | WatchMask::CREATE
+                    | WatchMask::MOVED_TO
+                    | WatchMask::DELETE,
+            )?;
+            Ok(Self { inotify, buffer: [0; 4096] })
+        }
+
+        pub fn wait_change(&mut self, file_name: &OsStr) -> io::Result<()> {
+            loop {
+                let events =
+                    self.inotify.read_events_blocking(&mut self.buffer)?;
+                let mut names = events.filter_map(|event| event.name);
+                if names.any(|name| name == file_name) {
+                    break Ok(());
+                }
+            }
+        }
+
+        pub fn discard_changes(&mut self) {
+            while let Ok(mut events) =
+                self.inotify.read_events(&mut self.buffer)
+            {
+                if events.next().is_none() {
+                    break;
+                }
+            }
+        }
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+mod os {
+    use std::{ffi::OsStr, io, path::Path};
+
+    pub struct Watcher {
+        _priv: (),
+    }
+
+    impl Watcher {
+        pub fn new(_directory: &Path) -> io::Result<Self> {
+            Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                "watching files is not supported in your platform",
+            ))
+        }
+
+        pub fn wait_change(&mut self, _file_name: &OsStr) -> io::Result<()> {
+            Err(io::Error::from(io::ErrorKind::Unsupported))
+        }
+
+        pub fn discard_changes(&mut self) {}
+    }
+}
+
+#[cfg(all(test, target_os = "linux"))]
+mod test {
+    use std::{env, fs, process, sync::mpsc, time::Duration};
+
+    use super::watch_file;
+
+    #[test]
+    fn notifies_file_changes() {
+        let directory = env::temp_dir()
+            .join(format!("circadianlight-watch-{}", process::id()));
+        fs::create_dir_all(&directory).unwrap();
+        let path = directory.join("config.toml");
+        let (sender, changes) = mpsc::channel();
+        watch_file(&path, sender, || ()).unwrap();
+        fs::write(directory.join("other.toml"), "").unwrap();
+        fs::write(&path, "curve = \"cosine\"\n").unwrap();
+        changes.recv_timeout(Duration::from_secs(5)).unwrap();
+        fs::remove_dir_all(&directory).unwrap();
+    }
+}